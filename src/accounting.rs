@@ -0,0 +1,463 @@
+//! Reader for PBS accounting logs, `server_priv/accounting/YYYYMMDD`
+use crate::types::{Attribs, Attrl, Op};
+use chrono::{NaiveDate, NaiveDateTime};
+use log::{debug, trace};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+
+/// Type of an accounting record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    /// A: job aborted by the server
+    Aborted,
+    /// B: reservation period began
+    ResvBegin,
+    /// C: job checkpointed and held
+    Checkpointed,
+    /// D: job deleted by request
+    Deleted,
+    /// E: job ended
+    Ended,
+    /// F: reservation period finished
+    ResvFinished,
+    /// K: reservation removed by the scheduler or server
+    ResvRemoved,
+    /// k: reservation removed by its owner
+    ResvDeleted,
+    /// L: license information
+    License,
+    /// M: job moved to another server
+    Moved,
+    /// P: provisioning started
+    ProvisionStart,
+    /// p: provisioning ended
+    ProvisionEnd,
+    /// Q: job queued
+    Queued,
+    /// R: job rerun
+    Rerun,
+    /// S: job started
+    Started,
+    /// T: job restarted from a checkpoint
+    Restarted,
+    /// U: unconfirmed reservation created
+    ResvRequested,
+    /// Y: reservation confirmed
+    ResvConfirmed,
+    /// a: job attributes altered
+    Altered,
+    /// e: resources used update for a running job
+    Update,
+    /// any other record type
+    Other(char),
+}
+
+impl RecordType {
+    pub fn as_char(&self) -> char {
+        match self {
+            RecordType::Aborted => 'A',
+            RecordType::ResvBegin => 'B',
+            RecordType::Checkpointed => 'C',
+            RecordType::Deleted => 'D',
+            RecordType::Ended => 'E',
+            RecordType::ResvFinished => 'F',
+            RecordType::ResvRemoved => 'K',
+            RecordType::ResvDeleted => 'k',
+            RecordType::License => 'L',
+            RecordType::Moved => 'M',
+            RecordType::ProvisionStart => 'P',
+            RecordType::ProvisionEnd => 'p',
+            RecordType::Queued => 'Q',
+            RecordType::Rerun => 'R',
+            RecordType::Started => 'S',
+            RecordType::Restarted => 'T',
+            RecordType::ResvRequested => 'U',
+            RecordType::ResvConfirmed => 'Y',
+            RecordType::Altered => 'a',
+            RecordType::Update => 'e',
+            RecordType::Other(c) => *c,
+        }
+    }
+    /// whether the record's entity is a reservation rather than a job
+    pub fn is_reservation(&self) -> bool {
+        matches!(
+            self,
+            RecordType::ResvBegin
+                | RecordType::ResvFinished
+                | RecordType::ResvRemoved
+                | RecordType::ResvDeleted
+                | RecordType::ResvRequested
+                | RecordType::ResvConfirmed
+        )
+    }
+}
+
+impl From<char> for RecordType {
+    fn from(c: char) -> RecordType {
+        match c {
+            'A' => RecordType::Aborted,
+            'B' => RecordType::ResvBegin,
+            'C' => RecordType::Checkpointed,
+            'D' => RecordType::Deleted,
+            'E' => RecordType::Ended,
+            'F' => RecordType::ResvFinished,
+            'K' => RecordType::ResvRemoved,
+            'k' => RecordType::ResvDeleted,
+            'L' => RecordType::License,
+            'M' => RecordType::Moved,
+            'P' => RecordType::ProvisionStart,
+            'p' => RecordType::ProvisionEnd,
+            'Q' => RecordType::Queued,
+            'R' => RecordType::Rerun,
+            'S' => RecordType::Started,
+            'T' => RecordType::Restarted,
+            'U' => RecordType::ResvRequested,
+            'Y' => RecordType::ResvConfirmed,
+            'a' => RecordType::Altered,
+            'e' => RecordType::Update,
+            c => RecordType::Other(c),
+        }
+    }
+}
+
+/// A single accounting log line
+#[derive(Debug)]
+pub struct AccountingRecord {
+    /// local time the record was written
+    pub time: NaiveDateTime,
+    pub record_type: RecordType,
+    /// job or reservation id, or license for L records
+    pub id: String,
+    /// key=value pairs from the message, Resource_List.x etc become resources
+    pub attribs: Attribs,
+    /// message exactly as logged
+    pub message: String,
+}
+
+impl AccountingRecord {
+    /// value of a plain attribute
+    pub fn get(&self, name: &str) -> Option<String> {
+        match self.attribs.get(name) {
+            Some(Attrl::Value(v)) => Some(v.val()),
+            _ => None,
+        }
+    }
+    /// value of a resource attribute, e.g. resource("resources_used", "cput")
+    pub fn resource(&self, name: &str, resource: &str) -> Option<String> {
+        match self.attribs.get(name) {
+            Some(Attrl::Resource(map)) => map.get(resource).map(|v| v.val()),
+            _ => None,
+        }
+    }
+}
+
+// split a message into key=value pairs, values may be double quoted to contain spaces
+fn split_message(message: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut chars = message.chars().peekable();
+    loop {
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        let mut val = String::new();
+        let mut in_val = false;
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if in_val => quoted = !quoted,
+                '\\' if quoted => {
+                    if let Some(n) = chars.next() {
+                        val.push(n);
+                    }
+                }
+                ' ' if !quoted => break,
+                '=' if !in_val => in_val = true,
+                c if in_val => val.push(c),
+                c => key.push(c),
+            }
+        }
+        // tokens without '=' are free text, e.g. in L records
+        if in_val {
+            out.push((key, val));
+        }
+    }
+    out
+}
+
+/// Parse one accounting log line
+pub fn parse_accounting_record(line: &str) -> Result<AccountingRecord, String> {
+    let mut parts = line.splitn(4, ';');
+    let time = parts.next().unwrap_or("");
+    let time = NaiveDateTime::parse_from_str(time, "%m/%d/%Y %H:%M:%S")
+        .map_err(|e| format!("invalid accounting timestamp {time:?}: {e}"))?;
+    let rtype = parts
+        .next()
+        .filter(|t| t.chars().count() == 1)
+        .and_then(|t| t.chars().next())
+        .ok_or_else(|| format!("invalid accounting record type in: {line}"))?;
+    let id = parts
+        .next()
+        .ok_or_else(|| format!("missing accounting entity id in: {line}"))?;
+    let message = parts.next().unwrap_or("").trim_end().to_string();
+    let mut attribs = Attribs::new();
+    for (key, val) in split_message(&message) {
+        match key.split_once('.') {
+            Some((name, resource)) => {
+                let mut map = BTreeMap::new();
+                map.insert(resource.to_string(), Op::Default(val));
+                attribs.add(name.to_string(), Attrl::Resource(map));
+            }
+            None => attribs.add(key, Attrl::Value(Op::Default(val))),
+        }
+    }
+    Ok(AccountingRecord {
+        time,
+        record_type: rtype.into(),
+        id: id.to_string(),
+        attribs,
+        message,
+    })
+}
+
+/// Streams records from an accounting log, one line at a time
+pub struct AccountingReader<R> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> AccountingReader<R> {
+    pub fn new(reader: R) -> AccountingReader<R> {
+        AccountingReader {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl AccountingReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        debug!("Opening accounting log {:?}", path.as_ref());
+        Ok(AccountingReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> Iterator for AccountingReader<R> {
+    type Item = Result<AccountingRecord, String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line += 1;
+            let line = match self.lines.next()? {
+                Ok(l) => l,
+                Err(e) => return Some(Err(format!("line {}: {e}", self.line))),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            trace!("accounting line {}: {line}", self.line);
+            return Some(
+                parse_accounting_record(&line).map_err(|e| format!("line {}: {e}", self.line)),
+            );
+        }
+    }
+}
+
+/// Daily log files in `dir` covering `from` to `to` (inclusive), oldest first
+///
+/// Files are selected by their YYYYMMDD names, other files are ignored.
+pub fn log_files<P: AsRef<Path>>(
+    dir: P,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let date = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            // chrono would take a short name like 2020062 too
+            .filter(|n| n.len() == 8 && n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| NaiveDate::parse_from_str(n, "%Y%m%d").ok());
+        if let Some(date) = date {
+            if from.is_some_and(|f| date < f) || to.is_some_and(|t| date > t) {
+                continue;
+            }
+            files.push((date, path));
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, p)| p).collect())
+}
+
+/// Stream records from every log in `dir` with timestamps between `from` and `to` (inclusive)
+pub fn read_accounting_dir<P: AsRef<Path>>(
+    dir: P,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+) -> io::Result<impl Iterator<Item = Result<AccountingRecord, String>>> {
+    let files = log_files(dir, from.map(|f| f.date()), to.map(|t| t.date()))?;
+    Ok(files
+        .into_iter()
+        .flat_map(
+            |path| -> Box<dyn Iterator<Item = Result<AccountingRecord, String>>> {
+                match AccountingReader::open(&path) {
+                    Ok(r) => {
+                        let p = path.display().to_string();
+                        Box::new(r.map(move |rec| rec.map_err(|e| format!("{p}: {e}"))))
+                    }
+                    Err(e) => Box::new(std::iter::once(Err(format!("{}: {e}", path.display())))),
+                }
+            },
+        )
+        .filter(move |rec| match rec {
+            Ok(r) => from.is_none_or(|f| r.time >= f) && to.is_none_or(|t| r.time <= t),
+            Err(_) => true,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Q: &str = "06/22/2020 10:12:14;Q;1234.pbsserver;queue=workq";
+    const S: &str = "06/22/2020 10:12:15;S;1234.pbsserver;user=user1 group=users project=_pbs_project_default jobname=STDIN queue=workq ctime=1592842334 qtime=1592842334 etime=1592842334 start=1592842335 exec_host=node01/0*32 exec_vnode=(node01:ncpus=32) Resource_List.ncpus=32 Resource_List.nodect=1 Resource_List.place=pack Resource_List.select=1:ncpus=32 resource_assigned.ncpus=32";
+    const E: &str = "06/22/2020 11:12:15;E;1234.pbsserver;user=user1 group=users project=_pbs_project_default jobname=STDIN queue=workq ctime=1592842334 qtime=1592842334 etime=1592842334 start=1592842335 exec_host=node01/0*32 exec_vnode=(node01:ncpus=32) Resource_List.ncpus=32 Resource_List.nodect=1 Resource_List.place=pack Resource_List.select=1:ncpus=32 session=12345 end=1592845935 Exit_status=0 resources_used.cpupercent=3150 resources_used.cput=31:30:00 resources_used.mem=1048576kb resources_used.ncpus=32 resources_used.vmem=2097152kb resources_used.walltime=01:00:00 run_count=1";
+    const L: &str = "06/22/2020 11:00:00;L;license;floating license hour:0 day:0 month:0 max:0";
+
+    fn pairs(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn split_quotes_and_escapes() {
+        assert_eq!(
+            split_message(r#"jobname="my job" comment="said \"hi\" in C:\\tmp"  queue=workq"#),
+            pairs(&[
+                ("jobname", "my job"),
+                ("comment", r#"said "hi" in C:\tmp"#),
+                ("queue", "workq"),
+            ])
+        );
+        // '=' in a value and backslashes outside quotes are kept
+        assert_eq!(
+            split_message(r"Resource_List.select=1:ncpus=2 dir=a\b"),
+            pairs(&[("Resource_List.select", "1:ncpus=2"), ("dir", r"a\b")])
+        );
+        assert_eq!(split_message("floating license hour:0"), pairs(&[]));
+        assert_eq!(
+            split_message("empty= x=1"),
+            pairs(&[("empty", ""), ("x", "1")])
+        );
+    }
+
+    #[test]
+    fn job_records() {
+        let q = parse_accounting_record(Q).unwrap();
+        assert_eq!(q.record_type, RecordType::Queued);
+        assert_eq!(q.id, "1234.pbsserver");
+        assert_eq!(q.get("queue").as_deref(), Some("workq"));
+        assert_eq!(
+            q.time,
+            NaiveDate::from_ymd_opt(2020, 6, 22)
+                .unwrap()
+                .and_hms_opt(10, 12, 14)
+                .unwrap()
+        );
+
+        let s = parse_accounting_record(S).unwrap();
+        assert_eq!(s.record_type, RecordType::Started);
+        assert_eq!(s.get("exec_host").as_deref(), Some("node01/0*32"));
+        assert_eq!(
+            s.resource("Resource_List", "select").as_deref(),
+            Some("1:ncpus=32")
+        );
+        assert_eq!(
+            s.resource("resource_assigned", "ncpus").as_deref(),
+            Some("32")
+        );
+
+        let e = parse_accounting_record(E).unwrap();
+        assert_eq!(e.record_type, RecordType::Ended);
+        assert_eq!(e.get("Exit_status").as_deref(), Some("0"));
+        assert_eq!(
+            e.resource("resources_used", "walltime").as_deref(),
+            Some("01:00:00")
+        );
+        assert_eq!(e.get("resources_used"), None);
+        assert_eq!(e.message, E.splitn(4, ';').nth(3).unwrap());
+    }
+
+    #[test]
+    fn resources_are_merged() {
+        let e = parse_accounting_record(E).unwrap();
+        match e.attribs.get("Resource_List") {
+            Some(Attrl::Resource(map)) => {
+                let keys: Vec<&String> = map.keys().collect();
+                assert_eq!(keys, ["ncpus", "nodect", "place", "select"]);
+            }
+            other => panic!("Resource_List not merged: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn license_record() {
+        let l = parse_accounting_record(L).unwrap();
+        assert_eq!(l.record_type, RecordType::License);
+        assert_eq!(l.id, "license");
+        assert_eq!(l.message, "floating license hour:0 day:0 month:0 max:0");
+        assert!(l.attribs.flat().is_empty());
+    }
+
+    #[test]
+    fn bad_records() {
+        assert!(parse_accounting_record("06/22/2020;Q;1.svr;").is_err());
+        assert!(parse_accounting_record("06/22/2020 10:12:14;QQ;1.svr;").is_err());
+        assert!(parse_accounting_record("06/22/2020 10:12:14;Q").is_err());
+        let log = format!("{Q}\n\n{E}\nnonsense\n");
+        let r: Vec<_> = AccountingReader::new(log.as_bytes()).collect();
+        assert_eq!(r.len(), 3);
+        assert!(r[0].is_ok() && r[1].is_ok());
+        assert!(r[2].as_ref().unwrap_err().starts_with("line 4: "));
+    }
+
+    #[test]
+    fn log_files_by_date() {
+        let dir = std::env::temp_dir().join(format!("pbs-accounting-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for f in [
+            "20200623",
+            "20200621",
+            "20200622",
+            "notes",
+            "2020062",
+            "20200622.gz",
+        ] {
+            let day = f.get(6..8).unwrap_or("01");
+            let line = format!("06/{day}/2020 10:00:00;Q;1.pbsserver;queue=workq\n");
+            fs::write(dir.join(f), line).unwrap();
+        }
+        let date = |d| NaiveDate::from_ymd_opt(2020, 6, d);
+        let names = |from, to| -> Vec<String> {
+            log_files(&dir, from, to)
+                .unwrap()
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(names(None, None), ["20200621", "20200622", "20200623"]);
+        assert_eq!(names(date(22), None), ["20200622", "20200623"]);
+        assert_eq!(names(None, date(22)), ["20200621", "20200622"]);
+        assert_eq!(names(date(22), date(22)), ["20200622"]);
+        let from = date(22).unwrap().and_hms_opt(12, 0, 0);
+        assert_eq!(read_accounting_dir(&dir, from, None).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,19 +3,33 @@ use log::{debug, error, info, trace, warn};
 use pbs_sys::{attrl, attropl, batch_status};
 use std::ffi::{CStr, CString};
 use std::ptr::{self, null_mut};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::bindings::{get_err, is_err, stat};
+use crate::attributes::{node, reservation, resource};
+use crate::bindings::{attrl_list, get_err, is_err, stat};
 use crate::helpers::{self, optstr_to_cstr};
-use crate::types::{Attribs, Attrl, Op, Server, StatResp};
+use crate::types::{
+    validate_resv_mod, AttrChange, Attribs, Attrl, Op, ReservationSpec, ReservationState,
+    ResourceDef, ResourceFlags, ResourceType, ResvModResponse, ResvModification, Server, StatResp,
+    VariableList, VnodeState, VnodeStateChange,
+};
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum ResvModFlag {
+    /// apply the change even if it conflicts with the reservation's jobs
     Force,
+    /// wait up to this many seconds for the scheduler to confirm the change
+    /// negative values wait that long and then withdraw the change if unconfirmed
+    Interactive(i64),
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum ResvSubFlag {
     Maintenance,
+    /// wait up to this many seconds for the scheduler to confirm the reservation
+    /// negative values wait that long and then delete the reservation if unconfirmed
+    Interactive(i64),
 }
 
 // signature for most of the pbs_stat* functions
@@ -39,6 +53,15 @@ unsafe extern "C" fn srv_stat(
 ) -> *mut batch_status {
     stat::pbs_statserver(conn, a, n)
 }
+unsafe extern "C" fn hook_stat(
+    conn: i32,
+    n: *mut i8,
+    a: *mut attrl,
+    _ex: *mut i8,
+) -> *mut batch_status {
+    // site hooks, as opposed to the built in PBS hooks
+    stat::pbs_stathook(conn, n, a, pbs_sys::SITE_HOOK.as_ptr() as *mut i8)
+}
 impl Server {
     pub fn stat_host(
         &self,
@@ -56,6 +79,14 @@ impl Server {
         debug!("performing a reservation stat");
         self.stat(name, info, stat::pbs_statresv)
     }
+    pub fn stat_hook(
+        &self,
+        name: &Option<String>,
+        info: Option<Attribs>,
+    ) -> Result<StatResp, String> {
+        debug!("performing a hook stat");
+        self.stat(name, info, hook_stat)
+    }
     pub fn stat_resource(
         &self,
         name: &Option<String>,
@@ -102,21 +133,30 @@ impl Server {
         _output: Option<Attribs>,
     ) -> Result<StatResp, String> {
         debug!("performing a job stat");
-        let crit: ConstList<attrl> = criteria.into();
         //TODO send criteria to api
         //let out: ConstList<attrl> = output.unwrap().into();
-        //todo send extend flags
-        // T, t to include subjobs, job arrays are not included
-        // x include finished and moved jobs
+        self.select_jobs(criteria, None)
+    }
+    /// Jobs matching `criteria` along with the subjobs of matching arrays
+    pub fn stat_jobs_with_subjobs(
+        &self,
+        criteria: Attribs,
+        _output: Option<Attribs>,
+    ) -> Result<StatResp, String> {
+        debug!("performing a job stat including subjobs");
+        self.select_jobs(criteria, Some("t"))
+    }
+
+    // pbs_selstat, `extend` "t" includes array subjobs and "x" finished and moved jobs
+    fn select_jobs(&self, criteria: Attribs, extend: Option<&str>) -> Result<StatResp, String> {
+        let crit: ConstList<attrl> = criteria.into();
+        let ext = optstr_to_cstr(extend);
         trace!("calling pbs server");
-        let data = unsafe {
-            stat::pbs_selstat(
-                self.conn(),
-                crit.head() as *mut attropl,
-                null_mut(),
-                null_mut(),
-            )
-        };
+        let data =
+            unsafe { stat::pbs_selstat(self.conn(), crit.head() as *mut attropl, null_mut(), ext) };
+        if !ext.is_null() {
+            _ = unsafe { CString::from_raw(ext) };
+        }
         if data.is_null() && is_err() {
             error!("job stat request failed {}", get_err());
             Err(get_err())
@@ -126,6 +166,12 @@ impl Server {
         }
     }
 
+    /// A single job or array subjob by id, with pbs_statjob
+    pub fn stat_job_id(&self, jobid: &str, info: Option<Attribs>) -> Result<StatResp, String> {
+        debug!("performing a stat of job {jobid}");
+        self.stat(&Some(jobid.to_string()), info, stat::pbs_statjob)
+    }
+
     fn stat(
         &self,
         name: &Option<String>,
@@ -196,6 +242,16 @@ impl Server {
         flags: Vec<ResvSubFlag>,
     ) -> Result<String, String> {
         trace!("Reservation submission, generating attributes list");
+        let mut attributes = attributes;
+        if let Some(i) = flags.iter().find_map(|f| match f {
+            ResvSubFlag::Interactive(i) => Some(*i),
+            _ => None,
+        }) {
+            attributes.add(
+                reservation::INTERACTIVE.to_string(),
+                Attrl::Value(Op::Set(i.to_string())),
+            );
+        }
         let attribs: ConstList<pbs_sys::attrl> = attributes.into();
         let extend = if flags.contains(&ResvSubFlag::Maintenance) {
             helpers::str_to_cstr("m")
@@ -204,7 +260,6 @@ impl Server {
         };
         trace!("Submitting reservation request");
         //bindings::attropl and bindings::attrl are interchangable
-        //TODO option to pass 'm' as extend arg for maintenance reservations
         let resvid = unsafe {
             pbs_sys::pbs_submit_resv(self.conn(), attribs.head() as *mut pbs_sys::attropl, extend)
         };
@@ -222,13 +277,36 @@ impl Server {
         }
     }
 
+    /// validate and submit a reservation built with ReservationSpec
+    pub fn submit_resv_spec(&self, spec: ReservationSpec) -> Result<String, String> {
+        trace!("Reservation submission from spec {spec:?}");
+        let attribs = spec.attribs()?;
+        let flags = if spec.is_maintenance() {
+            vec![ResvSubFlag::Maintenance]
+        } else {
+            Vec::new()
+        };
+        self.submit_resv(attribs, flags)
+    }
+
     pub fn mod_resv(
         &self,
         resv: &str,
         attributes: Attribs,
         flags: Vec<ResvModFlag>,
-    ) -> Result<String, String> {
+    ) -> Result<ResvModResponse, String> {
         trace!("Modify reservation submission, generating attributes list");
+        validate_resv_mod(&attributes, None)?;
+        let mut attributes = attributes;
+        if let Some(i) = flags.iter().find_map(|f| match f {
+            ResvModFlag::Interactive(i) => Some(*i),
+            _ => None,
+        }) {
+            attributes.add(
+                reservation::INTERACTIVE.to_string(),
+                Attrl::Value(Op::Set(i.to_string())),
+            );
+        }
         let attribs: ConstList<pbs_sys::attrl> = attributes.into();
         let extend = if flags.contains(&ResvModFlag::Force) {
             helpers::str_to_cstr("force")
@@ -246,10 +324,10 @@ impl Server {
             )
         };
         if !resvid.is_null() {
-            let resp = Ok(unsafe { CStr::from_ptr(resvid) }
-                .to_str()
-                .unwrap()
-                .to_string());
+            let resp = Ok(ResvModResponse::parse(
+                resv,
+                unsafe { CStr::from_ptr(resvid) }.to_str().unwrap(),
+            ));
             trace!("Reservation modification submitted, got resp {:?}", &resp);
             unsafe { libc::free(resvid as *mut libc::c_void) };
             resp
@@ -259,6 +337,19 @@ impl Server {
         }
     }
 
+    /// modify a reservation, checking the change is allowed in its current state first
+    pub fn modify_resv(
+        &self,
+        resv: &str,
+        modification: ResvModification,
+        flags: Vec<ResvModFlag>,
+    ) -> Result<ResvModResponse, String> {
+        let attribs = modification.attribs()?;
+        let (state, _) = self.resv_state(resv)?;
+        validate_resv_mod(&attribs, Some(state))?;
+        self.mod_resv(resv, attribs, flags)
+    }
+
     pub fn del_job(&self, jobid: &str) -> Result<(), String> {
         trace!("Deleting job {jobid}");
         let resp = unsafe {
@@ -270,6 +361,100 @@ impl Server {
         }
         Ok(())
     }
+    pub fn rerun_job(&self, jobid: &str) -> Result<(), String> {
+        trace!("Requeueing job {jobid}");
+        let resp = unsafe {
+            pbs_sys::pbs_rerunjob(self.conn(), helpers::str_to_cstr(jobid), ptr::null_mut())
+        };
+        if resp != 0 {
+            info!("Error requeueing job {jobid}: {}", get_err());
+            return Err(get_err());
+        }
+        Ok(())
+    }
+    /// change attributes of a queued or held job, the qalter equivalent
+    pub fn alter_job(&self, jobid: &str, attributes: Attribs) -> Result<(), String> {
+        trace!("Altering job {jobid}");
+        let attribs: ConstList<attrl> = attributes.into();
+        let resp = unsafe {
+            pbs_sys::pbs_alterjob(
+                self.conn(),
+                helpers::str_to_cstr(jobid),
+                attribs.head(),
+                ptr::null_mut(),
+            )
+        };
+        if resp != 0 {
+            info!("Error altering job {jobid}: {}", get_err());
+            return Err(get_err());
+        }
+        Ok(())
+    }
+
+    /// Set and remove variables in a job's Variable_List
+    ///
+    /// The current list is fetched first since the server replaces the whole
+    /// attribute, nothing is sent if it can't be read. Returns the list that was sent.
+    pub fn update_job_variables(
+        &self,
+        jobid: &str,
+        set: &[(&str, &str)],
+        remove: &[&str],
+    ) -> Result<VariableList, String> {
+        let resp = self.stat_job_id(jobid, None)?;
+        let job = resp
+            .resources
+            .first()
+            .ok_or_else(|| format!("no such job {jobid}"))?;
+        let mut vars = job
+            .variables()
+            .ok_or_else(|| format!("can't read the Variable_List of job {jobid}"))?;
+        for (name, val) in set {
+            vars.set(name, val);
+        }
+        for name in remove {
+            vars.remove(name);
+        }
+        let mut attribs = Attribs::new();
+        attribs.set_variables(&vars);
+        self.alter_job(jobid, attribs)?;
+        Ok(vars)
+    }
+
+    /// place holds on a job, `hold_types` is any of u, o, s and p, e.g. "uo"
+    pub fn hold_job(&self, jobid: &str, hold_types: &str) -> Result<(), String> {
+        trace!("Holding job {jobid}: {hold_types}");
+        let resp = unsafe {
+            pbs_sys::pbs_holdjob(
+                self.conn(),
+                helpers::str_to_cstr(jobid),
+                helpers::str_to_cstr(hold_types),
+                ptr::null_mut(),
+            )
+        };
+        if resp != 0 {
+            info!("Error holding job {jobid}: {}", get_err());
+            return Err(get_err());
+        }
+        Ok(())
+    }
+    /// release holds on a job, `hold_types` is any of u, o, s and p
+    pub fn release_job(&self, jobid: &str, hold_types: &str) -> Result<(), String> {
+        trace!("Releasing job {jobid}: {hold_types}");
+        let resp = unsafe {
+            pbs_sys::pbs_rlsjob(
+                self.conn(),
+                helpers::str_to_cstr(jobid),
+                helpers::str_to_cstr(hold_types),
+                ptr::null_mut(),
+            )
+        };
+        if resp != 0 {
+            info!("Error releasing job {jobid}: {}", get_err());
+            return Err(get_err());
+        }
+        Ok(())
+    }
     pub fn del_resv(&self, id: &str) -> Result<(), String> {
         trace!("Deleting Reservation {id}");
         let resp =
@@ -280,66 +465,305 @@ impl Server {
         }
         Ok(())
     }
-    pub fn offline_vnode(&self, name: &str, comment: Option<&str>) -> Result<(), String> {
-        trace!("offlining vnode: {name}");
-        //ret = marknode(con, name, ND_offline, pbs_sys::batch_op::INCR, null_mut(), pbs_sys::batch_op::INCR, comment)
-        let mut new = Attribs::new();
-        new.add(
-            helpers::cstr_to_str(pbs_sys::ATTR_NODE_state.as_ptr() as *mut i8).to_string(),
-            Attrl::Value(Op::Incr("offline".to_string())),
-        );
-        if let Some(c) = comment {
-            new.add(
-                helpers::cstr_to_str(pbs_sys::ATTR_comment.as_ptr() as *mut i8).to_string(),
-                Attrl::Value(Op::Set(c.to_string())),
-            )
-        }
-        let new: ConstList<attrl> = new.into();
+    /// confirm (or reject) a reservation, as the scheduler does
+    /// `location` is the exec_vnode style list of vnodes allocated to the reservation
+    pub fn confirm_resv(
+        &self,
+        id: &str,
+        location: &str,
+        start: u64,
+        success: bool,
+    ) -> Result<(), String> {
+        trace!("Confirming reservation {id}, success: {success}");
+        let extend = if success {
+            "PBS_RESV_CONFIRM_SUCCESS"
+        } else {
+            "PBS_RESV_CONFIRM_FAIL"
+        };
         let resp = unsafe {
-            pbs_sys::pbs_manager(
+            pbs_sys::pbs_confirmresv(
                 self.conn(),
-                pbs_sys::mgr_cmd_MGR_CMD_SET,
-                pbs_sys::mgr_obj_MGR_OBJ_HOST,
-                helpers::str_to_cstr(name),
-                new.head() as *mut attropl,
-                null_mut(),
+                helpers::str_to_cstr(id),
+                helpers::str_to_cstr(location),
+                start as std::os::raw::c_ulong,
+                helpers::str_to_cstr(extend),
             )
         };
         if resp != 0 {
-            info!("Error offlining vnode {name}: {}", get_err());
+            info!("Error confirming Reservation {id}: {}", get_err());
             return Err(get_err());
         }
         Ok(())
     }
+
+    /// current reserve_state and reserve_substate of a reservation
+    pub fn resv_state(
+        &self,
+        id: &str,
+    ) -> Result<(ReservationState, Option<ReservationState>), String> {
+        let resp = self.stat_reservation(&Some(id.to_string()), None)?;
+        resp.resources
+            .first()
+            .and_then(|r| r.resv_state())
+            .ok_or_else(|| format!("no state found for reservation {id}"))
+    }
+
+    /// Poll a reservation until it reaches one of `states`, returning the state reached
+    ///
+    /// Polls with exponential backoff from 1s up to 30s, and gives up early if the
+    /// reservation reaches a terminal state that wasn't asked for.
+    pub fn wait_for_resv_state(
+        &self,
+        id: &str,
+        states: &[ReservationState],
+        timeout: Duration,
+    ) -> Result<ReservationState, String> {
+        debug!("Waiting up to {timeout:?} for reservation {id} to reach {states:?}");
+        let deadline = Instant::now() + timeout;
+        let mut delay = Duration::from_secs(1);
+        loop {
+            let (state, _) = self.resv_state(id)?;
+            trace!("Reservation {id} is in state {state}");
+            if states.contains(&state) {
+                return Ok(state);
+            }
+            if state.is_terminal() {
+                return Err(format!("reservation {id} reached terminal state {state}"));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!(
+                    "timed out waiting for reservation {id}, last state {state}"
+                ));
+            }
+            thread::sleep(delay.min(deadline - now));
+            delay = (delay * 2).min(Duration::from_secs(30));
+        }
+    }
+
+    pub fn offline_vnode(&self, name: &str, comment: Option<&str>) -> Result<(), String> {
+        trace!("offlining vnode: {name}");
+        //ret = marknode(con, name, ND_offline, pbs_sys::batch_op::INCR, null_mut(), pbs_sys::batch_op::INCR, comment)
+        self.mark_vnode(name, VnodeState::OFFLINE, VnodeState::FREE, comment)
+    }
     pub fn clear_vnode(&self, name: &str, comment: Option<&str>) -> Result<(), String> {
         trace!("clearing offline,down for vnode: {name}");
         //ret = marknode(con, name, "offline", pbs_sys::batch_op::DECR, "down", pbs_sys::batch_op::DECR, comment)
-        let mut new = Attribs::new();
-        new.add(
-            helpers::cstr_to_str(pbs_sys::ATTR_NODE_state.as_ptr() as *mut i8).to_string(),
-            Attrl::Value(Op::Decr("offline".to_string())),
+        self.mark_vnode(
+            name,
+            VnodeState::FREE,
+            VnodeState::OFFLINE | VnodeState::DOWN,
+            comment,
+        )
+    }
+
+    /// Add and remove states on a vnode in a single request
+    ///
+    /// The vnode's current state is looked up first and reported along with the
+    /// expected result. With `dry_run` nothing is changed on the server.
+    pub fn set_vnode_state(
+        &self,
+        name: &str,
+        add: VnodeState,
+        remove: VnodeState,
+        comment: Option<&str>,
+        dry_run: bool,
+    ) -> Result<VnodeStateChange, String> {
+        trace!("setting vnode {name} state +{add} -{remove}, dry run: {dry_run}");
+        if add.intersects(remove) {
+            return Err(format!(
+                "can't both add and remove vnode state {}",
+                add & remove
+            ));
+        }
+        if !VnodeState::SETTABLE.contains(add | remove) {
+            return Err(format!(
+                "vnode state {} can't be set by clients",
+                (add | remove) - VnodeState::SETTABLE
+            ));
+        }
+        let resp = self.stat_vnode(&Some(name.to_string()), None)?;
+        let before = resp
+            .resources
+            .first()
+            .ok_or_else(|| format!("no such vnode {name}"))?
+            .vnode_state()?;
+        let after = (before | add) - remove;
+        if !dry_run {
+            self.mark_vnode(name, add, remove, comment)?;
+        }
+        Ok(VnodeStateChange {
+            name: name.to_string(),
+            before,
+            after,
+            applied: !dry_run,
+        })
+    }
+
+    /// set_vnode_state over a list of vnodes, one result per vnode
+    pub fn set_vnodes_state<S: AsRef<str>>(
+        &self,
+        names: &[S],
+        add: VnodeState,
+        remove: VnodeState,
+        comment: Option<&str>,
+        dry_run: bool,
+    ) -> Vec<Result<VnodeStateChange, String>> {
+        names
+            .iter()
+            .map(|n| self.set_vnode_state(n.as_ref(), add, remove, comment, dry_run))
+            .collect()
+    }
+
+    // equivalent of pbsnodes' marknode, increment and decrement states in one request
+    // with an entry per state
+    fn mark_vnode(
+        &self,
+        name: &str,
+        add: VnodeState,
+        remove: VnodeState,
+        comment: Option<&str>,
+    ) -> Result<(), String> {
+        let state = || node::STATE.to_string();
+        let mut entries: Vec<_> = add
+            .names()
+            .into_iter()
+            .map(|s| (state(), None, Op::Incr(s.to_string())))
+            .collect();
+        entries.extend(
+            remove
+                .names()
+                .into_iter()
+                .map(|s| (state(), None, Op::Decr(s.to_string()))),
         );
         if let Some(c) = comment {
-            new.add(
-                helpers::cstr_to_str(pbs_sys::ATTR_comment.as_ptr() as *mut i8).to_string(),
-                Attrl::Value(Op::Set(c.to_string())),
-            )
+            entries.push((node::COMMENT.to_string(), None, Op::Set(c.to_string())));
         }
-        let new: ConstList<attrl> = new.into();
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_SET,
+            pbs_sys::mgr_obj_MGR_OBJ_HOST,
+            name,
+            entries,
+        )
+        .inspect_err(|e| info!("Error setting state of vnode {name}: {e}"))
+    }
+
+    // single pbs_manager request on the object `name`
+    pub(crate) fn manage(
+        &self,
+        cmd: pbs_sys::mgr_cmd,
+        obj: pbs_sys::mgr_obj,
+        name: &str,
+        entries: Vec<(String, Option<String>, Op)>,
+    ) -> Result<(), String> {
+        let list = attrl_list(entries);
         let resp = unsafe {
             pbs_sys::pbs_manager(
                 self.conn(),
-                pbs_sys::mgr_cmd_MGR_CMD_SET,
-                pbs_sys::mgr_obj_MGR_OBJ_HOST,
+                cmd,
+                obj,
                 helpers::str_to_cstr(name),
-                new.head() as *mut attropl,
+                list.head() as *mut attropl,
                 null_mut(),
             )
         };
         if resp != 0 {
-            info!("Error offlining vnode {name}: {}", get_err());
             return Err(get_err());
         }
         Ok(())
     }
+
+    /// typed definitions of all resources, built in ones included
+    pub fn resource_defs(&self) -> Result<Vec<ResourceDef>, String> {
+        let resp = self.stat_resource(&None, None)?;
+        Ok(resp
+            .resources
+            .iter()
+            .filter_map(|s| match ResourceDef::try_from(s) {
+                Ok(d) => Some(d),
+                Err(e) => {
+                    debug!("skipping resource {}: {e}", s.name());
+                    None
+                }
+            })
+            .collect())
+    }
+    /// define a custom resource, `qmgr -c "create resource ..."`
+    pub fn create_resource(&self, def: &ResourceDef) -> Result<(), String> {
+        trace!("creating resource {}", def.name);
+        let entries = def
+            .entries()
+            .into_iter()
+            .map(|(k, v)| (k, None, Op::Set(v)))
+            .collect();
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_CREATE,
+            pbs_sys::mgr_obj_MGR_OBJ_RSC,
+            &def.name,
+            entries,
+        )
+    }
+    /// change a custom resource's type
+    ///
+    /// The type of a resource can only be changed while nothing requests it.
+    pub fn set_resource_type(&self, name: &str, resource_type: ResourceType) -> Result<(), String> {
+        trace!("setting type of resource {name} to {resource_type}");
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_SET,
+            pbs_sys::mgr_obj_MGR_OBJ_RSC,
+            name,
+            vec![(
+                resource::TYPE.to_string(),
+                None,
+                Op::Set(resource_type.to_string()),
+            )],
+        )
+    }
+    /// change a custom resource's flags, empty flags unset them
+    pub fn set_resource_flags(&self, name: &str, flags: ResourceFlags) -> Result<(), String> {
+        trace!("setting flags of resource {name} to {flags}");
+        let op = if flags.is_empty() {
+            Op::Unset(String::new())
+        } else {
+            Op::Set(flags.to_string())
+        };
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_SET,
+            pbs_sys::mgr_obj_MGR_OBJ_RSC,
+            name,
+            vec![(resource::FLAG.to_string(), None, op)],
+        )
+    }
+    /// delete a custom resource, `qmgr -c "delete resource ..."`
+    pub fn delete_resource(&self, name: &str) -> Result<(), String> {
+        trace!("deleting resource {name}");
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_DELETE,
+            pbs_sys::mgr_obj_MGR_OBJ_RSC,
+            name,
+            Vec::new(),
+        )
+    }
+
+    // set then unset attributes of one object with pbs_manager
+    pub(crate) fn apply_changes(
+        &self,
+        obj: pbs_sys::mgr_obj,
+        name: &str,
+        changes: &[AttrChange],
+    ) -> Result<(), String> {
+        let (unset, set): (Vec<_>, Vec<_>) = changes
+            .iter()
+            .partition(|c| matches!(c, AttrChange::Unset(_)));
+        for (cmd, changes) in [
+            (pbs_sys::mgr_cmd_MGR_CMD_SET, set),
+            (pbs_sys::mgr_cmd_MGR_CMD_UNSET, unset),
+        ] {
+            if !changes.is_empty() {
+                let entries = changes.iter().map(|c| c.entry()).collect();
+                self.manage(cmd, obj, name, entries)?;
+            }
+        }
+        Ok(())
+    }
 }
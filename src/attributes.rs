@@ -0,0 +1,520 @@
+//! Catalog of the standard PBS attributes from pbs_ifl.h
+//!
+//! Each object type has a module of name constants, e.g. `attributes::job::RESOURCE_LIST`,
+//! and a table of definitions to check attributes against before contacting the server.
+use crate::helpers::{parse_bool, parse_duration, parse_size};
+use crate::types::{split_escaped, Resource};
+use serde_json::{Number, Value};
+
+/// Type of an attribute's value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Long,
+    Float,
+    /// True/False, y/n or 1/0
+    Boolean,
+    /// e.g. 10gb
+    Size,
+    /// [[HH:]MM:]SS
+    Duration,
+    /// seconds since the epoch
+    Time,
+    String,
+    /// comma separated strings
+    StringArray,
+    /// comma separated entries with an optional +/- prefix
+    Acl,
+    /// one value per resource, name.resource
+    Resources,
+}
+
+impl ValueType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueType::Long => "long",
+            ValueType::Float => "float",
+            ValueType::Boolean => "boolean",
+            ValueType::Size => "size",
+            ValueType::Duration => "duration",
+            ValueType::Time => "time",
+            ValueType::String => "string",
+            ValueType::StringArray => "string_array",
+            ValueType::Acl => "acl",
+            ValueType::Resources => "resources",
+        }
+    }
+    /// value converted to json, sizes in bytes and durations in seconds
+    ///
+    /// None if `val` doesn't fit this type.
+    pub fn json(&self, val: &str) -> Option<Value> {
+        match self {
+            ValueType::Long | ValueType::Time => val.trim().parse::<i64>().ok().map(Value::from),
+            ValueType::Float => val
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            ValueType::Boolean => parse_bool(val).map(Value::Bool),
+            ValueType::Size => parse_size(val).map(Value::from),
+            ValueType::Duration => parse_duration(val).map(Value::from),
+            ValueType::StringArray => Some(Value::Array(
+                split_escaped(val).into_iter().map(Value::String).collect(),
+            )),
+            ValueType::String | ValueType::Acl | ValueType::Resources => {
+                Some(Value::String(val.to_string()))
+            }
+        }
+    }
+}
+
+/// Lowest privilege that can set an attribute
+///
+/// Ordered by privilege, ReadOnly attributes are only set by PBS itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Access {
+    User,
+    Operator,
+    Manager,
+    ReadOnly,
+}
+
+impl Access {
+    /// whether someone with `privilege` can set an attribute with this access
+    pub fn settable_by(&self, privilege: Access) -> bool {
+        *self != Access::ReadOnly && privilege >= *self
+    }
+}
+
+/// Definition of a standard attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttrDef {
+    /// name as used by the server, e.g. Resource_List
+    pub name: &'static str,
+    /// name of the constant in this module, e.g. RESOURCE_LIST
+    pub ident: &'static str,
+    pub value_type: ValueType,
+    pub access: Access,
+}
+
+impl AttrDef {
+    /// snake case name, e.g. resource_list
+    pub fn rust_name(&self) -> String {
+        self.ident.to_lowercase()
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.access == Access::ReadOnly
+    }
+    /// whether values are set per resource, e.g. Resource_List.ncpus
+    pub fn takes_resources(&self) -> bool {
+        self.value_type == ValueType::Resources
+    }
+}
+
+// name of a pbs_sys ATTR_* constant without its nul, at compile time
+const fn name(attr: &'static [u8]) -> &'static str {
+    match attr.split_last() {
+        Some((&0, name)) => match std::str::from_utf8(name) {
+            Ok(s) => s,
+            Err(_) => panic!("attribute name isn't utf8"),
+        },
+        _ => panic!("attribute name isn't nul terminated"),
+    }
+}
+
+// name constants in `module` and their definitions in `table`
+macro_rules! catalog {
+    ($(#[$doc:meta])* $module:ident, $table:ident {
+        $($ident:ident = $attr:ident, $value_type:ident, $access:ident;)*
+    }) => {
+        $(#[$doc])*
+        pub mod $module {
+            $(pub const $ident: &str = super::name(pbs_sys::$attr);)*
+        }
+        const $table: &[AttrDef] = &[$(AttrDef {
+            name: $module::$ident,
+            ident: stringify!($ident),
+            value_type: ValueType::$value_type,
+            access: Access::$access,
+        },)*];
+    };
+}
+
+catalog! {
+    /// job attributes
+    job, JOB {
+        EXECUTION_TIME = ATTR_a, Time, User;
+        CHECKPOINT = ATTR_c, String, User;
+        ERROR_PATH = ATTR_e, String, User;
+        GROUP_LIST = ATTR_g, StringArray, User;
+        HOLD_TYPES = ATTR_h, String, User;
+        JOIN_PATH = ATTR_j, String, User;
+        KEEP_FILES = ATTR_k, String, User;
+        RESOURCE_LIST = ATTR_l, Resources, User;
+        MAIL_POINTS = ATTR_m, String, User;
+        OUTPUT_PATH = ATTR_o, String, User;
+        PRIORITY = ATTR_p, Long, User;
+        RERUNABLE = ATTR_r, Boolean, User;
+        USER_LIST = ATTR_u, StringArray, User;
+        VARIABLE_LIST = ATTR_v, StringArray, User;
+        ACCOUNT_NAME = ATTR_A, String, User;
+        MAIL_USERS = ATTR_M, StringArray, User;
+        JOB_NAME = ATTR_N, String, User;
+        SHELL_PATH_LIST = ATTR_S, StringArray, User;
+        ARRAY_INDICES_SUBMITTED = ATTR_J, String, User;
+        DEPEND = ATTR_depend, String, User;
+        INTERACTIVE = ATTR_inter, Boolean, User;
+        SANDBOX = ATTR_sandbox, String, User;
+        STAGEIN = ATTR_stagein, StringArray, User;
+        STAGEOUT = ATTR_stageout, StringArray, User;
+        UMASK = ATTR_umask, Long, User;
+        BLOCK = ATTR_block, Boolean, User;
+        PROJECT = ATTR_project, String, User;
+        MAX_RUN_SUBJOBS = ATTR_max_run_subjobs, Long, User;
+        RELEASE_NODES_ON_STAGEOUT = ATTR_relnodes_on_stageout, Boolean, User;
+        TOLERATE_NODE_FAILURES = ATTR_tolerate_node_failures, String, User;
+        CREATE_RESV_FROM_JOB = ATTR_create_resv_from_job, Boolean, User;
+        COMMENT = ATTR_comment, String, Operator;
+        RUN_COUNT = ATTR_runcount, Long, Manager;
+        ELIGIBLE_TIME = ATTR_eligible_time, Duration, Manager;
+        ESTIMATED = ATTR_estimated, Resources, Manager;
+        TOPJOB_INELIGIBLE = ATTR_topjob_ineligible, Boolean, Manager;
+        JOB_OWNER = ATTR_owner, String, ReadOnly;
+        JOB_STATE = ATTR_state, String, ReadOnly;
+        SUBSTATE = ATTR_substate, Long, ReadOnly;
+        QUEUE = ATTR_queue, String, ReadOnly;
+        SERVER = ATTR_server, String, ReadOnly;
+        CTIME = ATTR_ctime, Time, ReadOnly;
+        ETIME = ATTR_etime, Time, ReadOnly;
+        MTIME = ATTR_mtime, Time, ReadOnly;
+        QTIME = ATTR_qtime, Time, ReadOnly;
+        STIME = ATTR_stime, Time, ReadOnly;
+        HISTORY_TIMESTAMP = ATTR_history_timestamp, Time, ReadOnly;
+        EXEC_HOST = ATTR_exechost, String, ReadOnly;
+        EXEC_VNODE = ATTR_execvnode, String, ReadOnly;
+        SCHEDSELECT = ATTR_SchedSelect, String, ReadOnly;
+        RESOURCES_USED = ATTR_used, Resources, ReadOnly;
+        RESOURCES_RELEASED = ATTR_released, String, ReadOnly;
+        RESOURCE_RELEASED_LIST = ATTR_rel_list, Resources, ReadOnly;
+        EUSER = ATTR_euser, String, ReadOnly;
+        EGROUP = ATTR_egroup, String, ReadOnly;
+        SESSION_ID = ATTR_session, Long, ReadOnly;
+        EXIT_STATUS = ATTR_exit_status, Long, ReadOnly;
+        STAGEOUT_STATUS = ATTR_stageout_status, Long, ReadOnly;
+        SUBMIT_ARGUMENTS = ATTR_submit_arguments, String, ReadOnly;
+        SUBMIT_HOST = ATTR_submit_host, String, ReadOnly;
+        ARRAY = ATTR_array, Boolean, ReadOnly;
+        ARRAY_ID = ATTR_array_id, String, ReadOnly;
+        ARRAY_INDEX = ATTR_array_index, Long, ReadOnly;
+        ARRAY_STATE_COUNT = ATTR_array_state_count, String, ReadOnly;
+        ARRAY_INDICES_REMAINING = ATTR_array_indices_remaining, String, ReadOnly;
+        ACCRUE_TYPE = ATTR_accrue_type, Long, ReadOnly;
+        QUEUE_RANK = ATTR_qrank, Long, ReadOnly;
+        HASHNAME = ATTR_hashname, String, ReadOnly;
+        HOP_COUNT = ATTR_hopcount, Long, ReadOnly;
+        ALT_ID = ATTR_altid, String, ReadOnly;
+        ACCOUNTING_ID = ATTR_acct_id, String, ReadOnly;
+        JOBDIR = ATTR_jobdir, String, ReadOnly;
+        PSET = ATTR_pset, String, ReadOnly;
+        RUN_VERSION = ATTR_run_version, Long, ReadOnly;
+        EXECUTABLE = ATTR_executable, String, ReadOnly;
+        ARGUMENT_LIST = ATTR_Arglist, String, ReadOnly;
+        JOB_KILL_DELAY = ATTR_job_kill_delay, Long, ReadOnly;
+    }
+}
+
+catalog! {
+    /// queue attributes
+    queue, QUEUE {
+        QUEUE_TYPE = ATTR_qtype, String, Manager;
+        ENABLED = ATTR_enable, Boolean, Operator;
+        STARTED = ATTR_start, Boolean, Operator;
+        PRIORITY = ATTR_p, Long, Manager;
+        COMMENT = ATTR_comment, String, Operator;
+        FROM_ROUTE_ONLY = ATTR_fromroute, Boolean, Manager;
+        ROUTE_DESTINATIONS = ATTR_routedest, StringArray, Manager;
+        ALT_ROUTER = ATTR_altrouter, Boolean, Manager;
+        ROUTE_HELD_JOBS = ATTR_routeheld, Boolean, Manager;
+        ROUTE_WAITING_JOBS = ATTR_routewait, Boolean, Manager;
+        ROUTE_RETRY_TIME = ATTR_routeretry, Long, Manager;
+        ROUTE_LIFETIME = ATTR_routelife, Long, Manager;
+        RESOURCES_MAX = ATTR_rescmax, Resources, Manager;
+        RESOURCES_MIN = ATTR_rescmin, Resources, Manager;
+        RESOURCES_DEFAULT = ATTR_rescdflt, Resources, Manager;
+        RESOURCES_AVAILABLE = ATTR_rescavail, Resources, Manager;
+        DEFAULT_CHUNK = ATTR_DefaultChunk, Resources, Manager;
+        MAX_RUN = ATTR_max_run, String, Manager;
+        MAX_RUN_RES = ATTR_max_run_res, String, Manager;
+        MAX_RUN_SOFT = ATTR_max_run_soft, String, Manager;
+        MAX_RUN_RES_SOFT = ATTR_max_run_res_soft, String, Manager;
+        MAX_QUEUED = ATTR_max_queued, String, Manager;
+        MAX_QUEUED_RES = ATTR_max_queued_res, String, Manager;
+        QUEUED_JOBS_THRESHOLD = ATTR_queued_jobs_threshold, String, Manager;
+        QUEUED_JOBS_THRESHOLD_RES = ATTR_queued_jobs_threshold_res, String, Manager;
+        MAX_RUNNING = ATTR_maxrun, Long, Manager;
+        MAX_QUEUABLE = ATTR_maxque, Long, Manager;
+        MAX_USER_RUN = ATTR_maxuserrun, Long, Manager;
+        MAX_USER_RUN_SOFT = ATTR_maxuserrunsoft, Long, Manager;
+        MAX_GROUP_RUN = ATTR_maxgrprun, Long, Manager;
+        MAX_GROUP_RUN_SOFT = ATTR_maxgrprunsoft, Long, Manager;
+        MAX_USER_RES = ATTR_maxuserres, Resources, Manager;
+        MAX_USER_RES_SOFT = ATTR_maxuserressoft, Resources, Manager;
+        MAX_GROUP_RES = ATTR_maxgroupres, Resources, Manager;
+        MAX_GROUP_RES_SOFT = ATTR_maxgroupressoft, Resources, Manager;
+        MAX_ARRAY_SIZE = ATTR_maxarraysize, Long, Manager;
+        ACL_USER_ENABLE = ATTR_acluren, Boolean, Manager;
+        ACL_USERS = ATTR_acluser, Acl, Manager;
+        ACL_GROUP_ENABLE = ATTR_aclgren, Boolean, Manager;
+        ACL_GROUPS = ATTR_aclgroup, Acl, Manager;
+        ACL_HOST_ENABLE = ATTR_aclhten, Boolean, Manager;
+        ACL_HOSTS = ATTR_aclhost, Acl, Manager;
+        CHECKPOINT_MIN = ATTR_chkptmin, Long, Manager;
+        KILL_DELAY = ATTR_killdelay, Long, Manager;
+        NODE_GROUP_KEY = ATTR_NodeGroupKey, StringArray, Manager;
+        BACKFILL_DEPTH = ATTR_backfill_depth, Long, Manager;
+        PARTITION = ATTR_partition, String, Manager;
+        HASNODES = ATTR_HasNodes, Boolean, ReadOnly;
+        RESOURCES_ASSIGNED = ATTR_rescassn, Resources, ReadOnly;
+        STATE_COUNT = ATTR_count, String, ReadOnly;
+        TOTAL_JOBS = ATTR_total, Long, ReadOnly;
+    }
+}
+
+catalog! {
+    /// server attributes
+    server, SERVER {
+        SCHEDULING = ATTR_scheduling, Boolean, Operator;
+        COMMENT = ATTR_comment, String, Operator;
+        DEFAULT_QUEUE = ATTR_dfltque, String, Manager;
+        MANAGERS = ATTR_managers, Acl, Manager;
+        OPERATORS = ATTR_operators, Acl, Manager;
+        ACL_ROOTS = ATTR_aclroot, Acl, Manager;
+        ACL_USER_ENABLE = ATTR_acluren, Boolean, Manager;
+        ACL_USERS = ATTR_acluser, Acl, Manager;
+        ACL_GROUP_ENABLE = ATTR_aclgren, Boolean, Manager;
+        ACL_GROUPS = ATTR_aclgroup, Acl, Manager;
+        ACL_HOST_ENABLE = ATTR_aclhten, Boolean, Manager;
+        ACL_HOSTS = ATTR_aclhost, Acl, Manager;
+        ACL_HOST_MOMS_ENABLE = ATTR_aclhostmomsen, Boolean, Manager;
+        RESV_ENABLE = ATTR_ResvEnable, Boolean, Manager;
+        ACL_RESV_USER_ENABLE = ATTR_aclResvuren, Boolean, Manager;
+        ACL_RESV_USERS = ATTR_aclResvuser, Acl, Manager;
+        ACL_RESV_GROUP_ENABLE = ATTR_aclResvgren, Boolean, Manager;
+        ACL_RESV_GROUPS = ATTR_aclResvgroup, Acl, Manager;
+        ACL_RESV_HOST_ENABLE = ATTR_aclResvhten, Boolean, Manager;
+        ACL_RESV_HOSTS = ATTR_aclResvhost, Acl, Manager;
+        RESOURCES_MAX = ATTR_rescmax, Resources, Manager;
+        RESOURCES_DEFAULT = ATTR_rescdflt, Resources, Manager;
+        RESOURCES_AVAILABLE = ATTR_rescavail, Resources, Manager;
+        DEFAULT_CHUNK = ATTR_DefaultChunk, Resources, Manager;
+        MAX_RUN = ATTR_max_run, String, Manager;
+        MAX_RUN_RES = ATTR_max_run_res, String, Manager;
+        MAX_RUN_SOFT = ATTR_max_run_soft, String, Manager;
+        MAX_RUN_RES_SOFT = ATTR_max_run_res_soft, String, Manager;
+        MAX_QUEUED = ATTR_max_queued, String, Manager;
+        MAX_QUEUED_RES = ATTR_max_queued_res, String, Manager;
+        QUEUED_JOBS_THRESHOLD = ATTR_queued_jobs_threshold, String, Manager;
+        QUEUED_JOBS_THRESHOLD_RES = ATTR_queued_jobs_threshold_res, String, Manager;
+        MAX_RUNNING = ATTR_maxrun, Long, Manager;
+        MAX_USER_RUN = ATTR_maxuserrun, Long, Manager;
+        MAX_USER_RUN_SOFT = ATTR_maxuserrunsoft, Long, Manager;
+        MAX_GROUP_RUN = ATTR_maxgrprun, Long, Manager;
+        MAX_GROUP_RUN_SOFT = ATTR_maxgrprunsoft, Long, Manager;
+        MAX_USER_RES = ATTR_maxuserres, Resources, Manager;
+        MAX_USER_RES_SOFT = ATTR_maxuserressoft, Resources, Manager;
+        MAX_GROUP_RES = ATTR_maxgroupres, Resources, Manager;
+        MAX_GROUP_RES_SOFT = ATTR_maxgroupressoft, Resources, Manager;
+        MAX_ARRAY_SIZE = ATTR_maxarraysize, Long, Manager;
+        MAX_CONCURRENT_PROVISION = ATTR_max_concurrent_prov, Long, Manager;
+        MAX_JOB_SEQUENCE_ID = ATTR_max_job_sequence_id, Long, Manager;
+        JOBSCRIPT_MAX_SIZE = ATTR_jobscript_max_size, Size, Manager;
+        SCHEDULER_ITERATION = ATTR_schediteration, Long, Manager;
+        LOG_EVENTS = ATTR_logevents, Long, Manager;
+        MAIL_FROM = ATTR_mailfrom, String, Manager;
+        NODE_FAIL_REQUEUE = ATTR_nodefailrq, Long, Manager;
+        QUERY_OTHER_JOBS = ATTR_queryother, Boolean, Manager;
+        FLATUID = ATTR_FlatUID, Boolean, Manager;
+        NODE_GROUP_ENABLE = ATTR_NodeGroupEnable, Boolean, Manager;
+        NODE_GROUP_KEY = ATTR_NodeGroupKey, StringArray, Manager;
+        DEFAULT_QDEL_ARGUMENTS = ATTR_dfltqdelargs, String, Manager;
+        DEFAULT_QSUB_ARGUMENTS = ATTR_dfltqsubargs, String, Manager;
+        PBS_LICENSE_INFO = ATTR_pbs_license_info, String, Manager;
+        PBS_LICENSE_MIN = ATTR_license_min, Long, Manager;
+        PBS_LICENSE_MAX = ATTR_license_max, Long, Manager;
+        PBS_LICENSE_LINGER_TIME = ATTR_license_linger, Long, Manager;
+        JOB_SORT_FORMULA = ATTR_job_sort_formula, String, Manager;
+        ELIGIBLE_TIME_ENABLE = ATTR_EligibleTimeEnable, Boolean, Manager;
+        RESERVE_RETRY_TIME = ATTR_resv_retry_time, Long, Manager;
+        JOB_HISTORY_ENABLE = ATTR_JobHistoryEnable, Boolean, Manager;
+        JOB_HISTORY_DURATION = ATTR_JobHistoryDuration, Duration, Manager;
+        RESV_POST_PROCESSING_TIME = ATTR_resv_post_processing, Long, Manager;
+        BACKFILL_DEPTH = ATTR_backfill_depth, Long, Manager;
+        JOB_REQUEUE_TIMEOUT = ATTR_job_requeue_timeout, Duration, Manager;
+        PYTHON_RESTART_MAX_HOOKS = ATTR_python_restart_max_hooks, Long, Manager;
+        PYTHON_RESTART_MAX_OBJECTS = ATTR_python_restart_max_objects, Long, Manager;
+        PYTHON_RESTART_MIN_INTERVAL = ATTR_python_restart_min_interval, Duration, Manager;
+        POWER_PROVISIONING = ATTR_power_provisioning, Boolean, Manager;
+        SYNC_MOM_HOOKFILES_TIMEOUT = ATTR_sync_mom_hookfiles_timeout, Long, Manager;
+        RPP_RETRY = ATTR_rpp_retry, Long, Manager;
+        RPP_HIGHWATER = ATTR_rpp_highwater, Long, Manager;
+        PNAMES = ATTR_PNames, StringArray, Manager;
+        SERVER_STATE = ATTR_status, String, ReadOnly;
+        SERVER_HOST = ATTR_SvrHost, String, ReadOnly;
+        PBS_VERSION = ATTR_version, String, ReadOnly;
+        RESOURCES_ASSIGNED = ATTR_rescassn, Resources, ReadOnly;
+        STATE_COUNT = ATTR_count, String, ReadOnly;
+        TOTAL_JOBS = ATTR_total, Long, ReadOnly;
+        LICENSE_COUNT = ATTR_license_count, String, ReadOnly;
+    }
+}
+
+catalog! {
+    /// vnode attributes
+    node, NODE {
+        STATE = ATTR_NODE_state, String, Operator;
+        COMMENT = ATTR_comment, String, Operator;
+        MOM = ATTR_NODE_Mom, StringArray, Manager;
+        PORT = ATTR_NODE_Port, Long, Manager;
+        QUEUE = ATTR_queue, String, Manager;
+        PRIORITY = ATTR_p, Long, Manager;
+        PARTITION = ATTR_partition, String, Manager;
+        RESOURCES_AVAILABLE = ATTR_rescavail, Resources, Manager;
+        RESV_ENABLE = ATTR_NODE_resv_enable, Boolean, Manager;
+        SHARING = ATTR_NODE_Sharing, String, Manager;
+        NO_MULTINODE_JOBS = ATTR_NODE_NoMultiNode, Boolean, Manager;
+        PROVISION_ENABLE = ATTR_NODE_ProvisionEnable, Boolean, Manager;
+        CURRENT_AOE = ATTR_NODE_current_aoe, String, Manager;
+        CURRENT_EOE = ATTR_NODE_current_eoe, String, Manager;
+        IN_MULTIVNODE_HOST = ATTR_NODE_in_multivnode_host, Long, Manager;
+        VNODE_POOL = ATTR_NODE_VnodePool, Long, Manager;
+        POWER_PROVISIONING = ATTR_NODE_power_provisioning, Boolean, Manager;
+        POWEROFF_ELIGIBLE = ATTR_NODE_poweroff_eligible, Boolean, Manager;
+        HOST = ATTR_NODE_Host, String, ReadOnly;
+        NTYPE = ATTR_NODE_ntype, String, ReadOnly;
+        JOBS = ATTR_NODE_jobs, StringArray, ReadOnly;
+        RESV = ATTR_NODE_resvs, StringArray, ReadOnly;
+        PCPUS = ATTR_NODE_pcpus, Long, ReadOnly;
+        LICENSE = ATTR_NODE_License, String, ReadOnly;
+        LICENSE_INFO = ATTR_NODE_LicenseInfo, Long, ReadOnly;
+        TOPOLOGY_INFO = ATTR_NODE_TopologyInfo, String, ReadOnly;
+        MAINTENANCE_JOBS = ATTR_NODE_MaintJobs, StringArray, ReadOnly;
+        RESOURCES_ASSIGNED = ATTR_rescassn, Resources, ReadOnly;
+        LAST_STATE_CHANGE_TIME = ATTR_NODE_last_state_change_time, Time, ReadOnly;
+        LAST_USED_TIME = ATTR_NODE_last_used_time, Time, ReadOnly;
+    }
+}
+
+catalog! {
+    /// reservation attributes
+    reservation, RESERVATION {
+        RESERVE_NAME = ATTR_resv_name, String, User;
+        RESERVE_START = ATTR_resv_start, Time, User;
+        RESERVE_END = ATTR_resv_end, Time, User;
+        RESERVE_DURATION = ATTR_resv_duration, Duration, User;
+        RESOURCE_LIST = ATTR_l, Resources, User;
+        AUTHORIZED_USERS = ATTR_auth_u, Acl, User;
+        AUTHORIZED_GROUPS = ATTR_auth_g, Acl, User;
+        AUTHORIZED_HOSTS = ATTR_auth_h, Acl, User;
+        MAIL_POINTS = ATTR_m, String, User;
+        MAIL_USERS = ATTR_M, StringArray, User;
+        INTERACTIVE = ATTR_inter, Long, User;
+        RESERVE_RRULE = ATTR_resv_rrule, String, User;
+        RESERVE_TIMEZONE = ATTR_resv_timezone, String, User;
+        DELETE_IDLE_TIME = ATTR_del_idle_time, Duration, User;
+        RESERVE_OWNER = ATTR_resv_owner, String, ReadOnly;
+        RESERVE_STATE = ATTR_resv_state, Long, ReadOnly;
+        RESERVE_SUBSTATE = ATTR_resv_substate, Long, ReadOnly;
+        RESERVE_TYPE = ATTR_resv_type, Long, ReadOnly;
+        RESERVE_STANDING = ATTR_resv_standing, Boolean, ReadOnly;
+        RESERVE_COUNT = ATTR_resv_count, Long, ReadOnly;
+        RESERVE_INDEX = ATTR_resv_idx, Long, ReadOnly;
+        RESERVE_EXECVNODES = ATTR_resv_execvnodes, String, ReadOnly;
+        RESERVE_RETRY = ATTR_resv_retry, Time, ReadOnly;
+        RESV_NODES = ATTR_resv_nodes, String, ReadOnly;
+        QUEUE = ATTR_queue, String, ReadOnly;
+        SERVER = ATTR_server, String, ReadOnly;
+        EUSER = ATTR_euser, String, ReadOnly;
+        EGROUP = ATTR_egroup, String, ReadOnly;
+        CTIME = ATTR_ctime, Time, ReadOnly;
+        MTIME = ATTR_mtime, Time, ReadOnly;
+        PARTITION = ATTR_partition, String, ReadOnly;
+    }
+}
+
+catalog! {
+    /// scheduler attributes
+    scheduler, SCHEDULER {
+        SCHEDULING = ATTR_scheduling, Boolean, Operator;
+        COMMENT = ATTR_comment, String, Operator;
+        SCHED_HOST = ATTR_SchedHost, String, Manager;
+        SCHED_PORT = ATTR_sched_port, Long, Manager;
+        SCHED_PRIV = ATTR_sched_priv, String, Manager;
+        SCHED_LOG = ATTR_sched_log, String, Manager;
+        SCHED_USER = ATTR_sched_user, String, Manager;
+        SCHEDULER_ITERATION = ATTR_schediteration, Long, Manager;
+        SCHED_CYCLE_LENGTH = ATTR_sched_cycle_len, Duration, Manager;
+        PARTITION = ATTR_partition, StringArray, Manager;
+        LOG_EVENTS = ATTR_logevents, Long, Manager;
+        DO_NOT_SPAN_PSETS = ATTR_do_not_span_psets, Boolean, Manager;
+        ONLY_EXPLICIT_PSETS = ATTR_only_explicit_psets, Boolean, Manager;
+        PREEMPT_TARGETS_ENABLE = ATTR_preempt_targets_enable, Boolean, Manager;
+        JOB_SORT_FORMULA_THRESHOLD = ATTR_job_sort_formula_threshold, Float, Manager;
+        THROUGHPUT_MODE = ATTR_throughput_mode, Boolean, Manager;
+        OPT_BACKFILL_FUZZY = ATTR_opt_backfill_fuzzy, String, Manager;
+        PREEMPT_QUEUE_PRIO = ATTR_sched_preempt_queue_prio, Long, Manager;
+        PREEMPT_PRIO = ATTR_sched_preempt_prio, String, Manager;
+        PREEMPT_ORDER = ATTR_sched_preempt_order, String, Manager;
+        PREEMPT_SORT = ATTR_sched_preempt_sort, String, Manager;
+        SERVER_DYN_RES_ALARM = ATTR_sched_server_dyn_res_alarm, Long, Manager;
+        STATE = ATTR_sched_state, String, ReadOnly;
+        PBS_VERSION = ATTR_version, String, ReadOnly;
+    }
+}
+
+catalog! {
+    /// resource definition attributes
+    resource, RESOURCE {
+        TYPE = ATTR_RESC_TYPE, String, Manager;
+        FLAG = ATTR_RESC_FLAG, String, Manager;
+    }
+}
+
+/// The standard attributes of an object type
+pub fn attribute_defs(object: Resource) -> &'static [AttrDef] {
+    match object {
+        Resource::Job => JOB,
+        Resource::Que => QUEUE,
+        Resource::Server => SERVER,
+        Resource::Vnode | Resource::Hostname => NODE,
+        Resource::Reservation => RESERVATION,
+        Resource::Scheduler => SCHEDULER,
+        Resource::Resource => RESOURCE,
+    }
+}
+
+/// Definition of a standard attribute, names are case insensitive as they are on the server
+pub fn lookup_attribute(object: Resource, name: &str) -> Option<&'static AttrDef> {
+    attribute_defs(object)
+        .iter()
+        .find(|a| a.name.eq_ignore_ascii_case(name))
+}
+
+/// Check that someone with `privilege` can set attribute `name` on `object`
+pub fn check_settable(
+    object: Resource,
+    name: &str,
+    privilege: Access,
+) -> Result<&'static AttrDef, String> {
+    let def = lookup_attribute(object, name)
+        .ok_or_else(|| format!("unknown {object:?} attribute {name}"))?;
+    if def.is_read_only() {
+        return Err(format!("{object:?} attribute {} is read only", def.name));
+    }
+    if !def.access.settable_by(privilege) {
+        return Err(format!(
+            "{object:?} attribute {} can only be set by {:?} or above",
+            def.name, def.access
+        ));
+    }
+    Ok(def)
+}
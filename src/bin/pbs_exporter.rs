@@ -0,0 +1,59 @@
+use pbs::{serve_metrics, JobLabel, MetricsConfig};
+use std::process::exit;
+
+const USAGE: &str = "usage: pbs_exporter [--listen <addr>] [--server <host[:port]>] \
+[--job-labels user,queue,state,project] [--max-series <n>] [--no-nodes]";
+
+fn main() {
+    let mut listen = "127.0.0.1:9307".to_string();
+    let mut server = None;
+    let mut cfg = MetricsConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next().unwrap_or_else(|| {
+                eprintln!("{arg} needs a value\n{USAGE}");
+                exit(1)
+            })
+        };
+        match arg.as_str() {
+            "--listen" => listen = value(),
+            "--server" => server = Some(value()),
+            "--max-series" => {
+                cfg.max_series = value().parse().unwrap_or_else(|e| {
+                    eprintln!("invalid --max-series: {e}");
+                    exit(1)
+                })
+            }
+            "--job-labels" => {
+                cfg.job_labels = value()
+                    .split(',')
+                    .filter(|l| !l.is_empty())
+                    .map(|l| match l {
+                        "user" => JobLabel::User,
+                        "queue" => JobLabel::Queue,
+                        "state" => JobLabel::State,
+                        "project" => JobLabel::Project,
+                        _ => {
+                            eprintln!("unknown job label {l}\n{USAGE}");
+                            exit(1)
+                        }
+                    })
+                    .collect()
+            }
+            "--no-nodes" => cfg.nodes = false,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            _ => {
+                eprintln!("unknown argument {arg}\n{USAGE}");
+                exit(1)
+            }
+        }
+    }
+    if let Err(e) = serve_metrics(listen.as_str(), server.as_deref(), &cfg) {
+        eprintln!("{e}");
+        exit(1);
+    }
+}
@@ -0,0 +1,238 @@
+use pbs::{Align, Attribs, Column, Preset, Resource, ResvModFlag, Server, StatResp, Table};
+use serde_json::{Map, Value};
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str =
+    "usage: pbs-rs [--server <host[:port]>] [--format table|json|text|csv|ndjson] [--wide] <command>
+commands:
+    stat <job|node|queue|resv|server|sched> [name] [filter...]
+    submit <script> [--queue <queue>] [attr=value...]
+    del <jobid...>
+    hold <jobid...> [--type <u|o|s|p>]
+    release <jobid...> [--type <u|o|s|p>]
+    offline <vnode...> [--comment <comment>]
+    clear <vnode...> [--comment <comment>]
+    resv create <attr=value...>
+    resv modify <resvid> <attr=value...> [--force]
+    resv delete <resvid...>
+filters use the form name[.resource](=|!=|<|>|<=|>=)value, attributes name[.resource]=value";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+    Ndjson,
+    Text,
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{msg}");
+    exit(1)
+}
+
+// table layout for each kind of object
+fn table(kind: Resource, wide: bool) -> Table {
+    let table = match kind {
+        Resource::Job => Table::preset(Preset::Qstat),
+        Resource::Vnode | Resource::Hostname => Table::preset(Preset::PbsnodesJobs),
+        Resource::Que => Table::preset(Preset::QstatQueue),
+        Resource::Reservation => Table::preset(Preset::PbsRstat),
+        Resource::Server => Table::new()
+            .column(Column::name("Server"))
+            .column(Column::new("State", "server_state"))
+            .column(Column::new("Jobs", "total_jobs").align(Align::Right)),
+        Resource::Scheduler => Table::new()
+            .column(Column::name("Sched"))
+            .column(Column::new("Scheduling", "scheduling"))
+            .column(Column::new("State", "state")),
+        Resource::Resource => Table::new()
+            .column(Column::name("Resource"))
+            .column(Column::new("Type", "type"))
+            .column(Column::new("Flag", "flag")),
+    };
+    table.wide(wide)
+}
+
+fn print(resp: &StatResp, kind: Resource, format: Format, wide: bool) {
+    match format {
+        Format::Table => print!("{}", table(kind, wide).render(resp)),
+        Format::Text => print!("{}", resp.text(kind)),
+        Format::Csv => print!("{}", resp.csv()),
+        Format::Ndjson => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            print!("{}", resp.ndjson(now))
+        }
+        Format::Json => {
+            let mut map = Map::new();
+            for s in &resp.resources {
+                map.insert(s.name(), s.attribs().json());
+            }
+            println!("{}", Value::Object(map));
+        }
+    }
+}
+
+fn stat(srv: &Server, args: &[String], format: Format, wide: bool) {
+    let kind = match args.first().map(String::as_str) {
+        Some("job") => Resource::Job,
+        Some("node") => Resource::Vnode,
+        Some("queue") => Resource::Que,
+        Some("resv") => Resource::Reservation,
+        Some("server") => Resource::Server,
+        Some("sched") => Resource::Scheduler,
+        _ => fail(USAGE),
+    };
+    let mut args = &args[1..];
+    let is_filter = |a: &String| a.contains(['=', '<', '>']);
+    // a leading argument that isn't a filter names the object
+    let name = match args.first() {
+        Some(a) if !is_filter(a) => {
+            args = &args[1..];
+            Some(a.clone())
+        }
+        _ => None,
+    };
+    if let Some(a) = args.iter().find(|a| !is_filter(a)) {
+        fail(&format!(
+            "{a} isn't a filter, only the first argument names an object\n{USAGE}"
+        ));
+    }
+    let filter = Attribs::from(&args.to_vec());
+    let resp = match (kind, name) {
+        // job filters are evaluated by the server
+        (Resource::Job, None) => srv.stat_job(filter, None),
+        (kind, name) => {
+            if args.iter().any(|a| a.contains(['<', '>'])) {
+                fail("only = and != filters are supported unless listing jobs");
+            }
+            let resp = match kind {
+                Resource::Job => srv.stat_job_id(&name.unwrap_or_default(), None),
+                Resource::Vnode => srv.stat_vnode(&name, None),
+                Resource::Que => srv.stat_que(&name, None),
+                Resource::Reservation => srv.stat_reservation(&name, None),
+                Resource::Server => srv.stat_server(&name, None),
+                _ => srv.stat_scheduler(&name, None),
+            };
+            resp.map(|mut r| {
+                r.resources.retain(|s| s.attribs().check_filter(&filter));
+                r
+            })
+        }
+    };
+    match resp {
+        Ok(r) => print(&r, kind, format, wide),
+        Err(e) => fail(&e),
+    }
+}
+
+fn assignments(args: &[String]) -> Attribs {
+    Attribs::from_assignments(args).unwrap_or_else(|e| fail(&format!("{e}\n{USAGE}")))
+}
+
+// run `f` on each id, reporting failures and exiting non zero if any failed
+fn each<F: Fn(&str) -> Result<(), String>>(ids: &[String], f: F) {
+    if ids.is_empty() {
+        fail(USAGE);
+    }
+    let mut failed = false;
+    for id in ids {
+        if let Err(e) = f(id) {
+            eprintln!("{id}: {e}");
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn main() {
+    let mut server = None;
+    let mut format = Format::Table;
+    let mut wide = false;
+    let mut queue = String::new();
+    let mut comment = None;
+    let mut hold_type = "u".to_string();
+    let mut force = false;
+    let mut pos = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(&format!("{arg} needs a value\n{USAGE}")))
+        };
+        match arg.as_str() {
+            "--server" => server = Some(value()),
+            "--format" => {
+                format = match value().as_str() {
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    "text" => Format::Text,
+                    "csv" => Format::Csv,
+                    "ndjson" => Format::Ndjson,
+                    f => fail(&format!("unknown format {f}\n{USAGE}")),
+                }
+            }
+            "--wide" => wide = true,
+            "--queue" => queue = value(),
+            "--comment" => comment = Some(value()),
+            "--type" => hold_type = value(),
+            "--force" => force = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            _ => pos.push(arg),
+        }
+    }
+    if pos.is_empty() {
+        fail(USAGE);
+    }
+    let srv = match server {
+        Some(s) => Server::connect_to(&s).unwrap_or_else(|e| fail(&e)),
+        None => Server::new(),
+    };
+    let args = &pos[1..];
+    match pos[0].as_str() {
+        "stat" => stat(&srv, args, format, wide),
+        "submit" => {
+            let script = args.first().unwrap_or_else(|| fail(USAGE));
+            let attribs = assignments(&args[1..]);
+            match srv.submit_job(attribs, script, &queue) {
+                Ok(id) => println!("{id}"),
+                Err(e) => fail(&e),
+            }
+        }
+        "del" => each(args, |id| srv.del_job(id)),
+        "hold" => each(args, |id| srv.hold_job(id, &hold_type)),
+        "release" => each(args, |id| srv.release_job(id, &hold_type)),
+        "offline" => each(args, |n| srv.offline_vnode(n, comment.as_deref())),
+        "clear" => each(args, |n| srv.clear_vnode(n, comment.as_deref())),
+        "resv" => match args.first().map(String::as_str) {
+            Some("create") => match srv.submit_resv(assignments(&args[1..]), Vec::new()) {
+                Ok(id) => println!("{id}"),
+                Err(e) => fail(&e),
+            },
+            Some("modify") => {
+                let id = args.get(1).unwrap_or_else(|| fail(USAGE));
+                let flags = if force {
+                    vec![ResvModFlag::Force]
+                } else {
+                    Vec::new()
+                };
+                match srv.mod_resv(id, assignments(&args[2..]), flags) {
+                    Ok(r) => println!("{}: {:?} {}", r.id, r.status, r.message),
+                    Err(e) => fail(&e),
+                }
+            }
+            Some("delete") => each(&args[1..], |id| srv.del_resv(id)),
+            _ => fail(USAGE),
+        },
+        _ => fail(USAGE),
+    }
+}
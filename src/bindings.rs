@@ -1,5 +1,5 @@
 use crate::helpers;
-use crate::types::{Attribs, Attrl};
+use crate::types::{Attribs, Attrl, Op};
 use linked_list_c::{ConstList, CustomList};
 use log::{debug, trace};
 use pbs_sys::attrl;
@@ -10,8 +10,8 @@ use pbs_sys as ffi;
 
 pub mod stat {
     pub use super::ffi::{
-        pbs_selstat, pbs_statfree, pbs_stathost, pbs_statque, pbs_statresv, pbs_statrsc,
-        pbs_statsched, pbs_statserver, pbs_statvnode,
+        pbs_selstat, pbs_statfree, pbs_stathook, pbs_stathost, pbs_statjob, pbs_statque,
+        pbs_statresv, pbs_statrsc, pbs_statsched, pbs_statserver, pbs_statvnode,
     };
 }
 
@@ -28,43 +28,43 @@ pub fn get_err() -> String {
     }
 }
 
+// build an attrl list from (name, resource, op) entries
+// unlike Attribs this allows the same attribute to appear more than once
+pub(crate) fn attrl_list(entries: Vec<(String, Option<String>, Op)>) -> ConstList<'static, attrl> {
+    let mut list: CustomList<attrl> = unsafe {
+        CustomList::from(ptr::null_mut(), |x| {
+            _ = Box::from_raw(x);
+        })
+    };
+    for (name, resource, op) in entries {
+        trace!("Adding {name}.{resource:?} {op:?}");
+        list.add(Box::into_raw(Box::new(attrl {
+            name: helpers::str_to_cstr(&name),
+            value: helpers::str_to_cstr(&op.val()),
+            resource: helpers::optstr_to_cstr(resource.as_deref()),
+            op: op.op(),
+            next: ptr::null_mut(),
+        })));
+    }
+    list.into()
+}
+
 impl From<Attribs> for ConstList<'_, attrl> {
     fn from(attribs: Attribs) -> ConstList<'static, attrl> {
         debug!("Converting Attribs to ConstList<attrl>");
-        let mut list: CustomList<attrl> = unsafe {
-            CustomList::from(ptr::null_mut(), |x| {
-                _ = Box::from_raw(x);
-            })
-        };
+        let mut entries = Vec::new();
         for (name, val) in attribs.attribs().iter() {
             match val {
-                Attrl::Value(v) => {
-                    trace!("Adding {name} {val:?}");
-                    let at = Box::into_raw(Box::new(attrl {
-                        name: helpers::str_to_cstr(name),
-                        value: helpers::str_to_cstr(&v.val()),
-                        resource: ptr::null_mut(),
-                        op: v.op(),
-                        next: ptr::null_mut(),
-                    }));
-                    list.add(at);
-                }
-
+                Attrl::Value(v) => entries.push((name.clone(), None, v.clone())),
                 Attrl::Resource(map) => {
                     for (r, v) in map.iter() {
-                        trace!("Adding {name}.{r} {v:?}");
-                        list.add(Box::into_raw(Box::new(attrl {
-                            name: helpers::str_to_cstr(name),
-                            value: helpers::str_to_cstr(&v.val()),
-                            resource: helpers::str_to_cstr(r),
-                            op: v.op(),
-                            next: ptr::null_mut(),
-                        })));
+                        entries.push((name.clone(), Some(r.clone()), v.clone()));
                     }
                 }
             };
         }
+        let list = attrl_list(entries);
         trace!("Converted Attribs to ConstList<attrl>");
-        list.into()
+        list
     }
 }
@@ -0,0 +1,1143 @@
+//! Declarative server configuration, qmgr as code
+//!
+//! A config file holds qmgr commands, as printed by `qmgr -c "print server"`, describing
+//! the desired server, queue, resource, hook and node attributes:
+//! ```text
+//! create resource ngpus_a100 type=long, flag=nh
+//! create queue workq queue_type=execution, enabled=True
+//! set queue workq resources_max.walltime = 24:00:00
+//! unset queue workq max_run
+//! delete queue oldq
+//! set server default_queue = workq
+//! set hook cgroups event = "execjob_begin,execjob_end"
+//! import hook cgroups application/x-python default cgroups.py
+//! ```
+//! ServerConfig::plan diffs it against the live server into the create, set, unset and
+//! delete steps needed, and Plan::apply runs them in dependency order.
+use crate::attributes::{self, queue, ValueType};
+use crate::types::{
+    diff_values, same_list, same_value, AttrChange, Resource, ResourceDef, ResourceFlags,
+    ResourceType, Server, Status,
+};
+use crate::{ContentEncoding, HookConfig, HookContent, QueueConfig, QueueType};
+use log::{debug, info, trace};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A configured object
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Object {
+    Server,
+    Resource(String),
+    Queue(String),
+    Node(String),
+    Hook(String),
+}
+
+impl Object {
+    /// object name, empty for the server
+    pub fn name(&self) -> &str {
+        match self {
+            Object::Server => "",
+            Object::Resource(n) | Object::Queue(n) | Object::Node(n) | Object::Hook(n) => n,
+        }
+    }
+    fn kind(&self) -> &'static str {
+        match self {
+            Object::Server => "server",
+            Object::Resource(_) => "resource",
+            Object::Queue(_) => "queue",
+            Object::Node(_) => "node",
+            Object::Hook(_) => "hook",
+        }
+    }
+    fn mgr_obj(&self) -> pbs_sys::mgr_obj {
+        match self {
+            Object::Server => pbs_sys::mgr_obj_MGR_OBJ_SERVER,
+            Object::Resource(_) => pbs_sys::mgr_obj_MGR_OBJ_RSC,
+            Object::Queue(_) => pbs_sys::mgr_obj_MGR_OBJ_QUEUE,
+            Object::Node(_) => pbs_sys::mgr_obj_MGR_OBJ_NODE,
+            Object::Hook(_) => pbs_sys::mgr_obj_MGR_OBJ_HOOK,
+        }
+    }
+    // object type in the attribute catalog
+    fn catalog(&self) -> Option<Resource> {
+        match self {
+            Object::Server => Some(Resource::Server),
+            Object::Resource(_) => Some(Resource::Resource),
+            Object::Queue(_) => Some(Resource::Que),
+            Object::Node(_) => Some(Resource::Vnode),
+            Object::Hook(_) => None,
+        }
+    }
+
+    // whether an attribute holds an unordered list, e.g. an ACL
+    fn is_list(&self, key: &str) -> bool {
+        let name = key.split('.').next().unwrap_or(key);
+        self.catalog()
+            .and_then(|o| attributes::lookup_attribute(o, name))
+            .is_some_and(|d| matches!(d.value_type, ValueType::Acl | ValueType::StringArray))
+    }
+
+    // attribute name as the server reports it, names are case insensitive
+    fn canonical(&self, key: &str) -> String {
+        let (name, resource) = match key.split_once('.') {
+            Some((n, r)) => (n, Some(r)),
+            None => (key, None),
+        };
+        let name = self
+            .catalog()
+            .and_then(|o| attributes::lookup_attribute(o, name))
+            .map_or(name, |d| d.name);
+        match resource {
+            Some(r) => format!("{name}.{r}"),
+            None => name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Server => write!(f, "server"),
+            o => write!(f, "{} {}", o.kind(), o.name()),
+        }
+    }
+}
+
+/// Desired state of one object
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectConfig {
+    /// values keyed by name or name.resource
+    pub attributes: BTreeMap<String, String>,
+    /// attributes to unset, a name without a resource unsets all of its resources
+    pub unset: BTreeSet<String>,
+    /// whether the object should be deleted
+    pub delete: bool,
+}
+
+/// Hook content to import from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookImport {
+    pub content: HookContent,
+    pub encoding: ContentEncoding,
+    pub path: PathBuf,
+}
+
+/// Desired configuration of a server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub server: ObjectConfig,
+    pub resources: BTreeMap<String, ObjectConfig>,
+    pub queues: BTreeMap<String, ObjectConfig>,
+    pub nodes: BTreeMap<String, ObjectConfig>,
+    pub hooks: BTreeMap<String, ObjectConfig>,
+    pub imports: BTreeMap<String, Vec<HookImport>>,
+}
+
+// split "word rest" on the first whitespace
+fn next_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.split_once(char::is_whitespace) {
+        Some((w, r)) => (w, r.trim_start()),
+        None => (s, ""),
+    }
+}
+
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+    match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(s) => s.to_string(),
+        None => s.to_string(),
+    }
+}
+
+// quote values qmgr would otherwise split
+fn quote(s: &str) -> String {
+    if s.contains([',', ' ', '\t']) {
+        format!("\"{s}\"")
+    } else {
+        s.to_string()
+    }
+}
+
+// "a = 1, b.c+=x,y" -> [(a, false, 1), (b.c, true, "x,y")]
+// commas outside quotes only start a new assignment when followed by name=
+fn assignments(s: &str) -> Result<Vec<(String, bool, String)>, String> {
+    let start = Regex::new(r"^\s*([\w.-]+)\s*(\+?=)(.*)$").unwrap();
+    let mut pieces = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                cur.push(c);
+            }
+            ',' if !quoted => pieces.push(std::mem::take(&mut cur)),
+            c => cur.push(c),
+        }
+    }
+    pieces.push(cur);
+    let mut out: Vec<(String, bool, String)> = Vec::new();
+    for piece in pieces {
+        match (start.captures(&piece), out.last_mut()) {
+            (Some(c), _) => out.push((c[1].to_string(), &c[2] == "+=", c[3].to_string())),
+            (None, Some(last)) => {
+                last.2.push(',');
+                last.2.push_str(&piece);
+            }
+            (None, None) if piece.trim().is_empty() => (),
+            (None, None) => return Err(format!("expected name=value, got {piece:?}")),
+        }
+    }
+    Ok(out
+        .into_iter()
+        .map(|(n, append, v)| (n, append, unquote(&v)))
+        .collect())
+}
+
+impl ServerConfig {
+    /// load a config file, hook files are relative to its directory
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ServerConfig, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("failed to read {path:?}: {e}"))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        ServerConfig::parse(&text, base).map_err(|e| format!("{}:{e}", path.display()))
+    }
+
+    /// parse qmgr commands, hook files are relative to `base`
+    pub fn parse(text: &str, base: &Path) -> Result<ServerConfig, String> {
+        let mut config = ServerConfig::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            config
+                .command(line, base)
+                .map_err(|e| format!("{}: {e}", i + 1))?;
+        }
+        Ok(config)
+    }
+
+    fn object(&mut self, kind: &str, name: &str) -> Result<&mut ObjectConfig, String> {
+        if name.is_empty() && !matches!(kind, "server" | "s") {
+            return Err(format!("{kind} needs a name"));
+        }
+        let map = match kind {
+            "server" | "s" => return Ok(&mut self.server),
+            "resource" => &mut self.resources,
+            "queue" | "q" => &mut self.queues,
+            "node" | "n" => &mut self.nodes,
+            "hook" => &mut self.hooks,
+            k => return Err(format!("unsupported object type {k}")),
+        };
+        Ok(map.entry(name.to_string()).or_default())
+    }
+
+    fn command(&mut self, line: &str, base: &Path) -> Result<(), String> {
+        let (cmd, rest) = next_word(line);
+        let (kind, rest) = next_word(rest);
+        let (name, rest) = match kind {
+            "server" | "s" => ("", rest),
+            _ => next_word(rest),
+        };
+        if matches!(cmd, "create" | "c" | "delete" | "d") && matches!(kind, "server" | "s") {
+            return Err("the server can't be created or deleted".to_string());
+        }
+        match cmd {
+            "create" | "c" | "set" | "s" => {
+                let obj = self.object(kind, name)?;
+                obj.delete = false;
+                for (attr, append, val) in assignments(rest)? {
+                    match obj.attributes.get_mut(&attr) {
+                        Some(v) if append => {
+                            v.push(',');
+                            v.push_str(&val);
+                        }
+                        // the live value isn't known until plan, so there's nothing to append to
+                        None if append => {
+                            return Err(format!("{attr} += needs an earlier {attr} ="))
+                        }
+                        _ => {
+                            obj.unset.remove(&attr);
+                            obj.attributes.insert(attr, val);
+                        }
+                    }
+                }
+            }
+            "unset" | "u" => {
+                let obj = self.object(kind, name)?;
+                for attr in rest.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+                    obj.attributes.remove(attr);
+                    obj.unset.insert(attr.to_string());
+                }
+            }
+            "delete" | "d" => self.object(kind, name)?.delete = true,
+            "import" | "i" if kind == "hook" => {
+                let (content, rest) = next_word(rest);
+                let (encoding, rest) = next_word(rest);
+                let file = unquote(rest);
+                if file.is_empty() {
+                    return Err("import needs a content type, encoding and file".to_string());
+                }
+                self.object(kind, name)?;
+                let import = HookImport {
+                    content: content.parse()?,
+                    encoding: encoding.parse()?,
+                    path: base.join(file),
+                };
+                let imports = self.imports.entry(name.to_string()).or_default();
+                imports.retain(|i| i.content != import.content);
+                imports.push(import);
+            }
+            c => return Err(format!("unsupported command {c}")),
+        }
+        Ok(())
+    }
+
+    /// Steps turning the live server into this configuration
+    ///
+    /// Attributes that aren't configured are left alone, use unset to remove them.
+    /// Queues and hooks are diffed as QueueConfig and HookConfig, so a queue resource
+    /// map that is configured is exact, attributes they don't have are compared as
+    /// they are like those of the server and nodes.
+    /// With `prune` queues and hooks that aren't configured are deleted, resources and
+    /// nodes are only deleted when asked to. Hook content is compared with the live hook
+    /// when `pbs_home` is given, otherwise it's always imported.
+    pub fn plan(&self, srv: &Server, prune: bool, pbs_home: Option<&Path>) -> Result<Plan, String> {
+        if self.server.delete {
+            return Err("the server can't be deleted".to_string());
+        }
+        let mut steps = Vec::new();
+        if !self.resources.is_empty() {
+            self.plan_resources(srv, &mut steps)?;
+        }
+        if self.server.attributes.len() + self.server.unset.len() > 0 {
+            let resp = srv.stat_server(&None, None)?;
+            let live = resp.resources.first().ok_or("no server status")?;
+            plan_object(&mut steps, Object::Server, &self.server, Some(live))?;
+        }
+        for (kind, configs) in [
+            (Object::Queue as fn(String) -> Object, &self.queues),
+            (Object::Node, &self.nodes),
+            (Object::Hook, &self.hooks),
+        ] {
+            let prune = prune && !matches!(kind(String::new()), Object::Node(_));
+            if configs.is_empty() && !prune {
+                continue;
+            }
+            let resp = match kind(String::new()) {
+                Object::Queue(_) => srv.stat_que(&None, None)?,
+                Object::Hook(_) => srv.stat_hook(&None, None)?,
+                _ => srv.stat_vnode(&None, None)?,
+            };
+            let live: BTreeMap<String, &Status> =
+                resp.resources.iter().map(|s| (s.name(), s)).collect();
+            for (name, config) in configs {
+                plan_object(
+                    &mut steps,
+                    kind(name.clone()),
+                    config,
+                    live.get(name).copied(),
+                )?;
+            }
+            if prune {
+                for name in live.keys().filter(|n| !configs.contains_key(*n)) {
+                    steps.push(Step::Delete(kind(name.clone())));
+                }
+            }
+        }
+        self.plan_imports(srv, pbs_home, &mut steps)?;
+        steps.sort_by_key(Step::order);
+        Ok(Plan { steps })
+    }
+
+    fn plan_resources(&self, srv: &Server, steps: &mut Vec<Step>) -> Result<(), String> {
+        let live: BTreeMap<String, ResourceDef> = srv
+            .resource_defs()?
+            .into_iter()
+            .map(|d| (d.name.clone(), d))
+            .collect();
+        for (name, config) in &self.resources {
+            let obj = Object::Resource(name.clone());
+            let canonical: BTreeMap<String, String> = config
+                .attributes
+                .iter()
+                .map(|(k, v)| (obj.canonical(k), v.clone()))
+                .collect();
+            let get_type = || canonical.get(attributes::resource::TYPE);
+            let get_flag = || canonical.get(attributes::resource::FLAG);
+            match live.get(name) {
+                Some(_) if config.delete => steps.push(Step::Delete(obj)),
+                None if config.delete => (),
+                None => {
+                    let resource_type = get_type()
+                        .ok_or_else(|| format!("resource {name} needs a type to be created"))?
+                        .parse()?;
+                    let mut def = ResourceDef::new(name, resource_type);
+                    if let Some(f) = get_flag() {
+                        def = def.flags(f)?;
+                    }
+                    let attrs = def
+                        .entries()
+                        .into_iter()
+                        .map(|(k, v)| AttrChange::Set(k, v))
+                        .collect();
+                    steps.push(Step::Create(obj, attrs));
+                }
+                Some(current) => {
+                    let mut set = Vec::new();
+                    let mut unset = Vec::new();
+                    if let Some(t) = get_type() {
+                        let t: ResourceType = t.parse()?;
+                        if t != current.resource_type {
+                            set.push(AttrChange::Set(
+                                attributes::resource::TYPE.to_string(),
+                                t.to_string(),
+                            ));
+                        }
+                    }
+                    if let Some(f) = get_flag() {
+                        let f: ResourceFlags = f.parse()?;
+                        if f != current.flags {
+                            let name = attributes::resource::FLAG.to_string();
+                            if f.is_empty() {
+                                unset.push(AttrChange::Unset(name));
+                            } else {
+                                set.push(AttrChange::Set(name, f.to_string()));
+                            }
+                        }
+                    }
+                    if !set.is_empty() {
+                        steps.push(Step::Set(obj.clone(), set));
+                    }
+                    if !unset.is_empty() {
+                        steps.push(Step::Unset(obj, unset));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn plan_imports(
+        &self,
+        srv: &Server,
+        pbs_home: Option<&Path>,
+        steps: &mut Vec<Step>,
+    ) -> Result<(), String> {
+        for (hook, imports) in &self.imports {
+            if self.hooks.get(hook).is_some_and(|h| h.delete) {
+                continue;
+            }
+            let created = steps
+                .iter()
+                .any(|s| matches!(s, Step::Create(Object::Hook(h), _) if h == hook));
+            for import in imports {
+                let data = fs::read(&import.path)
+                    .map_err(|e| format!("failed to read {:?}: {e}", import.path))?;
+                let changed = match pbs_home {
+                    Some(home) if !created => srv
+                        .export_hook(hook, import.content, import.encoding, home)
+                        .map_or(true, |current| current != data),
+                    _ => true,
+                };
+                if changed {
+                    steps.push(Step::Import {
+                        hook: hook.clone(),
+                        import: import.clone(),
+                        data,
+                    });
+                } else {
+                    trace!("{:?} of hook {hook} is up to date", import.content);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// steps for one queue, node, hook or the server given its live status, None if it doesn't exist
+fn plan_object(
+    steps: &mut Vec<Step>,
+    obj: Object,
+    config: &ObjectConfig,
+    live: Option<&Status>,
+) -> Result<(), String> {
+    // attributes QueueConfig or HookConfig have a field for, the others are compared as they are
+    let (typed, other): (BTreeMap<_, _>, BTreeMap<_, _>) = config
+        .attributes
+        .iter()
+        .map(|(k, v)| (obj.canonical(k), v.clone()))
+        .partition(|(k, _)| match obj {
+            Object::Queue(_) => QueueConfig::supports(k),
+            Object::Hook(_) => HookConfig::supports(k),
+            _ => false,
+        });
+    let sets = |values: BTreeMap<String, String>| -> Vec<AttrChange> {
+        values
+            .into_iter()
+            .map(|(k, v)| AttrChange::Set(k, v))
+            .collect()
+    };
+    let hook_sets = |values: Vec<(&str, String)>| -> Vec<AttrChange> {
+        values
+            .into_iter()
+            .map(|(k, v)| AttrChange::Set(k.to_string(), v))
+            .collect()
+    };
+    let live = match live {
+        Some(_) if config.delete => {
+            steps.push(Step::Delete(obj));
+            return Ok(());
+        }
+        None if config.delete => return Ok(()),
+        None => {
+            match obj {
+                Object::Queue(_) => {
+                    let mut values = QueueConfig::try_from(&typed)?.flat();
+                    values.extend(other);
+                    steps.push(Step::Create(obj, sets(values)));
+                }
+                // hooks are created empty and set afterwards
+                Object::Hook(_) => {
+                    let mut attrs = hook_sets(HookConfig::try_from(&typed)?.values());
+                    attrs.extend(sets(other));
+                    steps.push(Step::Create(obj.clone(), Vec::new()));
+                    if !attrs.is_empty() {
+                        steps.push(Step::Set(obj, attrs));
+                    }
+                }
+                _ => steps.push(Step::Create(obj, sets(other))),
+            }
+            return Ok(());
+        }
+        Some(live) => live,
+    };
+    let current = live.attribs().flat();
+    let mut changes = match obj {
+        Object::Queue(_) => QueueConfig::try_from(&typed)?.diff(&QueueConfig::from(live)),
+        Object::Hook(_) => hook_sets(HookConfig::try_from(&typed)?.diff(&HookConfig::from(live))),
+        _ => Vec::new(),
+    };
+    let (lists, scalars): (BTreeMap<_, _>, BTreeMap<_, _>) =
+        other.into_iter().partition(|(k, _)| obj.is_list(k));
+    changes.extend(diff_values(&scalars, &current, same_value));
+    changes.extend(diff_values(&lists, &current, same_list));
+    for k in config.unset.iter().map(|k| obj.canonical(k)) {
+        let prefix = format!("{k}.");
+        let is_set = current.keys().any(|l| *l == k || l.starts_with(&prefix));
+        if is_set && !changes.contains(&AttrChange::Unset(k.clone())) {
+            changes.push(AttrChange::Unset(k));
+        }
+    }
+    let (set, unset): (Vec<_>, Vec<_>) = changes
+        .into_iter()
+        .partition(|c| matches!(c, AttrChange::Set(..)));
+    if !set.is_empty() {
+        steps.push(Step::Set(obj.clone(), set));
+    }
+    if !unset.is_empty() {
+        steps.push(Step::Unset(obj, unset));
+    }
+    Ok(())
+}
+
+/// One operation on the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// create an object with these attributes
+    Create(Object, Vec<AttrChange>),
+    Set(Object, Vec<AttrChange>),
+    Unset(Object, Vec<AttrChange>),
+    Delete(Object),
+    /// load a hook's script or config file
+    Import {
+        hook: String,
+        import: HookImport,
+        data: Vec<u8>,
+    },
+}
+
+impl Step {
+    pub fn object(&self) -> Object {
+        match self {
+            Step::Create(o, _) | Step::Set(o, _) | Step::Unset(o, _) | Step::Delete(o) => o.clone(),
+            Step::Import { hook, .. } => Object::Hook(hook.clone()),
+        }
+    }
+
+    // Resources first as anything can use them, then queues which the server and
+    // nodes refer to, then hooks. Unsets and deletes go last, in reverse. Plans never
+    // create or delete the server.
+    fn order(&self) -> (u8, u8) {
+        let route = |attrs: &Vec<AttrChange>| {
+            attrs.iter().any(|a| match a {
+                AttrChange::Set(n, v) => {
+                    n == queue::QUEUE_TYPE && v.parse::<QueueType>() == Ok(QueueType::Route)
+                }
+                _ => false,
+            })
+        };
+        match self {
+            Step::Create(Object::Resource(_), _) => (0, 0),
+            Step::Set(Object::Resource(_), _) => (1, 0),
+            // route queues point at execution queues, so they're created after them
+            Step::Create(Object::Queue(_), a) => (2, route(a) as u8),
+            Step::Set(Object::Queue(_), _) => (3, 0),
+            Step::Set(Object::Server, _) => (4, 0),
+            Step::Create(Object::Node(_), _) => (5, 0),
+            Step::Set(Object::Node(_), _) => (6, 0),
+            Step::Create(Object::Hook(_), _) => (7, 0),
+            Step::Set(Object::Hook(_), _) => (8, 0),
+            Step::Import { .. } => (9, 0),
+            Step::Unset(..) => (10, 0),
+            Step::Delete(Object::Hook(_)) => (11, 0),
+            Step::Delete(Object::Queue(_)) => (12, 0),
+            Step::Delete(Object::Node(_)) => (13, 0),
+            Step::Delete(Object::Resource(_)) => (14, 0),
+            Step::Create(Object::Server, _) | Step::Delete(Object::Server) => {
+                unreachable!("the server can't be created or deleted")
+            }
+        }
+    }
+
+    fn run(&self, srv: &Server, pbs_home: Option<&Path>) -> Result<(), String> {
+        let obj = self.object();
+        match self {
+            Step::Create(_, attrs) => srv.manage(
+                pbs_sys::mgr_cmd_MGR_CMD_CREATE,
+                obj.mgr_obj(),
+                obj.name(),
+                attrs.iter().map(AttrChange::entry).collect(),
+            ),
+            Step::Set(_, changes) | Step::Unset(_, changes) => {
+                srv.apply_changes(obj.mgr_obj(), obj.name(), changes)
+            }
+            Step::Delete(_) => srv.manage(
+                pbs_sys::mgr_cmd_MGR_CMD_DELETE,
+                obj.mgr_obj(),
+                obj.name(),
+                Vec::new(),
+            ),
+            Step::Import { hook, import, data } => {
+                let home = pbs_home.ok_or("importing hooks needs PBS_HOME")?;
+                srv.import_hook(hook, import.content, import.encoding, data, home)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    /// as qmgr commands, one per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let obj = self.object();
+        match self {
+            Step::Create(_, attrs) => {
+                write!(f, "create {obj}")?;
+                for (i, a) in attrs.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    if let AttrChange::Set(n, v) = a {
+                        write!(f, "{sep}{n}={}", quote(v))?;
+                    }
+                }
+                Ok(())
+            }
+            Step::Set(_, changes) | Step::Unset(_, changes) => {
+                for (i, c) in changes.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    match c {
+                        AttrChange::Set(n, v) => write!(f, "set {obj} {n} = {}", quote(v))?,
+                        AttrChange::Unset(n) => write!(f, "unset {obj} {n}")?,
+                    }
+                }
+                Ok(())
+            }
+            Step::Delete(_) => write!(f, "delete {obj}"),
+            Step::Import { hook, import, .. } => write!(
+                f,
+                "import hook {hook} {} {} {}",
+                import.content.content_type(),
+                import.encoding.name(),
+                import.path.display()
+            ),
+        }
+    }
+}
+
+/// Ordered steps from ServerConfig::plan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Run every step in order
+    ///
+    /// A failed step doesn't stop the others, but later steps on the same object are
+    /// skipped. Steps depending on other objects, e.g. a server default_queue on a
+    /// queue that failed to be created, fail on the server side and are reported.
+    pub fn apply(&self, srv: &Server, pbs_home: Option<&Path>) -> ApplyReport {
+        self.run(|step| step.run(srv, pbs_home))
+    }
+
+    fn run<F: FnMut(&Step) -> Result<(), String>>(&self, mut run: F) -> ApplyReport {
+        let mut report = ApplyReport::default();
+        let mut failed = BTreeSet::new();
+        for step in &self.steps {
+            let obj = step.object();
+            if failed.contains(&obj) {
+                debug!("skipping {step}, an earlier step on {obj} failed");
+                report.skipped.push(step.clone());
+                continue;
+            }
+            match run(step) {
+                Ok(()) => {
+                    info!("applied {step}");
+                    report.applied.push(step.clone());
+                }
+                Err(e) => {
+                    info!("failed {step}: {e}");
+                    failed.insert(obj);
+                    report.failed.push((step.clone(), e));
+                }
+            }
+        }
+        report
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of Plan::apply
+#[derive(Debug, Clone, Default)]
+pub struct ApplyReport {
+    pub applied: Vec<Step>,
+    pub failed: Vec<(Step, String)>,
+    /// steps on objects an earlier step failed on
+    pub skipped: Vec<Step>,
+}
+
+impl ApplyReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.skipped.is_empty()
+    }
+}
+
+impl fmt::Display for ApplyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (step, e) in &self.failed {
+            writeln!(f, "failed: {step}: {e}")?;
+        }
+        for step in &self.skipped {
+            writeln!(f, "skipped: {step}")?;
+        }
+        writeln!(
+            f,
+            "{} applied, {} failed, {} skipped",
+            self.applied.len(),
+            self.failed.len(),
+            self.skipped.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Attribs, Attrl, Op, StatResp};
+
+    fn parse(text: &str) -> Result<ServerConfig, String> {
+        ServerConfig::parse(text, Path::new("/etc/pbs"))
+    }
+
+    #[test]
+    fn assignments_and_quoting() {
+        let c = parse(
+            "create queue workq queue_type=execution, enabled=True\n\
+             set queue workq resources_max.walltime = 24:00:00\n\
+             set hook cgroups event = \"execjob_begin,execjob_end\"\n",
+        )
+        .unwrap();
+        let q = &c.queues["workq"];
+        assert_eq!(q.attributes["queue_type"], "execution");
+        assert_eq!(q.attributes["enabled"], "True");
+        assert_eq!(q.attributes["resources_max.walltime"], "24:00:00");
+        assert_eq!(
+            c.hooks["cgroups"].attributes["event"],
+            "execjob_begin,execjob_end"
+        );
+    }
+
+    #[test]
+    fn continuation_commas() {
+        let c = parse(
+            "set server acl_hosts = a,b, c\nset server managers = x@h, y@h, scheduling = True",
+        )
+        .unwrap();
+        assert_eq!(c.server.attributes["acl_hosts"], "a,b, c");
+        assert_eq!(c.server.attributes["managers"], "x@h, y@h");
+        assert_eq!(c.server.attributes["scheduling"], "True");
+    }
+
+    #[test]
+    fn append() {
+        let c = parse("set server acl_hosts = a\nset server acl_hosts += b").unwrap();
+        assert_eq!(c.server.attributes["acl_hosts"], "a,b");
+        let e = parse("set server managers = x@h\nset server acl_hosts += b").unwrap_err();
+        assert!(e.starts_with("2: acl_hosts += needs"), "{e}");
+    }
+
+    #[test]
+    fn unset_and_delete() {
+        let c = parse(
+            "set queue q max_run = [u:PBS_GENERIC=10]\n\
+             unset queue q max_run, priority\n\
+             delete queue old\n",
+        )
+        .unwrap();
+        let q = &c.queues["q"];
+        assert!(q.attributes.is_empty());
+        assert_eq!(
+            q.unset.iter().map(String::as_str).collect::<Vec<_>>(),
+            ["max_run", "priority"]
+        );
+        assert!(c.queues["old"].delete);
+    }
+
+    #[test]
+    fn import() {
+        let c = parse("import hook h application/x-python default \"my hook.py\"").unwrap();
+        let i = &c.imports["h"][0];
+        assert_eq!(i.content, HookContent::Script);
+        assert_eq!(i.path, Path::new("/etc/pbs/my hook.py"));
+        assert!(c.hooks.contains_key("h"));
+    }
+
+    #[test]
+    fn errors() {
+        for (text, line) in [
+            ("create server", 1),
+            ("set server a = 1\ndelete server", 2),
+            ("set queue", 1),
+            ("set queue q oops", 1),
+            ("frobnicate queue q", 1),
+            ("set widget w a = 1", 1),
+        ] {
+            let e = parse(text).unwrap_err();
+            assert!(e.starts_with(&format!("{line}: ")), "{text:?}: {e}");
+        }
+    }
+
+    #[test]
+    fn typed_objects() {
+        let c = parse(
+            "create queue q queue_type=e, enabled=true, acl_users=\"b,a\"\n\
+             set queue q resources_max.mem = 1gb\n\
+             set hook h event = \"queuejob,runjob\", order = 5\n",
+        )
+        .unwrap();
+        let q = QueueConfig::try_from(&c.queues["q"].attributes).unwrap();
+        assert_eq!(q.queue_type, Some(QueueType::Execution));
+        assert_eq!(q.acl_users, Some(vec!["b".to_string(), "a".to_string()]));
+        assert_eq!(
+            q.resources_max,
+            Some(BTreeMap::from([("mem".to_string(), "1gb".to_string())]))
+        );
+        assert_eq!(QueueConfig::try_from(&q.flat()), Ok(q));
+        let h = HookConfig::try_from(&c.hooks["h"].attributes).unwrap();
+        assert_eq!(h.order, Some(5));
+        assert_eq!(h.event.as_ref().map(Vec::len), Some(2));
+
+        let bad = BTreeMap::from([("kill_delay".to_string(), "10".to_string())]);
+        assert!(QueueConfig::try_from(&bad).is_err());
+        let bad = BTreeMap::from([("enabled".to_string(), "maybe".to_string())]);
+        assert!(HookConfig::try_from(&bad).is_err());
+    }
+
+    const QSTAT_QF: &str = "\
+Queue: workq
+    queue_type = Execution
+    Priority = 10
+    max_running = 100
+    max_user_run = 5
+    kill_delay = 10
+    resources_max.mem = 1024mb
+    resources_max.walltime = 24:00:00
+    resources_available.ncpus = 32
+    resources_default.walltime = 01:00:00
+    acl_user_enable = True
+    acl_users = +alice,-bob
+    enabled = True
+    started = True
+
+";
+
+    fn workq() -> StatResp {
+        crate::parse_text(QSTAT_QF).unwrap().1
+    }
+
+    fn hook(values: &[(&str, &str)]) -> Status {
+        let mut a = Attribs::new();
+        for (k, v) in values {
+            a.add(k.to_string(), Attrl::Value(Op::Default(v.to_string())));
+        }
+        Status::new("h".to_string(), None, a)
+    }
+
+    fn plan(text: &str, obj: Object, live: Option<&Status>) -> Result<Vec<Step>, String> {
+        let c = parse(text).unwrap();
+        let config = match &obj {
+            Object::Server => &c.server,
+            Object::Queue(n) => &c.queues[n],
+            Object::Node(n) => &c.nodes[n],
+            Object::Hook(n) => &c.hooks[n],
+            Object::Resource(n) => &c.resources[n],
+        };
+        let mut steps = Vec::new();
+        plan_object(&mut steps, obj, config, live)?;
+        Ok(steps)
+    }
+
+    fn set(name: &str, val: &str) -> AttrChange {
+        AttrChange::Set(name.to_string(), val.to_string())
+    }
+
+    fn unset(name: &str) -> AttrChange {
+        AttrChange::Unset(name.to_string())
+    }
+
+    #[test]
+    fn plan_unchanged_queue() {
+        // as printed by qmgr, with the values written differently
+        let text = "create queue workq queue_type = Execution\n\
+                    set queue workq Priority = 10\n\
+                    set queue workq max_user_run = 5\n\
+                    set queue workq resources_max.mem = 1gb\n\
+                    set queue workq resources_max.walltime = 86400\n\
+                    set queue workq acl_users = \"-bob,+alice\"\n\
+                    set queue workq enabled = true\n\
+                    unset queue workq max_queued, comment\n";
+        let q = Object::Queue("workq".to_string());
+        assert_eq!(plan(text, q, Some(&workq().resources[0])), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn plan_queue_changes() {
+        let text = "set queue workq priority = 20\n\
+                    set queue workq kill_delay = 20, max_user_res.ncpus = 8\n\
+                    set queue workq resources_max.mem = 1gb\n\
+                    set queue workq resources_available.ncpus = 64\n\
+                    unset queue workq max_running, resources_default\n";
+        let q = Object::Queue("workq".to_string());
+        assert_eq!(
+            plan(text, q.clone(), Some(&workq().resources[0])).unwrap(),
+            [
+                Step::Set(
+                    q.clone(),
+                    vec![
+                        set("Priority", "20"),
+                        set("kill_delay", "20"),
+                        set("max_user_res.ncpus", "8"),
+                        set("resources_available.ncpus", "64"),
+                    ]
+                ),
+                // walltime isn't in the configured resources_max
+                Step::Unset(
+                    q,
+                    vec![
+                        unset("resources_max.walltime"),
+                        unset("max_running"),
+                        unset("resources_default"),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_new_queue() {
+        let text = "create queue q queue_type = route, route_destinations = \"a,b\"\n\
+                    set queue q kill_delay = 10\n";
+        let q = Object::Queue("q".to_string());
+        assert_eq!(
+            plan(text, q.clone(), None).unwrap(),
+            [Step::Create(
+                q,
+                vec![
+                    set("kill_delay", "10"),
+                    set("queue_type", "route"),
+                    set("route_destinations", "a,b"),
+                ]
+            )]
+        );
+        let e = plan(
+            "set queue q started = maybe",
+            Object::Queue("q".to_string()),
+            None,
+        );
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn plan_hooks() {
+        let h = Object::Hook("h".to_string());
+        // print hook writes the type, which HookConfig doesn't have
+        let text = "create hook h\n\
+                    set hook h type = site\n\
+                    set hook h event = \"runjob,queuejob\", order = 5\n";
+        assert_eq!(
+            plan(text, h.clone(), None).unwrap(),
+            [
+                Step::Create(h.clone(), Vec::new()),
+                Step::Set(
+                    h.clone(),
+                    vec![
+                        set("event", "runjob,queuejob"),
+                        set("order", "5"),
+                        set("type", "site")
+                    ]
+                ),
+            ]
+        );
+        let live = hook(&[
+            ("type", "site"),
+            ("event", "queuejob,runjob"),
+            ("order", "5"),
+            ("alarm", "30"),
+        ]);
+        assert_eq!(plan(text, h.clone(), Some(&live)), Ok(Vec::new()));
+        let text = "set hook h order = 6\nunset hook h alarm, freq";
+        assert_eq!(
+            plan(text, h.clone(), Some(&live)).unwrap(),
+            [
+                Step::Set(h.clone(), vec![set("order", "6")]),
+                Step::Unset(h, vec![unset("alarm")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_delete() {
+        let q = Object::Queue("workq".to_string());
+        let text = "delete queue workq";
+        assert_eq!(
+            plan(text, q.clone(), Some(&workq().resources[0])).unwrap(),
+            [Step::Delete(q.clone())]
+        );
+        assert_eq!(plan(text, q, None), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn plan_new_node() {
+        let n = Object::Node("n1".to_string());
+        assert_eq!(
+            plan(
+                "create node n1 resources_available.ngpus = 4",
+                n.clone(),
+                None
+            )
+            .unwrap(),
+            [Step::Create(n, vec![set("resources_available.ngpus", "4")])]
+        );
+    }
+
+    #[test]
+    fn step_order() {
+        let q = |n: &str| Object::Queue(n.to_string());
+        let h = Object::Hook("h".to_string());
+        let route = Step::Create(q("r"), vec![set("queue_type", "route")]);
+        let exec = Step::Create(q("e"), vec![set("queue_type", "execution")]);
+        let import = Step::Import {
+            hook: "h".to_string(),
+            import: HookImport {
+                content: HookContent::Script,
+                encoding: ContentEncoding::Default,
+                path: PathBuf::from("h.py"),
+            },
+            data: Vec::new(),
+        };
+        let mut steps = vec![
+            Step::Delete(Object::Resource("old".to_string())),
+            Step::Delete(q("old")),
+            Step::Unset(Object::Server, vec![unset("comment")]),
+            import.clone(),
+            Step::Set(h.clone(), vec![set("order", "1")]),
+            Step::Create(h.clone(), Vec::new()),
+            Step::Set(Object::Server, vec![set("default_queue", "e")]),
+            route.clone(),
+            exec.clone(),
+            Step::Create(Object::Resource("ngpus".to_string()), Vec::new()),
+        ];
+        steps.reverse();
+        steps.sort_by_key(Step::order);
+        assert_eq!(
+            steps,
+            [
+                Step::Create(Object::Resource("ngpus".to_string()), Vec::new()),
+                exec,
+                route,
+                Step::Set(Object::Server, vec![set("default_queue", "e")]),
+                Step::Create(h.clone(), Vec::new()),
+                Step::Set(h, vec![set("order", "1")]),
+                import,
+                Step::Unset(Object::Server, vec![unset("comment")]),
+                Step::Delete(q("old")),
+                Step::Delete(Object::Resource("old".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_report() {
+        let a = Object::Queue("a".to_string());
+        let plan = Plan {
+            steps: vec![
+                Step::Create(a.clone(), vec![set("queue_type", "execution")]),
+                Step::Set(a.clone(), vec![set("enabled", "true")]),
+                Step::Set(Object::Server, vec![set("default_queue", "a")]),
+                Step::Unset(a, vec![unset("comment")]),
+            ],
+        };
+        let report = plan.run(|step| match step {
+            Step::Create(..) => Err("no permission".to_string()),
+            _ => Ok(()),
+        });
+        assert!(!report.is_success());
+        assert_eq!(report.applied, [plan.steps[2].clone()]);
+        assert_eq!(
+            report.failed,
+            [(plan.steps[0].clone(), "no permission".to_string())]
+        );
+        assert_eq!(
+            report.skipped,
+            [plan.steps[1].clone(), plan.steps[3].clone()]
+        );
+        assert_eq!(
+            report.to_string(),
+            "failed: create queue a queue_type=execution: no permission\n\
+             skipped: set queue a enabled = true\n\
+             skipped: unset queue a comment\n\
+             1 applied, 1 failed, 2 skipped\n"
+        );
+        assert!(plan.run(|_| Ok(())).is_success());
+    }
+}
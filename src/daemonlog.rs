@@ -0,0 +1,554 @@
+//! Parser for server, scheduler and MoM logs, `<PBS_HOME>/{server,sched,mom}_logs/YYYYMMDD`
+use crate::accounting::log_files;
+use chrono::NaiveDateTime;
+use log::{debug, trace};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+use std::ops::BitOr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Set of log event classes, the event_type field of a log line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct EventClass(u32);
+
+const NAMES: [(EventClass, &str); 13] = [
+    (EventClass::ERROR, "error"),
+    (EventClass::SYSTEM, "system"),
+    (EventClass::ADMIN, "admin"),
+    (EventClass::JOB, "job"),
+    (EventClass::JOB_USAGE, "job_usage"),
+    (EventClass::SECURITY, "security"),
+    (EventClass::SCHED, "sched"),
+    (EventClass::DEBUG, "debug"),
+    (EventClass::DEBUG2, "debug2"),
+    (EventClass::RESV, "resv"),
+    (EventClass::DEBUG3, "debug3"),
+    (EventClass::DEBUG4, "debug4"),
+    (EventClass::FORCE, "force"),
+];
+
+impl EventClass {
+    pub const NONE: EventClass = EventClass(0);
+    pub const ERROR: EventClass = EventClass(0x0001);
+    pub const SYSTEM: EventClass = EventClass(0x0002);
+    pub const ADMIN: EventClass = EventClass(0x0004);
+    pub const JOB: EventClass = EventClass(0x0008);
+    pub const JOB_USAGE: EventClass = EventClass(0x0010);
+    pub const SECURITY: EventClass = EventClass(0x0020);
+    pub const SCHED: EventClass = EventClass(0x0040);
+    pub const DEBUG: EventClass = EventClass(0x0080);
+    pub const DEBUG2: EventClass = EventClass(0x0100);
+    pub const RESV: EventClass = EventClass(0x0200);
+    pub const DEBUG3: EventClass = EventClass(0x0400);
+    pub const DEBUG4: EventClass = EventClass(0x0800);
+    pub const FORCE: EventClass = EventClass(0x8000);
+    pub const ALL: EventClass = EventClass(0x8fff);
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    pub fn contains(&self, other: EventClass) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn intersects(&self, other: EventClass) -> bool {
+        self.0 & other.0 != 0
+    }
+    /// names of the individual classes set
+    pub fn names(&self) -> Vec<&'static str> {
+        NAMES
+            .iter()
+            .filter(|(c, _)| self.contains(*c))
+            .map(|(_, n)| *n)
+            .collect()
+    }
+}
+
+impl BitOr for EventClass {
+    type Output = EventClass;
+    fn bitor(self, rhs: EventClass) -> EventClass {
+        EventClass(self.0 | rhs.0)
+    }
+}
+
+impl FromStr for EventClass {
+    type Err = String;
+    /// parse the hex mask as logged, e.g. "0008" or "0x0008", or a comma separated name list
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+        // no class name is all hex digits
+        let bare = !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+        if let Some(hex) = hex.or(bare.then_some(s)) {
+            return u32::from_str_radix(hex, 16)
+                .map(EventClass)
+                .map_err(|e| format!("invalid event type {s}: {e}"));
+        }
+        let mut class = EventClass::NONE;
+        for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match NAMES.iter().find(|(_, n)| *n == name) {
+                Some((c, _)) => class = class | *c,
+                None => return Err(format!("unknown event class: {name}")),
+            }
+        }
+        Ok(class)
+    }
+}
+
+impl fmt::Display for EventClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // as in the log, e.g. 0008
+        write!(f, "{:04x}", self.0)
+    }
+}
+
+/// Kind of object a log line is about
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ObjectType {
+    Server,
+    Queue,
+    Job,
+    Request,
+    File,
+    Accounting,
+    Node,
+    Reservation,
+    Scheduler,
+    Hook,
+    Resource,
+    Tpp,
+    Other(String),
+}
+
+impl ObjectType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ObjectType::Server => "Svr",
+            ObjectType::Queue => "Que",
+            ObjectType::Job => "Job",
+            ObjectType::Request => "Req",
+            ObjectType::File => "Fil",
+            ObjectType::Accounting => "Act",
+            ObjectType::Node => "Node",
+            ObjectType::Reservation => "Resv",
+            ObjectType::Scheduler => "Sched",
+            ObjectType::Hook => "Hook",
+            ObjectType::Resource => "Resc",
+            ObjectType::Tpp => "TPP",
+            ObjectType::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for ObjectType {
+    fn from(s: &str) -> ObjectType {
+        match s {
+            "Svr" => ObjectType::Server,
+            "Que" => ObjectType::Queue,
+            "Job" => ObjectType::Job,
+            "Req" => ObjectType::Request,
+            "Fil" => ObjectType::File,
+            "Act" => ObjectType::Accounting,
+            "Node" => ObjectType::Node,
+            "Resv" => ObjectType::Reservation,
+            "Sched" => ObjectType::Scheduler,
+            "Hook" => ObjectType::Hook,
+            "Resc" => ObjectType::Resource,
+            "TPP" => ObjectType::Tpp,
+            s => ObjectType::Other(s.to_string()),
+        }
+    }
+}
+
+/// A single daemon log entry
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// local time the line was written
+    pub time: NaiveDateTime,
+    pub event: EventClass,
+    /// e.g. Server@host, pbs_mom, pbs_sched
+    pub daemon: String,
+    pub object_type: ObjectType,
+    pub object_name: String,
+    /// message, including any continuation lines joined with '\n'
+    pub message: String,
+}
+
+/// Parse one daemon log line
+pub fn parse_log_line(line: &str) -> Result<LogEntry, String> {
+    let mut parts = line.splitn(6, ';');
+    let time = parts.next().unwrap_or("");
+    let time = NaiveDateTime::parse_from_str(time, "%m/%d/%Y %H:%M:%S")
+        .map_err(|e| format!("invalid log timestamp {time:?}: {e}"))?;
+    let mut next = |field: &str| {
+        parts
+            .next()
+            .ok_or_else(|| format!("missing {field} in: {line}"))
+    };
+    let event = next("event type")?.parse()?;
+    let daemon = next("daemon")?.to_string();
+    let object_type = next("object type")?.into();
+    let object_name = next("object name")?.to_string();
+    let message = parts.next().unwrap_or("").to_string();
+    Ok(LogEntry {
+        time,
+        event,
+        daemon,
+        object_type,
+        object_name,
+        message,
+    })
+}
+
+/// Streams entries from a daemon log
+///
+/// Lines without a timestamp, e.g. hook output, are added to the previous entry's message.
+pub struct LogReader<R> {
+    lines: Lines<R>,
+    line: usize,
+    pending: Option<LogEntry>,
+}
+
+impl<R: BufRead> LogReader<R> {
+    pub fn new(reader: R) -> LogReader<R> {
+        LogReader {
+            lines: reader.lines(),
+            line: 0,
+            pending: None,
+        }
+    }
+}
+
+impl LogReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        debug!("Opening daemon log {:?}", path.as_ref());
+        Ok(LogReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> Iterator for LogReader<R> {
+    type Item = Result<LogEntry, String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line += 1;
+            let line = match self.lines.next() {
+                Some(Ok(l)) => l,
+                Some(Err(e)) => return Some(Err(format!("line {}: {e}", self.line))),
+                None => return self.pending.take().map(Ok),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            trace!("log line {}: {line}", self.line);
+            match parse_log_line(&line) {
+                Ok(entry) => {
+                    if let Some(prev) = self.pending.replace(entry) {
+                        return Some(Ok(prev));
+                    }
+                }
+                Err(e) => match self.pending.as_mut() {
+                    Some(prev) => {
+                        prev.message.push('\n');
+                        prev.message.push_str(&line);
+                    }
+                    None => return Some(Err(format!("line {}: {e}", self.line))),
+                },
+            }
+        }
+    }
+}
+
+/// Which log entries to keep
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    job: Option<String>,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    events: EventClass,
+    object_types: Vec<ObjectType>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        LogFilter::new()
+    }
+}
+
+// job ids match if equal, or if one has no server suffix and the sequence numbers match
+fn same_job(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    match (a.split_once('.'), b.split_once('.')) {
+        (Some((a, _)), None) => a == b,
+        (None, Some((b, _))) => a == b,
+        _ => false,
+    }
+}
+
+impl LogFilter {
+    /// keeps everything
+    pub fn new() -> LogFilter {
+        LogFilter {
+            job: None,
+            from: None,
+            to: None,
+            events: EventClass::ALL,
+            object_types: Vec::new(),
+        }
+    }
+    /// only entries about this job, e.g. 1234 or 1234.server
+    pub fn job(mut self, id: &str) -> LogFilter {
+        self.job = Some(id.to_string());
+        self
+    }
+    pub fn from(mut self, time: NaiveDateTime) -> LogFilter {
+        self.from = Some(time);
+        self
+    }
+    pub fn to(mut self, time: NaiveDateTime) -> LogFilter {
+        self.to = Some(time);
+        self
+    }
+    /// only entries with any of these event classes
+    pub fn events(mut self, events: EventClass) -> LogFilter {
+        self.events = events;
+        self
+    }
+    /// only entries about this type of object, may be given more than once
+    pub fn object_type(mut self, object_type: ObjectType) -> LogFilter {
+        self.object_types.push(object_type);
+        self
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if self.from.is_some_and(|f| entry.time < f) || self.to.is_some_and(|t| entry.time > t) {
+            return false;
+        }
+        if !entry.event.intersects(self.events) {
+            return false;
+        }
+        if !self.object_types.is_empty() && !self.object_types.contains(&entry.object_type) {
+            return false;
+        }
+        match &self.job {
+            Some(id) => entry.object_type == ObjectType::Job && same_job(&entry.object_name, id),
+            None => true,
+        }
+    }
+}
+
+/// Stream entries matching `filter` from every log in `dir`, e.g. PBS_HOME/server_logs
+///
+/// Only files whose date is inside the filter's time range are opened.
+pub fn read_log_dir<P: AsRef<Path>>(
+    dir: P,
+    filter: LogFilter,
+) -> io::Result<impl Iterator<Item = Result<LogEntry, String>>> {
+    let files = log_files(
+        dir,
+        filter.from.map(|f| f.date()),
+        filter.to.map(|t| t.date()),
+    )?;
+    Ok(files
+        .into_iter()
+        .flat_map(
+            |path| -> Box<dyn Iterator<Item = Result<LogEntry, String>>> {
+                match LogReader::open(&path) {
+                    Ok(r) => {
+                        let p = path.display().to_string();
+                        Box::new(r.map(move |e| e.map_err(|err| format!("{p}: {err}"))))
+                    }
+                    Err(e) => Box::new(std::iter::once(Err(format!("{}: {e}", path.display())))),
+                }
+            },
+        )
+        .filter(move |e| match e {
+            Ok(entry) => filter.matches(entry),
+            Err(_) => true,
+        }))
+}
+
+/// Which daemon's logs an entry came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogSource {
+    Server,
+    Scheduler,
+    Mom,
+}
+
+impl LogSource {
+    /// log directory under PBS_HOME
+    pub fn dir(&self) -> &'static str {
+        match self {
+            LogSource::Server => "server_logs",
+            LogSource::Scheduler => "sched_logs",
+            LogSource::Mom => "mom_logs",
+        }
+    }
+}
+
+/// Every entry about `job` in the server, scheduler and MoM logs under `pbs_home`, in time order
+///
+/// Log directories that don't exist on this host are skipped, so on the server
+/// host this usually won't include MoM entries. Unparsable lines are skipped.
+pub fn job_timeline<P: AsRef<Path>>(
+    pbs_home: P,
+    job: &str,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+) -> io::Result<Vec<(LogSource, LogEntry)>> {
+    let mut filter = LogFilter::new().job(job);
+    filter.from = from;
+    filter.to = to;
+    let mut timeline = Vec::new();
+    for source in [LogSource::Server, LogSource::Scheduler, LogSource::Mom] {
+        let dir = pbs_home.as_ref().join(source.dir());
+        if !dir.is_dir() {
+            debug!("Skipping missing log dir {dir:?}");
+            continue;
+        }
+        for entry in read_log_dir(&dir, filter.clone())? {
+            match entry {
+                Ok(e) => timeline.push((source, e)),
+                Err(e) => debug!("Skipping bad log line: {e}"),
+            }
+        }
+    }
+    // stable sort keeps each daemon's order within the same second
+    timeline.sort_by_key(|(_, e)| e.time);
+    Ok(timeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const SERVER: &str = "\
+06/23/2020 10:12:14;0100;Server@pbsserver;Req;;Type 0 request received from user1@login01, sock=17
+06/23/2020 10:12:14;0008;Server@pbsserver;Job;1234.pbsserver;Job Queued at request of user1@login01, owner = user1@login01, job name = STDIN, queue = workq
+06/23/2020 10:12:15;0008;Server@pbsserver;Job;1234.pbsserver;Job Run at request of Scheduler@pbsserver on exec_vnode (node01:ncpus=32)
+06/23/2020 10:12:15;0006;Server@pbsserver;Hook;Server@pbsserver;hook 'check' encountered an exception, request rejected
+Traceback (most recent call last):
+  File \"<embedded code object>\", line 3, in <module>
+06/23/2020 11:12:15;0010;Server@pbsserver;Job;1234.pbsserver;Exit_status=0 resources_used.cput=00:00:01
+";
+
+    const SCHED: &str = "\
+06/23/2020 10:12:15;0040;pbs_sched;Job;1234.pbsserver;Job run
+06/23/2020 10:12:15;0400;pbs_sched;Node;node01;Evaluating subchunk: ncpus=32
+";
+
+    const MOM: &str = "\
+06/23/2020 10:12:15;0008;pbs_mom;Job;1234.pbsserver;Started, pid = 12345
+06/23/2020 11:12:15;0080;pbs_mom;Job;1234.pbsserver;task 00000001 terminated
+";
+
+    fn entries(text: &str) -> Vec<LogEntry> {
+        LogReader::new(text.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn event_classes() {
+        assert_eq!("0008".parse(), Ok(EventClass::JOB));
+        assert_eq!("0x0008".parse(), Ok(EventClass::JOB));
+        assert_eq!("0006".parse(), Ok(EventClass::SYSTEM | EventClass::ADMIN));
+        assert_eq!("job,resv".parse(), Ok(EventClass::JOB | EventClass::RESV));
+        assert!("0x".parse::<EventClass>().is_err());
+        assert!("jobs".parse::<EventClass>().is_err());
+        assert_eq!(EventClass::JOB.to_string(), "0008");
+        assert_eq!(EventClass::ALL.to_string(), "8fff");
+        assert_eq!(
+            (EventClass::ERROR | EventClass::SCHED).names(),
+            ["error", "sched"]
+        );
+    }
+
+    #[test]
+    fn server_log() {
+        let e = entries(SERVER);
+        assert_eq!(e.len(), 5);
+        assert_eq!(e[0].object_type, ObjectType::Request);
+        assert_eq!(e[0].object_name, "");
+        assert_eq!(e[1].event, EventClass::JOB);
+        assert_eq!(e[1].daemon, "Server@pbsserver");
+        assert_eq!(e[1].object_type, ObjectType::Job);
+        assert_eq!(e[1].object_name, "1234.pbsserver");
+        // the message keeps its own semicolons and commas
+        assert!(e[1].message.ends_with("job name = STDIN, queue = workq"));
+        assert_eq!(
+            e[3].message,
+            "hook 'check' encountered an exception, request rejected\n\
+             Traceback (most recent call last):\n  \
+             File \"<embedded code object>\", line 3, in <module>"
+        );
+        assert_eq!(e[4].event, EventClass::JOB_USAGE);
+        assert_eq!(
+            e[4].time,
+            NaiveDate::from_ymd_opt(2020, 6, 23)
+                .unwrap()
+                .and_hms_opt(11, 12, 15)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn sched_and_mom_logs() {
+        let e = entries(SCHED);
+        assert_eq!(e[0].event, EventClass::SCHED);
+        assert_eq!(e[0].daemon, "pbs_sched");
+        assert_eq!(e[1].event, EventClass::DEBUG3);
+        assert_eq!(e[1].object_type, ObjectType::Node);
+        let e = entries(MOM);
+        assert_eq!(e[0].daemon, "pbs_mom");
+        assert_eq!(e[0].message, "Started, pid = 12345");
+        assert_eq!(e[1].event, EventClass::DEBUG);
+    }
+
+    #[test]
+    fn leading_garbage() {
+        let mut r = LogReader::new("not a log line\n".as_bytes());
+        assert!(r.next().unwrap().unwrap_err().starts_with("line 1: "));
+        assert!(r.next().is_none());
+    }
+
+    #[test]
+    fn filters() {
+        let e = entries(SERVER);
+        let job = LogFilter::new().job("1234");
+        assert_eq!(e.iter().filter(|x| job.matches(x)).count(), 3);
+        assert!(!LogFilter::new().job("1234.other").matches(&e[1]));
+        let usage = LogFilter::new().events(EventClass::JOB_USAGE);
+        assert_eq!(e.iter().filter(|x| usage.matches(x)).count(), 1);
+        let hooks = LogFilter::new().object_type(ObjectType::Hook);
+        assert_eq!(e.iter().filter(|x| hooks.matches(x)).count(), 1);
+        let late = LogFilter::new().from(e[4].time);
+        assert_eq!(e.iter().filter(|x| late.matches(x)).count(), 1);
+    }
+
+    #[test]
+    fn timeline() {
+        let home = std::env::temp_dir().join(format!("pbs-daemonlog-{}", std::process::id()));
+        for (dir, text) in [
+            ("server_logs", SERVER),
+            ("sched_logs", SCHED),
+            ("mom_logs", MOM),
+        ] {
+            std::fs::create_dir_all(home.join(dir)).unwrap();
+            std::fs::write(home.join(dir).join("20200623"), text).unwrap();
+        }
+        let t = job_timeline(&home, "1234.pbsserver", None, None).unwrap();
+        let got: Vec<(LogSource, &str)> = t.iter().map(|(s, e)| (*s, e.message.as_str())).collect();
+        assert_eq!(got.len(), 6);
+        assert_eq!(got[0].0, LogSource::Server);
+        // same second, server before sched before mom
+        assert_eq!(got[2], (LogSource::Scheduler, "Job run"));
+        assert_eq!(got[3], (LogSource::Mom, "Started, pid = 12345"));
+        assert_eq!(got[5].0, LogSource::Mom);
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use crate::attributes::{job, node};
+use crate::types::{AttrChange, Attribs, Attrl, Server, VnodeState};
+use log::{debug, info, trace, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What to do with jobs still running on draining vnodes once the deadline passes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainAction {
+    /// leave them running, and report them as remaining
+    Leave,
+    Requeue,
+    Delete,
+}
+
+/// State of an in progress drain, passed to the progress callback each poll
+#[derive(Debug, Clone)]
+pub struct DrainProgress {
+    /// running jobs on each vnode which isn't drained yet
+    pub busy: BTreeMap<String, BTreeSet<String>>,
+    /// vnodes with no running jobs left
+    pub drained: BTreeSet<String>,
+    pub elapsed: Duration,
+}
+
+/// Outcome of a drain
+#[derive(Debug, Clone, Default)]
+pub struct DrainReport {
+    /// vnodes with no running jobs left
+    pub drained: Vec<String>,
+    pub requeued: Vec<String>,
+    pub deleted: Vec<String>,
+    /// running jobs on each vnode which isn't drained
+    pub remaining: BTreeMap<String, BTreeSet<String>>,
+    /// failures requeueing or deleting stragglers
+    pub errors: Vec<String>,
+}
+
+impl Server {
+    /// Offline vnodes and wait for the jobs running on them to finish
+    ///
+    /// `progress` is called after every poll, returning false aborts the drain,
+    /// clears the offline state of every vnode this drain offlined and puts back
+    /// their comments. Array subjobs are included. Once `deadline`
+    /// passes, jobs still running are handled according to `action`.
+    pub fn drain_vnodes<S, F>(
+        &self,
+        names: &[S],
+        comment: Option<&str>,
+        deadline: Duration,
+        action: DrainAction,
+        mut progress: F,
+    ) -> Result<DrainReport, String>
+    where
+        S: AsRef<str>,
+        F: FnMut(&DrainProgress) -> bool,
+    {
+        let start = Instant::now();
+        let names: BTreeSet<String> = names.iter().map(|n| n.as_ref().to_string()).collect();
+        info!("Draining vnodes {names:?}");
+        // vnodes which weren't offline already and their old comment, to restore on abort
+        let mut offlined = Vec::new();
+        for name in &names {
+            let restore_comment = match comment.map(|_| self.vnode_comment(name)) {
+                None => None,
+                Some(Ok(Some(c))) => Some(AttrChange::Set(node::COMMENT.to_string(), c)),
+                Some(Ok(None)) => Some(AttrChange::Unset(node::COMMENT.to_string())),
+                Some(Err(e)) => {
+                    self.restore_vnodes(&offlined);
+                    return Err(e);
+                }
+            };
+            match self.set_vnode_state(name, VnodeState::OFFLINE, VnodeState::FREE, comment, false)
+            {
+                Ok(change) => {
+                    if !change.before.contains(VnodeState::OFFLINE) {
+                        offlined.push((name.clone(), restore_comment));
+                    }
+                }
+                Err(e) => {
+                    self.restore_vnodes(&offlined);
+                    return Err(e);
+                }
+            }
+        }
+        let mut delay = Duration::from_secs(5);
+        loop {
+            let busy = match self.running_jobs_on(&names) {
+                Ok(b) => b,
+                Err(e) => {
+                    self.restore_vnodes(&offlined);
+                    return Err(e);
+                }
+            };
+            let elapsed = start.elapsed();
+            let status = DrainProgress {
+                drained: names
+                    .iter()
+                    .filter(|n| !busy.contains_key(*n))
+                    .cloned()
+                    .collect(),
+                busy,
+                elapsed,
+            };
+            trace!("Drain progress {status:?}");
+            if !progress(&status) {
+                warn!("Drain aborted, restoring vnodes {offlined:?}");
+                self.restore_vnodes(&offlined);
+                return Err("drain aborted".to_string());
+            }
+            let mut report = DrainReport {
+                drained: status.drained.into_iter().collect(),
+                ..Default::default()
+            };
+            if status.busy.is_empty() {
+                debug!("Drain complete after {elapsed:?}");
+                return Ok(report);
+            }
+            if elapsed >= deadline {
+                info!("Drain deadline passed, {action:?} remaining jobs");
+                let jobs: BTreeSet<&String> = status.busy.values().flatten().collect();
+                for job in jobs {
+                    let (resp, done) = match action {
+                        DrainAction::Leave => continue,
+                        DrainAction::Requeue => (self.rerun_job(job), &mut report.requeued),
+                        DrainAction::Delete => (self.del_job(job), &mut report.deleted),
+                    };
+                    match resp {
+                        Ok(()) => done.push(job.clone()),
+                        Err(e) => report.errors.push(format!("{job}: {e}")),
+                    }
+                }
+                report.remaining = status.busy;
+                for jobs in report.remaining.values_mut() {
+                    jobs.retain(|j| !report.requeued.contains(j) && !report.deleted.contains(j));
+                }
+                report.remaining.retain(|_, jobs| !jobs.is_empty());
+                return Ok(report);
+            }
+            thread::sleep(delay.min(deadline - elapsed));
+            delay = (delay * 2).min(Duration::from_secs(60));
+        }
+    }
+
+    // running jobs and subjobs on each of the given vnodes, vnodes without jobs are left out
+    fn running_jobs_on(
+        &self,
+        names: &BTreeSet<String>,
+    ) -> Result<BTreeMap<String, BTreeSet<String>>, String> {
+        let criteria = Attribs::from(&vec![format!("{}=R", job::JOB_STATE)]);
+        let jobs = self.stat_jobs_with_subjobs(criteria, None)?;
+        let mut busy: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for job in jobs.resources {
+            match job.exec_vnode() {
+                Some(Ok(exec)) => {
+                    for vnode in exec.vnodes() {
+                        if names.contains(vnode) {
+                            busy.entry(vnode.to_string())
+                                .or_default()
+                                .insert(job.name());
+                        }
+                    }
+                }
+                Some(Err(e)) => warn!("Can't parse exec_vnode of job {}: {e}", job.name()),
+                None => {}
+            }
+        }
+        Ok(busy)
+    }
+
+    fn vnode_comment(&self, name: &str) -> Result<Option<String>, String> {
+        let resp = self.stat_vnode(&Some(name.to_string()), None)?;
+        let status = resp.resources.first();
+        Ok(match status.and_then(|s| s.attribs().get(node::COMMENT)) {
+            Some(Attrl::Value(v)) => Some(v.val()),
+            _ => None,
+        })
+    }
+
+    // only offline is cleared, a vnode that went down meanwhile stays down
+    fn restore_vnodes(&self, vnodes: &[(String, Option<AttrChange>)]) {
+        for (name, comment) in vnodes {
+            let mut resp = self
+                .set_vnode_state(name, VnodeState::FREE, VnodeState::OFFLINE, None, false)
+                .map(|_| ());
+            if let (Ok(()), Some(c)) = (&resp, comment) {
+                resp = self.apply_changes(
+                    pbs_sys::mgr_obj_MGR_OBJ_HOST,
+                    name,
+                    std::slice::from_ref(c),
+                );
+            }
+            if let Err(e) = resp {
+                warn!("Failed to restore vnode {name}: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+//! Job usage and efficiency computed from accounting records
+use crate::accounting::{AccountingRecord, RecordType};
+use crate::helpers::{csv_field, parse_duration, parse_size};
+use crate::types::ExecHost;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use log::trace;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Usage of one finished job, joined from its S and E records
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobUsage {
+    pub id: String,
+    pub user: String,
+    pub group: Option<String>,
+    pub project: Option<String>,
+    pub account: Option<String>,
+    pub queue: String,
+    /// epoch seconds
+    pub qtime: Option<i64>,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub ncpus: u64,
+    pub nodes: u64,
+    /// seconds
+    pub walltime_requested: Option<u64>,
+    pub walltime_used: Option<u64>,
+    pub cput: Option<u64>,
+    /// bytes
+    pub mem_requested: Option<u64>,
+    pub mem_used: Option<u64>,
+    pub exit_status: Option<i32>,
+}
+
+// look in the end record first, falling back to the start record
+fn lookup<'a>(
+    end: &'a AccountingRecord,
+    start: Option<&'a AccountingRecord>,
+    name: &str,
+    resource: Option<&str>,
+) -> Option<String> {
+    let get = |r: &AccountingRecord| match resource {
+        Some(res) => r.resource(name, res),
+        None => r.get(name),
+    };
+    get(end).or_else(|| start.and_then(get))
+}
+
+impl JobUsage {
+    /// Build from a job's E record and, if available, its last S record
+    pub fn new(end: &AccountingRecord, start: Option<&AccountingRecord>) -> JobUsage {
+        let get = |name: &str| lookup(end, start, name, None);
+        let time = |name: &str| get(name).and_then(|v| v.parse().ok());
+        let list = |res: &str| lookup(end, start, "Resource_List", Some(res));
+        let used = |res: &str| lookup(end, start, "resources_used", Some(res));
+        let ncpus = used("ncpus")
+            .or_else(|| list("ncpus"))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let nodes = list("nodect")
+            .and_then(|v| v.parse().ok())
+            .or_else(|| {
+                get("exec_host")
+                    .and_then(|h| h.parse::<ExecHost>().ok())
+                    .map(|h| h.hosts().len() as u64)
+            })
+            .unwrap_or(0);
+        JobUsage {
+            id: end.id.clone(),
+            user: get("user").unwrap_or_default(),
+            group: get("group"),
+            project: get("project"),
+            account: get("account"),
+            queue: get("queue").unwrap_or_default(),
+            qtime: time("qtime"),
+            start: time("start"),
+            end: time("end").or_else(|| {
+                end.time
+                    .and_local_timezone(Local)
+                    .single()
+                    .map(|t| t.timestamp())
+            }),
+            ncpus,
+            nodes,
+            walltime_requested: list("walltime").and_then(|v| parse_duration(&v)),
+            walltime_used: used("walltime").and_then(|v| parse_duration(&v)),
+            cput: used("cput").and_then(|v| parse_duration(&v)),
+            mem_requested: list("mem").and_then(|v| parse_size(&v)),
+            mem_used: used("mem").and_then(|v| parse_size(&v)),
+            exit_status: get("Exit_status").and_then(|v| v.parse().ok()),
+        }
+    }
+
+    /// cput / (ncpus * walltime)
+    pub fn cpu_efficiency(&self) -> Option<f64> {
+        let avail = self.ncpus * self.walltime_used?;
+        let cput = self.cput?;
+        (avail > 0).then(|| cput as f64 / avail as f64)
+    }
+    /// resources_used.mem / Resource_List.mem
+    pub fn mem_efficiency(&self) -> Option<f64> {
+        let (used, req) = (self.mem_used?, self.mem_requested?);
+        (req > 0).then(|| used as f64 / req as f64)
+    }
+    /// walltime used / walltime requested
+    pub fn walltime_accuracy(&self) -> Option<f64> {
+        let (used, req) = (self.walltime_used?, self.walltime_requested?);
+        (req > 0).then(|| used as f64 / req as f64)
+    }
+    /// seconds between being queued and starting
+    pub fn queue_wait(&self) -> Option<i64> {
+        Some(self.start? - self.qtime?)
+    }
+    pub fn node_hours(&self) -> f64 {
+        (self.nodes * self.walltime_used.unwrap_or(0)) as f64 / 3600.0
+    }
+    pub fn cpu_hours(&self) -> f64 {
+        (self.ncpus * self.walltime_used.unwrap_or(0)) as f64 / 3600.0
+    }
+    /// whether the job used less than the given fraction of its cpus or memory
+    pub fn is_wasteful(&self, min_cpu: f64, min_mem: f64) -> bool {
+        self.cpu_efficiency().is_some_and(|e| e < min_cpu)
+            || self.mem_efficiency().is_some_and(|e| e < min_mem)
+    }
+
+    pub fn json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "user": self.user,
+            "group": self.group,
+            "project": self.project,
+            "account": self.account,
+            "queue": self.queue,
+            "qtime": self.qtime,
+            "start": self.start,
+            "end": self.end,
+            "ncpus": self.ncpus,
+            "nodes": self.nodes,
+            "walltime_requested": self.walltime_requested,
+            "walltime_used": self.walltime_used,
+            "cput": self.cput,
+            "mem_requested": self.mem_requested,
+            "mem_used": self.mem_used,
+            "exit_status": self.exit_status,
+            "cpu_efficiency": self.cpu_efficiency(),
+            "mem_efficiency": self.mem_efficiency(),
+            "walltime_accuracy": self.walltime_accuracy(),
+            "queue_wait": self.queue_wait(),
+            "node_hours": self.node_hours(),
+        })
+    }
+}
+
+/// Join S and E records into per job usage, yielding a job as soon as its E record is seen
+///
+/// Jobs whose S record was before the first record still get a JobUsage from the
+/// E record alone.
+pub fn job_usage<I>(records: I) -> impl Iterator<Item = JobUsage>
+where
+    I: IntoIterator<Item = AccountingRecord>,
+{
+    let mut started: HashMap<String, AccountingRecord> = HashMap::new();
+    records
+        .into_iter()
+        .filter_map(move |rec| match rec.record_type {
+            // reruns get a new S record, only the last one counts
+            RecordType::Started => {
+                started.insert(rec.id.clone(), rec);
+                None
+            }
+            RecordType::Ended => {
+                let start = started.remove(&rec.id);
+                trace!("Job {} ended, start record: {}", rec.id, start.is_some());
+                Some(JobUsage::new(&rec, start.as_ref()))
+            }
+            RecordType::Deleted | RecordType::Aborted => {
+                started.remove(&rec.id);
+                None
+            }
+            _ => None,
+        })
+}
+
+/// What to aggregate jobs by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    User,
+    Group,
+    Project,
+    Account,
+    Queue,
+}
+
+impl GroupBy {
+    fn name(&self) -> &'static str {
+        match self {
+            GroupBy::User => "user",
+            GroupBy::Group => "group",
+            GroupBy::Project => "project",
+            GroupBy::Account => "account",
+            GroupBy::Queue => "queue",
+        }
+    }
+    fn value(&self, job: &JobUsage) -> String {
+        match self {
+            GroupBy::User => job.user.clone(),
+            GroupBy::Group => job.group.clone().unwrap_or_default(),
+            GroupBy::Project => job.project.clone().unwrap_or_default(),
+            GroupBy::Account => job.account.clone().unwrap_or_default(),
+            GroupBy::Queue => job.queue.clone(),
+        }
+    }
+}
+
+/// Time windows jobs are bucketed into by end time
+///
+/// Windows start at midnight in this machine's local timezone, the same zone the
+/// server writes accounting timestamps in when run on the server host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Day,
+    /// weeks start on Monday
+    Week,
+    Month,
+}
+
+impl Window {
+    fn start(&self, time: i64) -> Option<NaiveDate> {
+        let date = DateTime::from_timestamp(time, 0)?
+            .with_timezone(&Local)
+            .date_naive();
+        match self {
+            Window::Day => Some(date),
+            Window::Week => {
+                Some(date - Duration::days(date.weekday().num_days_from_monday() as i64))
+            }
+            Window::Month => date.with_day(1),
+        }
+    }
+}
+
+/// Totals for one group of jobs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// group values in the order of the GroupBy list
+    pub key: Vec<(&'static str, String)>,
+    /// first day of the window, if aggregating by window
+    pub window: Option<NaiveDate>,
+    pub jobs: u64,
+    pub node_hours: f64,
+    pub cpu_hours: f64,
+    pub cput_hours: f64,
+    pub wait_total: i64,
+    pub wait_max: i64,
+    waited: u64,
+    mem_used: u64,
+    mem_requested: u64,
+    wall_used: u64,
+    wall_requested: u64,
+    cpu_avail: u64,
+    cpu_used: u64,
+}
+
+impl Summary {
+    fn add(&mut self, job: &JobUsage) {
+        self.jobs += 1;
+        self.node_hours += job.node_hours();
+        self.cpu_hours += job.cpu_hours();
+        self.cput_hours += job.cput.unwrap_or(0) as f64 / 3600.0;
+        if let Some(w) = job.queue_wait() {
+            self.waited += 1;
+            self.wait_total += w;
+            self.wait_max = self.wait_max.max(w);
+        }
+        if let (Some(u), Some(r)) = (job.mem_used, job.mem_requested) {
+            self.mem_used += u;
+            self.mem_requested += r;
+        }
+        if let (Some(u), Some(r)) = (job.walltime_used, job.walltime_requested) {
+            self.wall_used += u;
+            self.wall_requested += r;
+        }
+        if let (Some(c), Some(w)) = (job.cput, job.walltime_used) {
+            self.cpu_used += c;
+            self.cpu_avail += job.ncpus * w;
+        }
+    }
+    /// total cput over total cpu time allocated
+    pub fn cpu_efficiency(&self) -> Option<f64> {
+        (self.cpu_avail > 0).then(|| self.cpu_used as f64 / self.cpu_avail as f64)
+    }
+    /// total memory used over total memory requested
+    pub fn mem_efficiency(&self) -> Option<f64> {
+        (self.mem_requested > 0).then(|| self.mem_used as f64 / self.mem_requested as f64)
+    }
+    /// total walltime used over total walltime requested
+    pub fn walltime_accuracy(&self) -> Option<f64> {
+        (self.wall_requested > 0).then(|| self.wall_used as f64 / self.wall_requested as f64)
+    }
+    /// mean queue wait in seconds
+    pub fn wait_mean(&self) -> Option<f64> {
+        (self.waited > 0).then(|| self.wait_total as f64 / self.waited as f64)
+    }
+
+    pub fn json(&self) -> Value {
+        let mut v = json!({
+            "window": self.window.map(|d| d.to_string()),
+            "jobs": self.jobs,
+            "node_hours": self.node_hours,
+            "cpu_hours": self.cpu_hours,
+            "cput_hours": self.cput_hours,
+            "cpu_efficiency": self.cpu_efficiency(),
+            "mem_efficiency": self.mem_efficiency(),
+            "walltime_accuracy": self.walltime_accuracy(),
+            "wait_mean": self.wait_mean(),
+            "wait_max": self.wait_max,
+        });
+        for (k, val) in &self.key {
+            v[*k] = Value::String(val.clone());
+        }
+        v
+    }
+}
+
+/// Aggregate jobs by the given fields and optionally by time window
+///
+/// Windows are in local time, see Window.
+pub fn aggregate_usage<'a, I>(jobs: I, by: &[GroupBy], window: Option<Window>) -> Vec<Summary>
+where
+    I: IntoIterator<Item = &'a JobUsage>,
+{
+    let mut groups: BTreeMap<(Option<NaiveDate>, Vec<String>), Summary> = BTreeMap::new();
+    for job in jobs {
+        let key: Vec<String> = by.iter().map(|g| g.value(job)).collect();
+        let win = window.and_then(|w| job.end.and_then(|e| w.start(e)));
+        let s = groups.entry((win, key.clone())).or_insert_with(|| Summary {
+            key: by.iter().map(|g| g.name()).zip(key).collect(),
+            window: win,
+            ..Default::default()
+        });
+        s.add(job);
+    }
+    groups.into_values().collect()
+}
+
+fn opt<T: ToString>(val: Option<T>) -> String {
+    val.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Per job usage as CSV with a header line
+pub fn jobs_csv<'a, I>(jobs: I) -> String
+where
+    I: IntoIterator<Item = &'a JobUsage>,
+{
+    let mut out = String::from("id,user,group,project,account,queue,qtime,start,end,ncpus,nodes,walltime_requested,walltime_used,cput,mem_requested,mem_used,exit_status,cpu_efficiency,mem_efficiency,walltime_accuracy,queue_wait,node_hours\n");
+    for j in jobs {
+        let row = [
+            j.id.clone(),
+            j.user.clone(),
+            opt(j.group.as_ref()),
+            opt(j.project.as_ref()),
+            opt(j.account.as_ref()),
+            j.queue.clone(),
+            opt(j.qtime),
+            opt(j.start),
+            opt(j.end),
+            j.ncpus.to_string(),
+            j.nodes.to_string(),
+            opt(j.walltime_requested),
+            opt(j.walltime_used),
+            opt(j.cput),
+            opt(j.mem_requested),
+            opt(j.mem_used),
+            opt(j.exit_status),
+            opt(j.cpu_efficiency()),
+            opt(j.mem_efficiency()),
+            opt(j.walltime_accuracy()),
+            opt(j.queue_wait()),
+            j.node_hours().to_string(),
+        ];
+        let row: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Summaries as CSV with a header line, group columns first
+pub fn summary_csv(summaries: &[Summary]) -> String {
+    let mut out = String::new();
+    let group: Vec<&str> = summaries
+        .first()
+        .map(|s| s.key.iter().map(|(k, _)| *k).collect())
+        .unwrap_or_default();
+    let mut header = group.clone();
+    header.extend([
+        "window",
+        "jobs",
+        "node_hours",
+        "cpu_hours",
+        "cput_hours",
+        "cpu_efficiency",
+        "mem_efficiency",
+        "walltime_accuracy",
+        "wait_mean",
+        "wait_max",
+    ]);
+    out.push_str(&header.join(","));
+    out.push('\n');
+    for s in summaries {
+        let mut row: Vec<String> = s.key.iter().map(|(_, v)| csv_field(v)).collect();
+        row.extend([
+            opt(s.window),
+            s.jobs.to_string(),
+            s.node_hours.to_string(),
+            s.cpu_hours.to_string(),
+            s.cput_hours.to_string(),
+            opt(s.cpu_efficiency()),
+            opt(s.mem_efficiency()),
+            opt(s.walltime_accuracy()),
+            opt(s.wait_mean()),
+            s.wait_max.to_string(),
+        ]);
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounting::parse_accounting_record;
+
+    fn records(lines: &[&str]) -> Vec<AccountingRecord> {
+        lines
+            .iter()
+            .map(|l| parse_accounting_record(l).unwrap())
+            .collect()
+    }
+
+    const S: &str = "06/22/2020 10:12:15;S;1234.pbsserver;user=user1 group=users project=climate jobname=run queue=workq ctime=1592842334 qtime=1592842334 etime=1592842334 start=1592842335 exec_host=node01/0*32+node02/0*32 exec_vnode=(node01:ncpus=32)+(node02:ncpus=32) Resource_List.mem=100gb Resource_List.ncpus=64 Resource_List.select=2:ncpus=32:mem=50gb Resource_List.walltime=02:00:00";
+    const E: &str = "06/22/2020 11:12:15;E;1234.pbsserver;user=user1 group=users project=climate jobname=run queue=workq start=1592842335 exec_host=node01/0*32+node02/0*32 Resource_List.mem=100gb Resource_List.ncpus=64 Resource_List.walltime=02:00:00 session=4242 end=1592845935 Exit_status=0 resources_used.cput=32:00:00 resources_used.mem=25gb resources_used.ncpus=64 resources_used.walltime=01:00:00 run_count=1";
+
+    #[test]
+    fn start_and_end_are_joined() {
+        let jobs: Vec<JobUsage> = job_usage(records(&[S, E])).collect();
+        assert_eq!(jobs.len(), 1);
+        let j = &jobs[0];
+        assert_eq!(j.id, "1234.pbsserver");
+        assert_eq!(j.user, "user1");
+        assert_eq!(j.project.as_deref(), Some("climate"));
+        assert_eq!(j.account, None);
+        // qtime is only in the S record
+        assert_eq!(j.qtime, Some(1592842334));
+        assert_eq!(j.queue_wait(), Some(1));
+        assert_eq!(j.end, Some(1592845935));
+        assert_eq!(j.ncpus, 64);
+        // no nodect, counted from exec_host
+        assert_eq!(j.nodes, 2);
+        assert_eq!(j.walltime_requested, Some(7200));
+        assert_eq!(j.walltime_used, Some(3600));
+        assert_eq!(j.cput, Some(32 * 3600));
+        assert_eq!(j.mem_requested, Some(100 << 30));
+        assert_eq!(j.mem_used, Some(25 << 30));
+        assert_eq!(j.exit_status, Some(0));
+        assert_eq!(j.cpu_efficiency(), Some(0.5));
+        assert_eq!(j.mem_efficiency(), Some(0.25));
+        assert_eq!(j.walltime_accuracy(), Some(0.5));
+        assert_eq!(j.node_hours(), 2.0);
+        assert_eq!(j.cpu_hours(), 64.0);
+        assert!(j.is_wasteful(0.6, 0.1));
+        assert!(!j.is_wasteful(0.5, 0.25));
+    }
+
+    #[test]
+    fn end_record_alone() {
+        let jobs: Vec<JobUsage> = job_usage(records(&[E])).collect();
+        assert_eq!(jobs[0].qtime, None);
+        assert_eq!(jobs[0].queue_wait(), None);
+        assert_eq!(jobs[0].start, Some(1592842335));
+    }
+
+    #[test]
+    fn reruns_use_the_last_start() {
+        let rerun = "06/22/2020 10:30:00;R;1234.pbsserver;";
+        let restart = S.replace("start=1592842335", "start=1592843400");
+        let e = E.replace("start=1592842335 ", "");
+        let deleted = [
+            "06/22/2020 10:00:00;S;99.pbsserver;user=user2 queue=workq start=1592841600",
+            "06/22/2020 10:01:00;D;99.pbsserver;requestor=user2@login01",
+            "06/22/2020 10:02:00;E;99.pbsserver;user=user2 queue=workq end=1592841720",
+        ];
+        let mut lines = vec![S, rerun, restart.as_str(), e.as_str()];
+        lines.extend(deleted);
+        let jobs: Vec<JobUsage> = job_usage(records(&lines)).collect();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].start, Some(1592843400));
+        // the S record was dropped by the delete
+        assert_eq!(jobs[1].id, "99.pbsserver");
+        assert_eq!(jobs[1].start, None);
+    }
+
+    fn job(user: &str, end: i64, walltime: u64) -> JobUsage {
+        let e = format!(
+            "06/22/2020 11:12:15;E;{end}.pbsserver;user={user} queue=workq qtime={} start={} end={end} resources_used.ncpus=2 resources_used.cput={walltime} resources_used.walltime={walltime} Resource_List.nodect=1",
+            end - walltime as i64 - 60,
+            end - walltime as i64,
+        );
+        JobUsage::new(&parse_accounting_record(&e).unwrap(), None)
+    }
+
+    #[test]
+    fn windows() {
+        // noon UTC, Tuesday and Thursday of one week in June 2020
+        let (tue, thu) = (1592913600, 1593086400);
+        let jobs = [
+            job("a", tue, 3600),
+            job("a", thu, 7200),
+            job("b", thu, 3600),
+        ];
+        let local = |t| {
+            DateTime::from_timestamp(t, 0)
+                .unwrap()
+                .with_timezone(&Local)
+                .date_naive()
+        };
+
+        let days = aggregate_usage(&jobs, &[GroupBy::User], Some(Window::Day));
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].window, Some(local(tue)));
+        assert_eq!(days[0].key, [("user", "a".to_string())]);
+
+        let weeks = aggregate_usage(&jobs, &[GroupBy::User], Some(Window::Week));
+        assert_eq!(weeks.len(), 2);
+        let a = &weeks[0];
+        assert_eq!(a.window, NaiveDate::from_ymd_opt(2020, 6, 22));
+        assert_eq!((a.jobs, a.node_hours, a.cpu_hours), (2, 3.0, 6.0));
+        assert_eq!(
+            (a.wait_total, a.wait_max, a.wait_mean()),
+            (120, 60, Some(60.0))
+        );
+        assert_eq!(a.cpu_efficiency(), Some(0.5));
+
+        let months = aggregate_usage(&jobs, &[], Some(Window::Month));
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].window, NaiveDate::from_ymd_opt(2020, 6, 1));
+        assert_eq!(months[0].jobs, 3);
+
+        let all = aggregate_usage(&jobs, &[GroupBy::Queue, GroupBy::User], None);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].window, None);
+        assert_eq!(
+            all[1].key,
+            [("queue", "workq".to_string()), ("user", "b".to_string())]
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let mut j = job("a", 1592913600, 3600);
+        j.account = Some("x,y".to_string());
+        let out = jobs_csv([&j]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("id,user,group,project,account,queue,"));
+        assert_eq!(
+            lines[1],
+            "1592913600.pbsserver,a,,,\"x,y\",workq,1592909940,1592910000,1592913600,2,1,,3600,3600,,,,0.5,,,60,1"
+        );
+
+        let s = aggregate_usage([&j], &[GroupBy::Account], None);
+        assert_eq!(
+            summary_csv(&s),
+            "account,window,jobs,node_hours,cpu_hours,cput_hours,cpu_efficiency,mem_efficiency,walltime_accuracy,wait_mean,wait_max\n\
+             \"x,y\",,1,1,2,1,0.5,,,60,60\n"
+        );
+        assert_eq!(summary_csv(&[]), "window,jobs,node_hours,cpu_hours,cput_hours,cpu_efficiency,mem_efficiency,walltime_accuracy,wait_mean,wait_max\n");
+    }
+}
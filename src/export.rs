@@ -0,0 +1,275 @@
+//! Flat CSV and NDJSON export of stat results with a consistent, inferred schema
+use crate::helpers::{csv_field, parse_duration, parse_size};
+use crate::types::StatResp;
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Type of an exported column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FieldType {
+    Integer,
+    Float,
+    Boolean,
+    /// exported as bytes
+    Size,
+    /// exported as seconds
+    Duration,
+    String,
+}
+
+impl FieldType {
+    // every type a single value could be read as
+    fn candidates(val: &str) -> Vec<FieldType> {
+        let mut out = Vec::new();
+        if val.parse::<i64>().is_ok() {
+            out.push(FieldType::Integer);
+        }
+        if val.parse::<f64>().is_ok() {
+            out.push(FieldType::Float);
+        }
+        if val.eq_ignore_ascii_case("true") || val.eq_ignore_ascii_case("false") {
+            out.push(FieldType::Boolean);
+        }
+        if parse_size(val).is_some() {
+            out.push(FieldType::Size);
+        }
+        if parse_duration(val).is_some() {
+            out.push(FieldType::Duration);
+        }
+        out.push(FieldType::String);
+        out
+    }
+
+    /// value converted for export, None if it doesn't fit this type
+    fn convert(&self, val: &str) -> Option<Value> {
+        match self {
+            FieldType::Integer => val.parse::<i64>().ok().map(Value::from),
+            FieldType::Float => val
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            FieldType::Boolean => match val.to_lowercase().as_str() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            FieldType::Size => parse_size(val).map(Value::from),
+            FieldType::Duration => parse_duration(val).map(Value::from),
+            FieldType::String => Some(Value::String(val.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldType::Integer => "integer",
+            FieldType::Float => "float",
+            FieldType::Boolean => "boolean",
+            FieldType::Size => "size",
+            FieldType::Duration => "duration",
+            FieldType::String => "string",
+        }
+    }
+}
+
+/// An exported column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// attribute name, or name.resource
+    pub name: String,
+    pub field_type: FieldType,
+    /// objects the attribute was found on
+    pub count: usize,
+    /// whether some objects didn't have the attribute
+    pub nullable: bool,
+}
+
+/// Columns and their types, inferred from one or more stats
+///
+/// Merging the schemas of successive stats keeps exports loadable into the same table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    fields: BTreeMap<String, Field>,
+    objects: usize,
+}
+
+impl Schema {
+    /// infer the type of every attribute found in `resp`
+    pub fn infer(resp: &StatResp) -> Schema {
+        let mut candidates: BTreeMap<String, (Vec<FieldType>, usize)> = BTreeMap::new();
+        for status in &resp.resources {
+            for (key, val) in status.attribs().flat() {
+                let c = FieldType::candidates(&val);
+                let entry = candidates.entry(key).or_insert_with(|| (c.clone(), 0));
+                entry.0.retain(|t| c.contains(t));
+                entry.1 += 1;
+            }
+        }
+        let objects = resp.resources.len();
+        let fields = candidates
+            .into_iter()
+            .map(|(name, (types, count))| {
+                let field = Field {
+                    name: name.clone(),
+                    // String is always a candidate, so there's always one left
+                    field_type: types.first().copied().unwrap_or(FieldType::String),
+                    count,
+                    nullable: count < objects,
+                };
+                (name, field)
+            })
+            .collect();
+        Schema { fields, objects }
+    }
+
+    /// combine with the schema of another stat, widening types that disagree
+    pub fn merge(&mut self, other: &Schema) {
+        for (name, field) in &other.fields {
+            match self.fields.get_mut(name) {
+                Some(f) => {
+                    f.field_type = widen(f.field_type, field.field_type);
+                    f.count += field.count;
+                    f.nullable = f.nullable || field.nullable;
+                }
+                None => {
+                    let mut f = field.clone();
+                    f.nullable = f.nullable || self.objects > 0;
+                    self.fields.insert(name.clone(), f);
+                }
+            }
+        }
+        for (name, f) in self.fields.iter_mut() {
+            if !other.fields.contains_key(name) && other.objects > 0 {
+                f.nullable = true;
+            }
+        }
+        self.objects += other.objects;
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.values()
+    }
+    pub fn get(&self, name: &str) -> Option<&Field> {
+        self.fields.get(name)
+    }
+
+    fn value(&self, name: &str, val: &str) -> Value {
+        let t = self
+            .fields
+            .get(name)
+            .map(|f| f.field_type)
+            .unwrap_or(FieldType::String);
+        // values that don't fit the schema are kept as strings rather than dropped
+        t.convert(val)
+            .unwrap_or_else(|| Value::String(val.to_string()))
+    }
+
+    /// CSV with a name column followed by one column per schema field
+    ///
+    /// Sizes are written in bytes, durations in seconds and booleans as true/false.
+    /// Missing attributes are empty.
+    pub fn csv(&self, resp: &StatResp) -> String {
+        let mut out = String::from("name");
+        for name in self.fields.keys() {
+            out.push(',');
+            out.push_str(&csv_field(name));
+        }
+        out.push('\n');
+        for status in &resp.resources {
+            let flat = status.attribs().flat();
+            out.push_str(&csv_field(&status.name()));
+            for name in self.fields.keys() {
+                out.push(',');
+                if let Some(v) = flat.get(name) {
+                    let v = match self.value(name, v) {
+                        Value::String(s) => s,
+                        v => v.to_string(),
+                    };
+                    out.push_str(&csv_field(&v));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// One JSON object per line with the object name, the stat timestamp and typed attributes
+    ///
+    /// Attributes are keyed by name or name.resource, missing ones are null.
+    pub fn ndjson(&self, resp: &StatResp, timestamp: u64) -> String {
+        let mut out = String::new();
+        for status in &resp.resources {
+            let flat = status.attribs().flat();
+            let mut obj = Map::new();
+            obj.insert("name".to_string(), Value::String(status.name()));
+            obj.insert("timestamp".to_string(), Value::from(timestamp));
+            for name in self.fields.keys() {
+                let v = match flat.get(name) {
+                    Some(v) => self.value(name, v),
+                    None => Value::Null,
+                };
+                obj.insert(name.clone(), v);
+            }
+            out.push_str(&Value::Object(obj).to_string());
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn json(&self) -> Value {
+        Value::Array(
+            self.fields
+                .values()
+                .map(|f| {
+                    serde_json::json!({
+                        "name": f.name,
+                        "type": f.field_type.name(),
+                        "count": f.count,
+                        "nullable": f.nullable,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+// narrowest type both a and b fit in
+fn widen(a: FieldType, b: FieldType) -> FieldType {
+    use FieldType::*;
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Integer, Float) | (Float, Integer) => Float,
+        (Integer, Size) | (Size, Integer) => Size,
+        (Integer, Duration) | (Duration, Integer) => Duration,
+        _ => String,
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in self.fields.values() {
+            writeln!(
+                f,
+                "{}: {}{} ({}/{})",
+                field.name,
+                field.field_type.name(),
+                if field.nullable { " nullable" } else { "" },
+                field.count,
+                self.objects
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl StatResp {
+    /// CSV using the schema inferred from this stat, see Schema::csv
+    pub fn csv(&self) -> String {
+        Schema::infer(self).csv(self)
+    }
+    /// NDJSON using the schema inferred from this stat, see Schema::ndjson
+    pub fn ndjson(&self, timestamp: u64) -> String {
+        Schema::infer(self).ndjson(self, timestamp)
+    }
+}
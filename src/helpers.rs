@@ -55,3 +55,86 @@ pub(crate) fn json_val(val: String) -> Value {
     }
     Value::String(val)
 }
+
+// Helper function to convert a PBS size (e.g. 100gb, 4kw) into bytes
+// PBS size units are powers of 1024, words are 8 bytes, no suffix means bytes
+pub(crate) fn parse_size(val: &str) -> Option<u64> {
+    let val = val.trim().to_lowercase();
+    let digits = val.find(|c: char| !c.is_ascii_digit()).unwrap_or(val.len());
+    let (num, unit) = val.split_at(digits);
+    let num: u64 = num.parse().ok()?;
+    let (mult, unit) = match unit.chars().next() {
+        Some('k') => (1u64 << 10, &unit[1..]),
+        Some('m') => (1 << 20, &unit[1..]),
+        Some('g') => (1 << 30, &unit[1..]),
+        Some('t') => (1 << 40, &unit[1..]),
+        Some('p') => (1 << 50, &unit[1..]),
+        _ => (1, unit),
+    };
+    let word = match unit {
+        "" | "b" => 1,
+        "w" => 8,
+        _ => return None,
+    };
+    num.checked_mul(mult)?.checked_mul(word)
+}
+
+// Helper function to get a numeric amount out of a resource value
+// sizes are converted to bytes, anything non numeric is None
+pub(crate) fn parse_amount(val: &str) -> Option<u64> {
+    val.trim().parse().ok().or_else(|| parse_size(val))
+}
+
+// Helper function to convert a PBS duration ([[HH:]MM:]SS[.ms]) into seconds
+pub(crate) fn parse_duration(val: &str) -> Option<u64> {
+    let mut secs = 0u64;
+    let mut parts = 0;
+    for p in val.trim().split(':') {
+        parts += 1;
+        if parts > 3 {
+            return None;
+        }
+        // fractional seconds are dropped
+        let p = p.split('.').next()?;
+        secs = secs.checked_mul(60)?.checked_add(p.parse().ok()?)?;
+    }
+    Some(secs)
+}
+
+// Helper function to parse a PBS boolean, which accepts true/false, t/f, y/n and 1/0 in any case
+pub(crate) fn parse_bool(val: &str) -> Option<bool> {
+    match val.trim().to_lowercase().as_str() {
+        "true" | "t" | "y" | "1" => Some(true),
+        "false" | "f" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// Helper function to write bytes in the largest PBS unit that keeps the value >= 1, e.g. 1536mb -> 1.5gb
+pub(crate) fn format_size(bytes: u64) -> String {
+    let units = ["b", "kb", "mb", "gb", "tb", "pb"];
+    let mut i = 0;
+    while i + 1 < units.len() && bytes >= 1 << (10 * (i + 1)) {
+        i += 1;
+    }
+    let val = bytes as f64 / (1u64 << (10 * i)) as f64;
+    if val.fract() == 0.0 {
+        format!("{val}{}", units[i])
+    } else {
+        format!("{val:.1}{}", units[i])
+    }
+}
+
+// Helper function to write seconds as HH:MM:SS, hours aren't wrapped into days
+pub(crate) fn format_duration(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// Helper function to quote a CSV field if needed
+pub(crate) fn csv_field(val: &str) -> String {
+    if val.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", val.replace('"', "\"\""))
+    } else {
+        val.to_string()
+    }
+}
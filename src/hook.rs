@@ -0,0 +1,648 @@
+use crate::helpers::parse_bool;
+use crate::types::{Attrl, Op, Server, Status};
+use log::{debug, trace};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// hook attribute names, these aren't in pbs_ifl.h
+const EVENT: &str = "event";
+const ORDER: &str = "order";
+const ENABLED: &str = "enabled";
+const ALARM: &str = "alarm";
+const USER: &str = "user";
+const FAIL_ACTION: &str = "fail_action";
+const FREQ: &str = "freq";
+const DEBUG: &str = "debug";
+const TYPE: &str = "type";
+
+/// Event a hook runs on
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HookEvent {
+    QueueJob,
+    ModifyJob,
+    MoveJob,
+    RunJob,
+    JobObit,
+    ResvSub,
+    ModifyResv,
+    ResvBegin,
+    ResvConfirm,
+    ResvEnd,
+    Management,
+    ModifyVnode,
+    Provision,
+    Periodic,
+    ExecJobBegin,
+    ExecJobPrologue,
+    ExecJobLaunch,
+    ExecJobAttach,
+    ExecJobResize,
+    ExecJobPreterm,
+    ExecJobPostsuspend,
+    ExecJobPreresume,
+    ExecJobAbort,
+    ExecJobEpilogue,
+    ExecJobEnd,
+    ExecHostStartup,
+    ExecHostPeriodic,
+    /// events added after this crate
+    Other(String),
+}
+
+const EVENTS: [(HookEvent, &str); 27] = [
+    (HookEvent::QueueJob, "queuejob"),
+    (HookEvent::ModifyJob, "modifyjob"),
+    (HookEvent::MoveJob, "movejob"),
+    (HookEvent::RunJob, "runjob"),
+    (HookEvent::JobObit, "jobobit"),
+    (HookEvent::ResvSub, "resvsub"),
+    (HookEvent::ModifyResv, "modifyresv"),
+    (HookEvent::ResvBegin, "resv_begin"),
+    (HookEvent::ResvConfirm, "resv_confirm"),
+    (HookEvent::ResvEnd, "resv_end"),
+    (HookEvent::Management, "management"),
+    (HookEvent::ModifyVnode, "modifyvnode"),
+    (HookEvent::Provision, "provision"),
+    (HookEvent::Periodic, "periodic"),
+    (HookEvent::ExecJobBegin, "execjob_begin"),
+    (HookEvent::ExecJobPrologue, "execjob_prologue"),
+    (HookEvent::ExecJobLaunch, "execjob_launch"),
+    (HookEvent::ExecJobAttach, "execjob_attach"),
+    (HookEvent::ExecJobResize, "execjob_resize"),
+    (HookEvent::ExecJobPreterm, "execjob_preterm"),
+    (HookEvent::ExecJobPostsuspend, "execjob_postsuspend"),
+    (HookEvent::ExecJobPreresume, "execjob_preresume"),
+    (HookEvent::ExecJobAbort, "execjob_abort"),
+    (HookEvent::ExecJobEpilogue, "execjob_epilogue"),
+    (HookEvent::ExecJobEnd, "execjob_end"),
+    (HookEvent::ExecHostStartup, "exechost_startup"),
+    (HookEvent::ExecHostPeriodic, "exechost_periodic"),
+];
+
+impl HookEvent {
+    /// whether the hook runs on the MoMs rather than the server
+    pub fn is_mom(&self) -> bool {
+        self.to_string().starts_with("exec")
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty hook event".to_string());
+        }
+        Ok(EVENTS
+            .iter()
+            .find(|(_, n)| *n == s)
+            .map(|(e, _)| e.clone())
+            .unwrap_or_else(|| HookEvent::Other(s.to_string())))
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookEvent::Other(s) => write!(f, "{s}"),
+            e => {
+                let name = EVENTS
+                    .iter()
+                    .find(|(ev, _)| ev == e)
+                    .map(|(_, n)| *n)
+                    .unwrap_or_default();
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+/// Settable attributes of a site hook, None leaves an attribute as it is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookConfig {
+    pub event: Option<Vec<HookEvent>>,
+    /// lower orders run first, 1-1000 for site hooks
+    pub order: Option<i64>,
+    pub enabled: Option<bool>,
+    /// seconds the hook may run before it's killed
+    pub alarm: Option<u64>,
+    /// pbsadmin or pbsuser
+    pub user: Option<String>,
+    /// e.g. offline_vnodes, clear_vnodes_upon_recovery or scheduler_restart_cycle
+    pub fail_action: Option<Vec<String>>,
+    /// seconds between runs of periodic hooks
+    pub freq: Option<u64>,
+    pub debug: Option<bool>,
+}
+
+fn join<T: ToString>(vals: &[T]) -> String {
+    vals.iter().map(T::to_string).collect::<Vec<_>>().join(",")
+}
+
+fn split(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != "\"\"")
+        .map(str::to_string)
+        .collect()
+}
+
+impl HookConfig {
+    pub fn new() -> HookConfig {
+        HookConfig::default()
+    }
+    /// whether an attribute has a field here
+    pub(crate) fn supports(key: &str) -> bool {
+        matches!(
+            key,
+            EVENT | ORDER | ENABLED | ALARM | USER | FAIL_ACTION | FREQ | DEBUG
+        )
+    }
+    /// attribute name and value of every attribute that is set
+    pub fn values(&self) -> Vec<(&'static str, String)> {
+        let mut out = Vec::new();
+        if let Some(e) = &self.event {
+            out.push((EVENT, join(e)));
+        }
+        if let Some(o) = self.order {
+            out.push((ORDER, o.to_string()));
+        }
+        if let Some(e) = self.enabled {
+            out.push((ENABLED, e.to_string()));
+        }
+        if let Some(a) = self.alarm {
+            out.push((ALARM, a.to_string()));
+        }
+        if let Some(u) = &self.user {
+            out.push((USER, u.clone()));
+        }
+        if let Some(f) = &self.fail_action {
+            // an empty list is written as none
+            let f = if f.is_empty() {
+                "none".to_string()
+            } else {
+                join(f)
+            };
+            out.push((FAIL_ACTION, f));
+        }
+        if let Some(f) = self.freq {
+            out.push((FREQ, f.to_string()));
+        }
+        if let Some(d) = self.debug {
+            out.push((DEBUG, d.to_string()));
+        }
+        out
+    }
+    // events and fail actions are sets, sort them so they compare equal whatever their order
+    fn sorted(&self) -> HookConfig {
+        let mut c = self.clone();
+        if let Some(e) = c.event.as_mut() {
+            e.sort();
+        }
+        if let Some(f) = c.fail_action.as_mut() {
+            f.sort();
+        }
+        c
+    }
+    /// attributes set here that differ in `current`
+    pub fn diff(&self, current: &HookConfig) -> Vec<(&'static str, String)> {
+        let current = current.sorted().values();
+        self.sorted()
+            .values()
+            .into_iter()
+            .filter(|v| !current.contains(v))
+            .collect()
+    }
+}
+
+impl From<&Status> for HookConfig {
+    /// a hook from stat_hook, unparsable values are left as None
+    fn from(s: &Status) -> HookConfig {
+        let get = |name: &str| match s.attribs().get(name) {
+            Some(Attrl::Value(v)) => Some(v.val()),
+            _ => None,
+        };
+        let bool = |name: &str| get(name).and_then(|v| parse_bool(&v));
+        HookConfig {
+            event: get(EVENT).map(|e| split(&e).iter().filter_map(|e| e.parse().ok()).collect()),
+            order: get(ORDER).and_then(|o| o.trim().parse().ok()),
+            enabled: bool(ENABLED),
+            alarm: get(ALARM).and_then(|a| a.trim().parse().ok()),
+            user: get(USER),
+            fail_action: get(FAIL_ACTION)
+                .map(|f| split(&f).into_iter().filter(|f| f != "none").collect()),
+            freq: get(FREQ).and_then(|f| f.trim().parse().ok()),
+            debug: bool(DEBUG),
+        }
+    }
+}
+
+fn number<T: FromStr>(name: &str, val: &str) -> Result<T, String> {
+    val.trim()
+        .parse()
+        .map_err(|_| format!("{name} isn't a number: {val}"))
+}
+
+impl TryFrom<&BTreeMap<String, String>> for HookConfig {
+    type Error = String;
+    /// a hook from attribute values, as values() returns them
+    fn try_from(values: &BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        let mut c = HookConfig::new();
+        for (k, v) in values {
+            let bool = || parse_bool(v).ok_or_else(|| format!("{k} isn't a boolean: {v}"));
+            match k.as_str() {
+                EVENT => {
+                    let mut events = Vec::new();
+                    for e in split(v) {
+                        // Other is for events newer than this crate reported by the server,
+                        // in a config it's more likely a typo
+                        match e.parse()? {
+                            HookEvent::Other(e) => return Err(format!("unknown hook event {e}")),
+                            e => events.push(e),
+                        }
+                    }
+                    c.event = Some(events)
+                }
+                ORDER => c.order = Some(number(k, v)?),
+                ENABLED => c.enabled = Some(bool()?),
+                ALARM => c.alarm = Some(number(k, v)?),
+                USER => c.user = Some(v.clone()),
+                FAIL_ACTION => {
+                    c.fail_action = Some(split(v).into_iter().filter(|f| f != "none").collect())
+                }
+                FREQ => c.freq = Some(number(k, v)?),
+                DEBUG => c.debug = Some(bool()?),
+                _ => return Err(format!("unsupported hook attribute {k}")),
+            }
+        }
+        Ok(c)
+    }
+}
+
+/// A site hook as returned by stat_hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub name: String,
+    /// site for hooks created with create_hook
+    pub hook_type: String,
+    pub config: HookConfig,
+}
+
+impl From<&Status> for Hook {
+    fn from(s: &Status) -> Hook {
+        let hook_type = match s.attribs().get(TYPE) {
+            Some(Attrl::Value(v)) => v.val(),
+            _ => String::new(),
+        };
+        Hook {
+            name: s.name(),
+            hook_type,
+            config: s.into(),
+        }
+    }
+}
+
+/// What is being imported or exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookContent {
+    /// the hook's Python script
+    Script,
+    /// the hook's configuration file, ini, json or python
+    Config,
+}
+
+impl HookContent {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            HookContent::Script => "application/x-python",
+            HookContent::Config => "application/x-config",
+        }
+    }
+}
+
+impl FromStr for HookContent {
+    type Err = String;
+    /// parse a content type, application/x-python or application/x-config
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [HookContent::Script, HookContent::Config]
+            .into_iter()
+            .find(|c| c.content_type() == s.trim())
+            .ok_or_else(|| format!("unknown hook content type {s}"))
+    }
+}
+
+/// How imported or exported content is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// as is
+    Default,
+    /// base64, decoded by the server on import
+    Base64,
+}
+
+impl ContentEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Default => "default",
+            ContentEncoding::Base64 => "base64",
+        }
+    }
+}
+
+impl FromStr for ContentEncoding {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [ContentEncoding::Default, ContentEncoding::Base64]
+            .into_iter()
+            .find(|e| e.name() == s.trim())
+            .ok_or_else(|| format!("unknown hook content encoding {s}"))
+    }
+}
+
+// file in the server's hook work directory for passing content to and from the server
+fn work_file(pbs_home: &Path, hook: &str) -> (String, PathBuf) {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let name = format!("{hook}.{}.{nanos}", process::id());
+    let path = pbs_home.join("server_priv/hooks/tmp").join(&name);
+    (name, path)
+}
+
+impl Server {
+    /// all site hooks
+    pub fn hooks(&self) -> Result<Vec<Hook>, String> {
+        Ok(self
+            .stat_hook(&None, None)?
+            .resources
+            .iter()
+            .map(Hook::from)
+            .collect())
+    }
+
+    /// create a site hook, `qmgr -c "create hook ..."`, and set the attributes in `config`
+    pub fn create_hook(&self, name: &str, config: &HookConfig) -> Result<(), String> {
+        trace!("creating hook {name}");
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_CREATE,
+            pbs_sys::mgr_obj_MGR_OBJ_HOOK,
+            name,
+            Vec::new(),
+        )?;
+        self.set_hook(name, config)
+    }
+
+    /// set the attributes in `config` on a site hook
+    pub fn set_hook(&self, name: &str, config: &HookConfig) -> Result<(), String> {
+        let entries: Vec<_> = config
+            .values()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), None, Op::Set(v)))
+            .collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        trace!("setting {} attributes on hook {name}", entries.len());
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_SET,
+            pbs_sys::mgr_obj_MGR_OBJ_HOOK,
+            name,
+            entries,
+        )
+    }
+
+    pub fn delete_hook(&self, name: &str) -> Result<(), String> {
+        trace!("deleting hook {name}");
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_DELETE,
+            pbs_sys::mgr_obj_MGR_OBJ_HOOK,
+            name,
+            Vec::new(),
+        )
+    }
+
+    /// Load a hook's script or config file, `qmgr -c "import hook ..."`
+    ///
+    /// The server reads imported content from its hook work directory, so like qmgr
+    /// this has to run on the server host with write access to `pbs_home`.
+    /// `data` must already be in `encoding`.
+    pub fn import_hook<P: AsRef<Path>>(
+        &self,
+        name: &str,
+        content: HookContent,
+        encoding: ContentEncoding,
+        data: &[u8],
+        pbs_home: P,
+    ) -> Result<(), String> {
+        let (file, path) = work_file(pbs_home.as_ref(), name);
+        debug!("importing {content:?} for hook {name} through {path:?}");
+        fs::write(&path, data).map_err(|e| format!("failed to write {path:?}: {e}"))?;
+        let resp = self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_IMPORT,
+            pbs_sys::mgr_obj_MGR_OBJ_HOOK,
+            name,
+            vec![
+                (
+                    "content-type".to_string(),
+                    None,
+                    Op::Set(content.content_type().to_string()),
+                ),
+                (
+                    "content-encoding".to_string(),
+                    None,
+                    Op::Set(encoding.name().to_string()),
+                ),
+                ("input-file".to_string(), None, Op::Set(file)),
+            ],
+        );
+        if let Err(e) = fs::remove_file(&path) {
+            debug!("failed to remove {path:?}: {e}");
+        }
+        resp
+    }
+
+    /// Fetch a hook's script or config file, `qmgr -c "export hook ..."`
+    ///
+    /// The server writes exported content to its hook work directory, so like qmgr
+    /// this has to run on the server host with read access to `pbs_home`.
+    pub fn export_hook<P: AsRef<Path>>(
+        &self,
+        name: &str,
+        content: HookContent,
+        encoding: ContentEncoding,
+        pbs_home: P,
+    ) -> Result<Vec<u8>, String> {
+        let (file, path) = work_file(pbs_home.as_ref(), name);
+        debug!("exporting {content:?} for hook {name} through {path:?}");
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_EXPORT,
+            pbs_sys::mgr_obj_MGR_OBJ_HOOK,
+            name,
+            vec![
+                (
+                    "content-type".to_string(),
+                    None,
+                    Op::Set(content.content_type().to_string()),
+                ),
+                (
+                    "content-encoding".to_string(),
+                    None,
+                    Op::Set(encoding.name().to_string()),
+                ),
+                ("output-file".to_string(), None, Op::Set(file)),
+            ],
+        )?;
+        let data = fs::read(&path).map_err(|e| format!("failed to read {path:?}: {e}"));
+        if let Err(e) = fs::remove_file(&path) {
+            debug!("failed to remove {path:?}: {e}");
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Attribs;
+
+    fn values(v: &[(&str, &str)]) -> BTreeMap<String, String> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!("execjob_begin".parse(), Ok(HookEvent::ExecJobBegin));
+        assert_eq!(
+            "futurejob".parse(),
+            Ok(HookEvent::Other("futurejob".to_string()))
+        );
+        assert!("".parse::<HookEvent>().is_err());
+        assert_eq!(HookEvent::ResvBegin.to_string(), "resv_begin");
+        assert!(HookEvent::ExecHostPeriodic.is_mom());
+        assert!(!HookEvent::QueueJob.is_mom());
+    }
+
+    #[test]
+    fn from_stat() {
+        let mut a = Attribs::new();
+        for (k, v) in [
+            (TYPE, "site"),
+            (ENABLED, "true"),
+            (EVENT, "execjob_end,execjob_begin,exechost_startup"),
+            (USER, "pbsadmin"),
+            (ALARM, "90"),
+            (ORDER, "100"),
+            (DEBUG, "false"),
+            (FAIL_ACTION, "none"),
+            (FREQ, "often"),
+        ] {
+            a.add(k.to_string(), Attrl::Value(Op::Default(v.to_string())));
+        }
+        let hook = Hook::from(&Status::new("cgroups".to_string(), None, a));
+        assert_eq!(hook.name, "cgroups");
+        assert_eq!(hook.hook_type, "site");
+        assert_eq!(
+            hook.config,
+            HookConfig {
+                event: Some(vec![
+                    HookEvent::ExecJobEnd,
+                    HookEvent::ExecJobBegin,
+                    HookEvent::ExecHostStartup,
+                ]),
+                order: Some(100),
+                enabled: Some(true),
+                alarm: Some(90),
+                user: Some("pbsadmin".to_string()),
+                // none is no actions rather than unset
+                fail_action: Some(Vec::new()),
+                // unparsable
+                freq: None,
+                debug: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let live = HookConfig {
+            event: Some(vec![HookEvent::ExecJobEnd, HookEvent::ExecJobBegin]),
+            order: Some(100),
+            enabled: Some(true),
+            fail_action: Some(Vec::new()),
+            ..Default::default()
+        };
+        let mut want = HookConfig {
+            event: Some(vec![HookEvent::ExecJobBegin, HookEvent::ExecJobEnd]),
+            enabled: Some(true),
+            fail_action: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert_eq!(want.diff(&live), []);
+        want.order = Some(5);
+        want.alarm = Some(30);
+        want.fail_action = Some(vec!["offline_vnodes".to_string()]);
+        assert_eq!(
+            want.diff(&live),
+            [
+                (ORDER, "5".to_string()),
+                (ALARM, "30".to_string()),
+                (FAIL_ACTION, "offline_vnodes".to_string()),
+            ]
+        );
+        want.fail_action = Some(Vec::new());
+        assert_eq!(
+            HookConfig::new().diff(&want),
+            [],
+            "unset fields are left alone"
+        );
+    }
+
+    #[test]
+    fn try_from_values() {
+        let c = HookConfig::try_from(&values(&[
+            (EVENT, "queuejob, modifyjob"),
+            (ORDER, "10"),
+            (ENABLED, "True"),
+            (FAIL_ACTION, "none"),
+            (FREQ, "300"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            c.event,
+            Some(vec![HookEvent::QueueJob, HookEvent::ModifyJob])
+        );
+        assert_eq!(c.order, Some(10));
+        assert_eq!(c.enabled, Some(true));
+        assert_eq!(c.fail_action, Some(Vec::new()));
+        assert_eq!(c.freq, Some(300));
+        let round_trip: BTreeMap<String, String> = c
+            .values()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(HookConfig::try_from(&round_trip), Ok(c));
+    }
+
+    #[test]
+    fn try_from_errors() {
+        let err = |v: &[(&str, &str)]| HookConfig::try_from(&values(v)).unwrap_err();
+        assert_eq!(
+            err(&[(EVENT, "queuejob,runjobb")]),
+            "unknown hook event runjobb"
+        );
+        assert_eq!(err(&[(ORDER, "first")]), "order isn't a number: first");
+        assert_eq!(err(&[(ENABLED, "maybe")]), "enabled isn't a boolean: maybe");
+        assert_eq!(
+            err(&[("alarms", "30")]),
+            "unsupported hook attribute alarms"
+        );
+    }
+}
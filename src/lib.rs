@@ -1,7 +1,49 @@
+mod accounting;
 mod api;
+pub mod attributes;
 mod bindings;
+mod config;
+mod daemonlog;
+mod drain;
+mod efficiency;
+mod export;
 mod helpers;
+mod hook;
+mod metrics;
+mod queue;
+mod snapshot;
+mod table;
+mod text;
 mod types;
+mod watch;
 
+pub use accounting::{
+    log_files, parse_accounting_record, read_accounting_dir, AccountingReader, AccountingRecord,
+    RecordType,
+};
 pub use api::{ResvModFlag, ResvSubFlag};
-pub use types::{Attribs, Attrl, Op, Resource, Server, StatResp, Status};
+pub use attributes::{Access, AttrDef, ValueType};
+pub use config::{ApplyReport, HookImport, Object, ObjectConfig, Plan, ServerConfig, Step};
+pub use daemonlog::{
+    job_timeline, parse_log_line, read_log_dir, EventClass, LogEntry, LogFilter, LogReader,
+    LogSource, ObjectType,
+};
+pub use drain::{DrainAction, DrainProgress, DrainReport};
+pub use efficiency::{
+    aggregate_usage, job_usage, jobs_csv, summary_csv, GroupBy, JobUsage, Summary, Window,
+};
+pub use export::{Field, FieldType, Schema};
+pub use hook::{ContentEncoding, Hook, HookConfig, HookContent, HookEvent};
+pub use metrics::{render_metrics, serve_metrics, JobLabel, MetricsConfig};
+pub use queue::{QueueConfig, QueueType};
+pub use snapshot::{Capacity, ClusterSnapshot, NodeUsage, QueueUsage};
+pub use table::{Align, CellFormat, Column, Preset, Table};
+pub use text::{parse_text, Text};
+pub use types::{
+    builtin_resources, decode, has_decoder, AttrChange, Attribs, Attrl, Decoded, Diagnostic,
+    DiagnosticKind, ExecHost, ExecVnode, Frequency, HostSlot, NotRunningReason, Op, Operation,
+    ReservationSpec, ReservationState, Resource, ResourceDef, ResourceFlags, ResourceType,
+    ResvModResponse, ResvModStatus, ResvModification, Rrule, Scope, Server, StatResp, Status,
+    VariableList, VnodeAlloc, VnodeState, VnodeStateChange,
+};
+pub use watch::{diff, Event, WatchHandle, WatchIter, Watcher};
@@ -0,0 +1,416 @@
+use crate::attributes::{job, node, queue};
+use crate::snapshot::{is_array_parent, ClusterSnapshot};
+use crate::types::{Attribs, Attrl, Server, Status};
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Labels jobs can be grouped by in the jobs metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobLabel {
+    User,
+    Queue,
+    State,
+    Project,
+}
+
+impl JobLabel {
+    fn name(&self) -> &'static str {
+        match self {
+            JobLabel::User => "user",
+            JobLabel::Queue => "queue",
+            JobLabel::State => "state",
+            JobLabel::Project => "project",
+        }
+    }
+    fn value(&self, job: &Status) -> String {
+        let attr = match self {
+            JobLabel::User => job::JOB_OWNER,
+            JobLabel::Queue => job::QUEUE,
+            JobLabel::State => job::JOB_STATE,
+            JobLabel::Project => job::PROJECT,
+        };
+        let val = match job.attribs().get(attr) {
+            Some(Attrl::Value(v)) => v.val(),
+            _ => String::new(),
+        };
+        // Job_Owner is user@submithost
+        match self {
+            JobLabel::User => val.split('@').next().unwrap_or("").to_string(),
+            _ => val,
+        }
+    }
+}
+
+/// What to export and how much of it
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// metric name prefix, defaults to "pbs"
+    pub prefix: String,
+    /// labels the jobs metric is grouped by, defaults to queue and state
+    pub job_labels: Vec<JobLabel>,
+    /// export per vnode metrics
+    pub nodes: bool,
+    /// most series emitted per metric, the rest are counted in
+    /// <prefix>_exporter_dropped_series
+    pub max_series: usize,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            prefix: "pbs".to_string(),
+            job_labels: vec![JobLabel::Queue, JobLabel::State],
+            nodes: true,
+            max_series: 10000,
+        }
+    }
+}
+
+// one metric family while it's being built
+struct Family {
+    name: String,
+    help: &'static str,
+    series: Vec<(Vec<(&'static str, String)>, f64)>,
+}
+
+fn escape(val: &str) -> String {
+    val.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+struct Renderer<'a> {
+    cfg: &'a MetricsConfig,
+    families: Vec<Family>,
+}
+
+impl Renderer<'_> {
+    fn family(&mut self, name: &str, help: &'static str) -> &mut Family {
+        self.families.push(Family {
+            name: format!("{}_{name}", self.cfg.prefix),
+            help,
+            series: Vec::new(),
+        });
+        self.families.last_mut().unwrap()
+    }
+
+    fn render(self) -> String {
+        let mut out = String::new();
+        let mut dropped = BTreeMap::new();
+        for f in self.families {
+            _ = writeln!(out, "# TYPE {} gauge", f.name);
+            _ = writeln!(out, "# HELP {} {}", f.name, f.help);
+            if f.series.len() > self.cfg.max_series {
+                dropped.insert(f.name.clone(), f.series.len() - self.cfg.max_series);
+            }
+            for (labels, val) in f.series.iter().take(self.cfg.max_series) {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+                    .collect();
+                if labels.is_empty() {
+                    _ = writeln!(out, "{} {val}", f.name);
+                } else {
+                    _ = writeln!(out, "{}{{{}}} {val}", f.name, labels.join(","));
+                }
+            }
+        }
+        let name = format!("{}_exporter_dropped_series", self.cfg.prefix);
+        _ = writeln!(out, "# TYPE {name} gauge");
+        _ = writeln!(out, "# HELP {name} Series left out because of max_series");
+        for (family, n) in dropped {
+            _ = writeln!(out, "{name}{{family=\"{family}\"}} {n}");
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+fn value(status: &Status, attr: &str) -> Option<String> {
+    match status.attribs().get(attr) {
+        Some(Attrl::Value(v)) => Some(v.val()),
+        _ => None,
+    }
+}
+
+fn state_counts(status: &Status) -> Vec<(String, f64)> {
+    value(status, queue::STATE_COUNT)
+        .map(|c| {
+            Attribs::split_state_count(&c)
+                .into_iter()
+                .filter_map(|(s, n)| n.parse().ok().map(|n| (s, n)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn flag(status: &Status, attr: &str) -> f64 {
+    match value(status, attr) {
+        Some(v) if v.eq_ignore_ascii_case("true") => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Render a snapshot in the OpenMetrics text format
+pub fn render_metrics(snapshot: &ClusterSnapshot, cfg: &MetricsConfig) -> String {
+    let mut r = Renderer {
+        cfg,
+        families: Vec::new(),
+    };
+
+    let f = r.family(
+        "server_jobs",
+        "Jobs on the server by state, from state_count",
+    );
+    for srv in &snapshot.server {
+        for (state, n) in state_counts(srv) {
+            f.series
+                .push((vec![("server", srv.name()), ("state", state)], n));
+        }
+    }
+
+    let f = r.family("queue_jobs", "Jobs in a queue by state, from state_count");
+    for q in &snapshot.queues {
+        for (state, n) in state_counts(q) {
+            f.series
+                .push((vec![("queue", q.name()), ("state", state)], n));
+        }
+    }
+    let f = r.family("queue_enabled", "Whether a queue accepts new jobs");
+    for q in &snapshot.queues {
+        f.series
+            .push((vec![("queue", q.name())], flag(q, queue::ENABLED)));
+    }
+    let f = r.family("queue_started", "Whether jobs in a queue can be run");
+    for q in &snapshot.queues {
+        f.series
+            .push((vec![("queue", q.name())], flag(q, queue::STARTED)));
+    }
+
+    if cfg.nodes {
+        let f = r.family("node_state", "Set to 1 for each state a vnode is in");
+        for (name, n) in snapshot.nodes() {
+            let states = if n.state.is_free() {
+                vec!["free"]
+            } else {
+                n.state.names()
+            };
+            for s in states {
+                f.series
+                    .push((vec![("node", name.clone()), ("state", s.to_string())], 1.0));
+            }
+        }
+        for (attr, metric, help) in [
+            (
+                node::RESOURCES_AVAILABLE,
+                "node_resources_available",
+                "Numeric resources available on a vnode, sizes in bytes",
+            ),
+            (
+                node::RESOURCES_ASSIGNED,
+                "node_resources_assigned",
+                "Numeric resources assigned on a vnode, sizes in bytes",
+            ),
+        ] {
+            let f = r.family(metric, help);
+            for v in &snapshot.vnodes {
+                for (res, amount) in v.resource_amounts(attr) {
+                    f.series
+                        .push((vec![("node", v.name()), ("resource", res)], amount as f64));
+                }
+            }
+        }
+    }
+
+    let mut jobs: BTreeMap<Vec<(&'static str, String)>, f64> = BTreeMap::new();
+    for job in snapshot.jobs.iter().filter(|j| !is_array_parent(j)) {
+        let labels = cfg
+            .job_labels
+            .iter()
+            .map(|l| (l.name(), l.value(job)))
+            .collect();
+        *jobs.entry(labels).or_default() += 1.0;
+    }
+    let f = r.family("jobs", "Jobs grouped by the configured labels");
+    f.series.extend(jobs);
+
+    for (metric, help, cap) in [
+        (
+            "cluster_available",
+            "Resources on usable vnodes, sizes in bytes",
+            snapshot.available(),
+        ),
+        (
+            "cluster_assigned",
+            "Resources assigned to jobs, sizes in bytes",
+            snapshot.assigned(),
+        ),
+        (
+            "cluster_reservation_blocked",
+            "Free resources held by reservations, sizes in bytes",
+            snapshot.reservation_blocked(),
+        ),
+    ] {
+        let f = r.family(metric, help);
+        for (res, v) in [("ncpus", cap.ncpus), ("mem", cap.mem), ("ngpus", cap.ngpus)] {
+            f.series
+                .push((vec![("resource", res.to_string())], v as f64));
+        }
+    }
+    let idle = snapshot.idle_nodes().len() as f64;
+    r.family("cluster_idle_nodes", "Usable vnodes with nothing assigned")
+        .series
+        .push((Vec::new(), idle));
+    let frag = snapshot.fragmentation();
+    r.family(
+        "cluster_fragmentation",
+        "Fraction of free ncpus on partially used vnodes",
+    )
+    .series
+    .push((Vec::new(), frag));
+
+    r.render()
+}
+
+// request line, after reading the headers up to the blank line so the client
+// isn't reset when the connection is closed under them
+fn read_request(stream: &TcpStream) -> io::Result<String> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    Ok(request)
+}
+
+/// Serve /metrics over HTTP on `addr`, taking a snapshot of `srv` on every scrape
+///
+/// Connects to the default server if `srv` is None. Blocks forever unless the
+/// listener fails.
+pub fn serve_metrics<A: ToSocketAddrs>(
+    addr: A,
+    srv: Option<&str>,
+    cfg: &MetricsConfig,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("Serving metrics on {:?}", listener.local_addr());
+    let server = match srv {
+        Some(s) => Server::connect_to(s).map_err(io::Error::other)?,
+        None => Server::new(),
+    };
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Error accepting connection: {e}");
+                continue;
+            }
+        };
+        let request = match read_request(&stream) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Error reading request: {e}");
+                continue;
+            }
+        };
+        debug!("Got request {}", request.trim());
+        let path = request.split_whitespace().nth(1).unwrap_or("");
+        let resp = if !request.starts_with("GET ") || path != "/metrics" {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        } else {
+            match ClusterSnapshot::capture(&server) {
+                Ok(s) => {
+                    let body = render_metrics(&s, cfg);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                }
+                Err(e) => {
+                    warn!("Error taking snapshot: {e}");
+                    format!(
+                        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{e}",
+                        e.len()
+                    )
+                }
+            }
+        };
+        if let Err(e) = stream.write_all(resp.as_bytes()) {
+            warn!("Error writing response: {e}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_text;
+    use std::thread;
+
+    #[test]
+    fn jobs_skip_array_parents() {
+        let jobs = parse_text(
+            "\
+Job Id: 7[].svr
+    job_state = B
+    queue = workq
+
+Job Id: 7[1].svr
+    job_state = R
+    queue = workq
+
+Job Id: 7[2].svr
+    job_state = Q
+    queue = workq
+
+Job Id: 8.svr
+    job_state = Q
+    queue = workq
+",
+        )
+        .unwrap()
+        .1
+        .resources;
+        let snap = ClusterSnapshot::new(0, Vec::new(), Vec::new(), Vec::new(), jobs, Vec::new());
+        let out = render_metrics(&snap, &MetricsConfig::default());
+        let jobs: Vec<&str> = out.lines().filter(|l| l.starts_with("pbs_jobs{")).collect();
+        assert_eq!(
+            jobs,
+            [
+                "pbs_jobs{queue=\"workq\",state=\"Q\"} 2",
+                "pbs_jobs{queue=\"workq\",state=\"R\"} 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn request_headers_are_consumed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut c = TcpStream::connect(addr).unwrap();
+            c.write_all(b"GET /metrics HTTP/1.1\r\n").unwrap();
+            // headers in a later packet must still be read before replying
+            thread::sleep(Duration::from_millis(50));
+            c.write_all(b"Host: x\r\nAccept: */*\r\n\r\n").unwrap();
+            c
+        });
+        let (stream, _) = listener.accept().unwrap();
+        assert_eq!(read_request(&stream).unwrap(), "GET /metrics HTTP/1.1\r\n");
+        let _client = client.join().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut rest = [0; 1];
+        assert!(io::Read::read(&mut &stream, &mut rest).is_err());
+    }
+}
@@ -0,0 +1,484 @@
+use crate::attributes::queue;
+use crate::helpers::parse_bool;
+use crate::types::{
+    diff_resources, diff_values, same_list, same_value, AttrChange, Attrl, Op, Server, Status,
+};
+use log::trace;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueType {
+    Execution,
+    Route,
+}
+
+impl FromStr for QueueType {
+    type Err = String;
+    /// case insensitive, the server accepts any prefix and reports Execution or Route
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if !s.is_empty() && "execution".starts_with(&s) {
+            Ok(QueueType::Execution)
+        } else if !s.is_empty() && "route".starts_with(&s) {
+            Ok(QueueType::Route)
+        } else {
+            Err(format!("unknown queue type {s}"))
+        }
+    }
+}
+
+impl fmt::Display for QueueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueType::Execution => write!(f, "execution"),
+            QueueType::Route => write!(f, "route"),
+        }
+    }
+}
+
+/// Configuration of a queue
+///
+/// None leaves an attribute as it is. Resource maps are exact, resources missing
+/// from a Some map are unset by diff().
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueConfig {
+    pub queue_type: Option<QueueType>,
+    pub enabled: Option<bool>,
+    pub started: Option<bool>,
+    pub priority: Option<i64>,
+    pub from_route_only: Option<bool>,
+    pub route_destinations: Option<Vec<String>>,
+    pub resources_max: Option<BTreeMap<String, String>>,
+    pub resources_min: Option<BTreeMap<String, String>>,
+    pub resources_default: Option<BTreeMap<String, String>>,
+    /// e.g. [u:PBS_GENERIC=10]
+    pub max_run: Option<String>,
+    pub max_run_soft: Option<String>,
+    pub max_running: Option<i64>,
+    pub max_queued: Option<String>,
+    pub acl_user_enable: Option<bool>,
+    pub acl_users: Option<Vec<String>>,
+    pub acl_group_enable: Option<bool>,
+    pub acl_groups: Option<Vec<String>>,
+}
+
+// attributes compared as unordered lists
+const LISTS: [&str; 3] = [
+    queue::ROUTE_DESTINATIONS,
+    queue::ACL_USERS,
+    queue::ACL_GROUPS,
+];
+
+impl QueueConfig {
+    pub fn new() -> QueueConfig {
+        QueueConfig::default()
+    }
+
+    /// whether an attribute, name or name.resource, has a field here
+    pub(crate) fn supports(key: &str) -> bool {
+        match key.split_once('.') {
+            Some((name, _)) => matches!(
+                name,
+                queue::RESOURCES_MAX | queue::RESOURCES_MIN | queue::RESOURCES_DEFAULT
+            ),
+            None => matches!(
+                key,
+                queue::QUEUE_TYPE
+                    | queue::ENABLED
+                    | queue::STARTED
+                    | queue::PRIORITY
+                    | queue::FROM_ROUTE_ONLY
+                    | queue::ROUTE_DESTINATIONS
+                    | queue::MAX_RUN
+                    | queue::MAX_RUN_SOFT
+                    | queue::MAX_RUNNING
+                    | queue::MAX_QUEUED
+                    | queue::ACL_USER_ENABLE
+                    | queue::ACL_USERS
+                    | queue::ACL_GROUP_ENABLE
+                    | queue::ACL_GROUPS
+            ),
+        }
+    }
+
+    /// values of the attributes that are set, other than resources
+    pub fn values(&self) -> BTreeMap<String, String> {
+        let mut out = BTreeMap::new();
+        let mut add = |name: &str, val: Option<String>| {
+            if let Some(v) = val {
+                out.insert(name.to_string(), v);
+            }
+        };
+        add(queue::QUEUE_TYPE, self.queue_type.map(|t| t.to_string()));
+        add(queue::ENABLED, self.enabled.map(|e| e.to_string()));
+        add(queue::STARTED, self.started.map(|s| s.to_string()));
+        add(queue::PRIORITY, self.priority.map(|p| p.to_string()));
+        add(
+            queue::FROM_ROUTE_ONLY,
+            self.from_route_only.map(|f| f.to_string()),
+        );
+        add(
+            queue::ROUTE_DESTINATIONS,
+            self.route_destinations.as_ref().map(|d| d.join(",")),
+        );
+        add(queue::MAX_RUN, self.max_run.clone());
+        add(queue::MAX_RUN_SOFT, self.max_run_soft.clone());
+        add(queue::MAX_RUNNING, self.max_running.map(|m| m.to_string()));
+        add(queue::MAX_QUEUED, self.max_queued.clone());
+        add(
+            queue::ACL_USER_ENABLE,
+            self.acl_user_enable.map(|a| a.to_string()),
+        );
+        add(
+            queue::ACL_USERS,
+            self.acl_users.as_ref().map(|a| a.join(",")),
+        );
+        add(
+            queue::ACL_GROUP_ENABLE,
+            self.acl_group_enable.map(|a| a.to_string()),
+        );
+        add(
+            queue::ACL_GROUPS,
+            self.acl_groups.as_ref().map(|a| a.join(",")),
+        );
+        out
+    }
+
+    fn resources(&self) -> [(&'static str, Option<&BTreeMap<String, String>>); 3] {
+        [
+            (queue::RESOURCES_MAX, self.resources_max.as_ref()),
+            (queue::RESOURCES_MIN, self.resources_min.as_ref()),
+            (queue::RESOURCES_DEFAULT, self.resources_default.as_ref()),
+        ]
+    }
+
+    /// every attribute that is set, with resources as name.resource
+    pub fn flat(&self) -> BTreeMap<String, String> {
+        let mut out = self.values();
+        for (name, map) in self.resources() {
+            for (r, v) in map.into_iter().flatten() {
+                out.insert(format!("{name}.{r}"), v.clone());
+            }
+        }
+        out
+    }
+
+    /// Changes that turn `live` into this configuration
+    ///
+    /// Values are compared the way the server normalises them, so 1gb matches 1024mb
+    /// and lists match in any order.
+    pub fn diff(&self, live: &QueueConfig) -> Vec<AttrChange> {
+        let (lists, scalars): (BTreeMap<_, _>, BTreeMap<_, _>) = self
+            .values()
+            .into_iter()
+            .partition(|(k, _)| LISTS.contains(&k.as_str()));
+        let current = live.flat();
+        let mut out = diff_values(&scalars, &current, same_value);
+        out.extend(diff_values(&lists, &current, same_list));
+        for (name, map) in self.resources() {
+            if let Some(map) = map {
+                out.extend(diff_resources(name, map, &current));
+            }
+        }
+        out
+    }
+}
+
+impl From<&Status> for QueueConfig {
+    /// a queue from stat_que, unparsable values are left as None
+    fn from(s: &Status) -> QueueConfig {
+        let get = |name: &str| match s.attribs().get(name) {
+            Some(Attrl::Value(v)) => Some(v.val()),
+            _ => None,
+        };
+        let bool = |name: &str| get(name).and_then(|v| parse_bool(&v));
+        let long = |name: &str| get(name).and_then(|v| v.trim().parse().ok());
+        let list = |name: &str| {
+            get(name).map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|e| !e.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+        };
+        let resources = |name: &str| {
+            Some(match s.attribs().get(name) {
+                Some(Attrl::Resource(map)) => {
+                    map.iter().map(|(r, v)| (r.clone(), v.val())).collect()
+                }
+                _ => BTreeMap::new(),
+            })
+        };
+        QueueConfig {
+            queue_type: get(queue::QUEUE_TYPE).and_then(|t| t.parse().ok()),
+            enabled: bool(queue::ENABLED),
+            started: bool(queue::STARTED),
+            priority: long(queue::PRIORITY),
+            from_route_only: bool(queue::FROM_ROUTE_ONLY),
+            route_destinations: list(queue::ROUTE_DESTINATIONS),
+            resources_max: resources(queue::RESOURCES_MAX),
+            resources_min: resources(queue::RESOURCES_MIN),
+            resources_default: resources(queue::RESOURCES_DEFAULT),
+            max_run: get(queue::MAX_RUN),
+            max_run_soft: get(queue::MAX_RUN_SOFT),
+            max_running: long(queue::MAX_RUNNING),
+            max_queued: get(queue::MAX_QUEUED),
+            acl_user_enable: bool(queue::ACL_USER_ENABLE),
+            acl_users: list(queue::ACL_USERS),
+            acl_group_enable: bool(queue::ACL_GROUP_ENABLE),
+            acl_groups: list(queue::ACL_GROUPS),
+        }
+    }
+}
+
+impl TryFrom<&BTreeMap<String, String>> for QueueConfig {
+    type Error = String;
+    /// a queue from values keyed by name or name.resource, as flat() returns them
+    fn try_from(values: &BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        let mut c = QueueConfig::new();
+        for (k, v) in values {
+            let bool = || parse_bool(v).ok_or_else(|| format!("{k} isn't a boolean: {v}"));
+            let long = || {
+                v.trim()
+                    .parse()
+                    .map_err(|_| format!("{k} isn't a number: {v}"))
+            };
+            let list = || {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|e| !e.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            };
+            if let Some((name, r)) = k.split_once('.') {
+                let map = match name {
+                    queue::RESOURCES_MAX => &mut c.resources_max,
+                    queue::RESOURCES_MIN => &mut c.resources_min,
+                    queue::RESOURCES_DEFAULT => &mut c.resources_default,
+                    _ => return Err(format!("unsupported queue attribute {k}")),
+                };
+                map.get_or_insert_with(BTreeMap::new)
+                    .insert(r.to_string(), v.clone());
+                continue;
+            }
+            match k.as_str() {
+                queue::QUEUE_TYPE => c.queue_type = Some(v.parse()?),
+                queue::ENABLED => c.enabled = Some(bool()?),
+                queue::STARTED => c.started = Some(bool()?),
+                queue::PRIORITY => c.priority = Some(long()?),
+                queue::FROM_ROUTE_ONLY => c.from_route_only = Some(bool()?),
+                queue::ROUTE_DESTINATIONS => c.route_destinations = Some(list()),
+                queue::MAX_RUN => c.max_run = Some(v.clone()),
+                queue::MAX_RUN_SOFT => c.max_run_soft = Some(v.clone()),
+                queue::MAX_RUNNING => c.max_running = Some(long()?),
+                queue::MAX_QUEUED => c.max_queued = Some(v.clone()),
+                queue::ACL_USER_ENABLE => c.acl_user_enable = Some(bool()?),
+                queue::ACL_USERS => c.acl_users = Some(list()),
+                queue::ACL_GROUP_ENABLE => c.acl_group_enable = Some(bool()?),
+                queue::ACL_GROUPS => c.acl_groups = Some(list()),
+                _ => return Err(format!("unsupported queue attribute {k}")),
+            }
+        }
+        Ok(c)
+    }
+}
+
+impl Server {
+    /// configuration of a queue
+    pub fn queue_config(&self, name: &str) -> Result<QueueConfig, String> {
+        let resp = self.stat_que(&Some(name.to_string()), None)?;
+        resp.resources
+            .first()
+            .map(QueueConfig::from)
+            .ok_or_else(|| format!("no such queue {name}"))
+    }
+
+    /// create a queue, `qmgr -c "create queue ..."`, queue_type should be set
+    pub fn create_queue(&self, name: &str, config: &QueueConfig) -> Result<(), String> {
+        trace!("creating queue {name}");
+        let entries = config
+            .flat()
+            .into_iter()
+            .map(|(k, v)| AttrChange::Set(k, v).entry())
+            .collect();
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_CREATE,
+            pbs_sys::mgr_obj_MGR_OBJ_QUEUE,
+            name,
+            entries,
+        )
+    }
+
+    /// delete a queue, the server refuses if it still has jobs
+    pub fn delete_queue(&self, name: &str) -> Result<(), String> {
+        trace!("deleting queue {name}");
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_DELETE,
+            pbs_sys::mgr_obj_MGR_OBJ_QUEUE,
+            name,
+            Vec::new(),
+        )
+    }
+
+    /// set every attribute set in `config`, nothing is unset
+    pub fn set_queue(&self, name: &str, config: &QueueConfig) -> Result<(), String> {
+        let changes: Vec<_> = config
+            .flat()
+            .into_iter()
+            .map(|(k, v)| AttrChange::Set(k, v))
+            .collect();
+        self.apply_queue(name, &changes)
+    }
+
+    /// apply changes from QueueConfig::diff
+    pub fn apply_queue(&self, name: &str, changes: &[AttrChange]) -> Result<(), String> {
+        trace!("applying {} changes to queue {name}", changes.len());
+        self.apply_changes(pbs_sys::mgr_obj_MGR_OBJ_QUEUE, name, changes)
+    }
+
+    fn set_queue_flag(&self, name: &str, attr: &str, val: bool) -> Result<(), String> {
+        self.manage(
+            pbs_sys::mgr_cmd_MGR_CMD_SET,
+            pbs_sys::mgr_obj_MGR_OBJ_QUEUE,
+            name,
+            vec![(attr.to_string(), None, Op::Set(val.to_string()))],
+        )
+    }
+    /// let the queue accept jobs
+    pub fn enable_queue(&self, name: &str) -> Result<(), String> {
+        self.set_queue_flag(name, queue::ENABLED, true)
+    }
+    /// stop the queue accepting jobs, queued jobs are kept
+    pub fn disable_queue(&self, name: &str) -> Result<(), String> {
+        self.set_queue_flag(name, queue::ENABLED, false)
+    }
+    /// let jobs in the queue run, or be routed
+    pub fn start_queue(&self, name: &str) -> Result<(), String> {
+        self.set_queue_flag(name, queue::STARTED, true)
+    }
+    /// stop jobs in the queue being run or routed, running jobs are left alone
+    pub fn stop_queue(&self, name: &str) -> Result<(), String> {
+        self.set_queue_flag(name, queue::STARTED, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_text;
+
+    const QSTAT_QF: &str = "\
+Queue: workq
+    queue_type = Execution
+    Priority = 10
+    total_jobs = 0
+    state_count = Transit:0 Queued:0 Held:0 Waiting:0 Running:0 Exiting:0 Begun:0 
+    max_running = 100
+    resources_max.mem = 1024mb
+    resources_max.walltime = 24:00:00
+    resources_default.walltime = 01:00:00
+    acl_user_enable = True
+    acl_users = +alice,-bob
+    enabled = True
+    started = False
+
+";
+
+    fn live() -> QueueConfig {
+        QueueConfig::from(&parse_text(QSTAT_QF).unwrap().1.resources[0])
+    }
+
+    fn map(v: &[(&str, &str)]) -> BTreeMap<String, String> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn queue_types() {
+        assert_eq!("Execution".parse(), Ok(QueueType::Execution));
+        assert_eq!("e".parse(), Ok(QueueType::Execution));
+        assert_eq!("ROUTE".parse(), Ok(QueueType::Route));
+        assert!("".parse::<QueueType>().is_err());
+        assert!("exec_queue".parse::<QueueType>().is_err());
+    }
+
+    #[test]
+    fn from_status() {
+        assert_eq!(
+            live(),
+            QueueConfig {
+                queue_type: Some(QueueType::Execution),
+                enabled: Some(true),
+                started: Some(false),
+                priority: Some(10),
+                max_running: Some(100),
+                resources_max: Some(map(&[("mem", "1024mb"), ("walltime", "24:00:00")])),
+                // resources not set on the queue are empty rather than unknown
+                resources_min: Some(BTreeMap::new()),
+                resources_default: Some(map(&[("walltime", "01:00:00")])),
+                acl_user_enable: Some(true),
+                acl_users: Some(vec!["+alice".to_string(), "-bob".to_string()]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_normalises() {
+        let want = QueueConfig {
+            queue_type: Some(QueueType::Execution),
+            enabled: Some(true),
+            started: Some(false),
+            resources_max: Some(map(&[("mem", "1gb"), ("walltime", "86400")])),
+            acl_users: Some(vec!["-bob".to_string(), "+alice".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(want.diff(&live()), []);
+    }
+
+    #[test]
+    fn diff_changes() {
+        let want = QueueConfig {
+            started: Some(true),
+            max_running: Some(50),
+            // walltime is no longer limited
+            resources_max: Some(map(&[("mem", "2gb")])),
+            acl_users: Some(vec!["+alice".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            want.diff(&live()),
+            [
+                AttrChange::Set(queue::MAX_RUNNING.to_string(), "50".to_string()),
+                AttrChange::Set(queue::STARTED.to_string(), "true".to_string()),
+                AttrChange::Set(queue::ACL_USERS.to_string(), "+alice".to_string()),
+                AttrChange::Set("resources_max.mem".to_string(), "2gb".to_string()),
+                AttrChange::Unset("resources_max.walltime".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn try_from_values() {
+        let c = QueueConfig::try_from(&map(&[
+            ("queue_type", "route"),
+            ("route_destinations", "a, b"),
+            ("resources_default.ncpus", "1"),
+            ("Priority", "5"),
+        ]))
+        .unwrap();
+        assert_eq!(c.queue_type, Some(QueueType::Route));
+        assert_eq!(
+            c.route_destinations,
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(c.resources_default, Some(map(&[("ncpus", "1")])));
+        assert_eq!(QueueConfig::try_from(&c.flat()), Ok(c));
+        assert!(QueueConfig::try_from(&map(&[("Priority", "high")])).is_err());
+        assert!(QueueConfig::try_from(&map(&[("kill_delay", "10")])).is_err());
+    }
+}
@@ -0,0 +1,442 @@
+use crate::attributes::{job, node, reservation};
+use crate::types::{Attribs, Attrl, ExecVnode, Server, Status, VnodeState};
+use log::{debug, warn};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Add, AddAssign};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Amounts of the resources tracked by a snapshot, mem is in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capacity {
+    pub ncpus: u64,
+    pub mem: u64,
+    pub ngpus: u64,
+}
+
+impl Capacity {
+    fn from_amounts(amounts: &BTreeMap<String, u64>) -> Capacity {
+        let get = |r: &str| amounts.get(r).copied().unwrap_or(0);
+        Capacity {
+            ncpus: get("ncpus"),
+            mem: get("mem"),
+            ngpus: get("ngpus"),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ncpus == 0 && self.mem == 0 && self.ngpus == 0
+    }
+    /// resources left in self once other is taken out, never below zero
+    pub fn saturating_sub(&self, other: &Capacity) -> Capacity {
+        Capacity {
+            ncpus: self.ncpus.saturating_sub(other.ncpus),
+            mem: self.mem.saturating_sub(other.mem),
+            ngpus: self.ngpus.saturating_sub(other.ngpus),
+        }
+    }
+    pub fn json(&self) -> Value {
+        json!({"ncpus": self.ncpus, "mem": self.mem, "ngpus": self.ngpus})
+    }
+}
+
+impl Add for Capacity {
+    type Output = Capacity;
+    fn add(self, rhs: Capacity) -> Capacity {
+        Capacity {
+            ncpus: self.ncpus + rhs.ncpus,
+            mem: self.mem + rhs.mem,
+            ngpus: self.ngpus + rhs.ngpus,
+        }
+    }
+}
+
+impl AddAssign for Capacity {
+    fn add_assign(&mut self, rhs: Capacity) {
+        *self = *self + rhs;
+    }
+}
+
+/// Usage of a single vnode
+#[derive(Debug, Clone)]
+pub struct NodeUsage {
+    pub name: String,
+    pub state: VnodeState,
+    pub available: Capacity,
+    pub assigned: Capacity,
+    /// running jobs placed on this vnode
+    pub jobs: Vec<String>,
+    /// confirmed or running reservations holding this vnode
+    pub reservations: Vec<String>,
+}
+
+impl NodeUsage {
+    pub fn free(&self) -> Capacity {
+        self.available.saturating_sub(&self.assigned)
+    }
+    /// usable, with nothing assigned and not held by a reservation
+    pub fn is_idle(&self) -> bool {
+        !self.state.intersects(VnodeState::UNAVAILABLE)
+            && self.assigned.is_empty()
+            && self.jobs.is_empty()
+            && self.reservations.is_empty()
+    }
+    /// some but not all of the vnode's cpus are assigned
+    pub fn is_partial(&self) -> bool {
+        self.assigned.ncpus > 0 && self.assigned.ncpus < self.available.ncpus
+    }
+    pub fn json(&self) -> Value {
+        json!({
+            "state": self.state.to_string(),
+            "available": self.available.json(),
+            "assigned": self.assigned.json(),
+            "jobs": self.jobs,
+            "reservations": self.reservations,
+        })
+    }
+}
+
+/// Usage of a single queue
+#[derive(Debug, Clone, Default)]
+pub struct QueueUsage {
+    pub name: String,
+    /// number of jobs in the queue per job_state
+    pub jobs_by_state: BTreeMap<String, u64>,
+    /// resources allocated to the queue's running jobs
+    pub assigned: Capacity,
+    /// resources requested by the queue's queued jobs
+    pub queued_demand: Capacity,
+    pub jobs: Vec<String>,
+}
+
+impl QueueUsage {
+    pub fn json(&self) -> Value {
+        json!({
+            "jobs_by_state": self.jobs_by_state,
+            "assigned": self.assigned.json(),
+            "queued_demand": self.queued_demand.json(),
+            "jobs": self.jobs,
+        })
+    }
+}
+
+/// Point in time view of the whole cluster, with jobs linked to vnodes and queues
+pub struct ClusterSnapshot {
+    /// seconds since the epoch when the snapshot was taken
+    pub timestamp: u64,
+    pub server: Vec<Status>,
+    pub queues: Vec<Status>,
+    pub vnodes: Vec<Status>,
+    /// jobs and array subjobs, array parents are kept here but left out of usage
+    pub jobs: Vec<Status>,
+    pub reservations: Vec<Status>,
+    nodes: BTreeMap<String, NodeUsage>,
+    queue_usage: BTreeMap<String, QueueUsage>,
+    job_vnodes: BTreeMap<String, Vec<String>>,
+}
+
+// an array job's parent, e.g. 123[].server, its subjobs are listed and counted instead
+pub(crate) fn is_array_parent(job: &Status) -> bool {
+    job.name().contains("[]")
+}
+
+fn value(status: &Status, name: &str) -> Option<String> {
+    match status.attribs().get(name) {
+        Some(Attrl::Value(v)) => Some(v.val()),
+        _ => None,
+    }
+}
+
+impl ClusterSnapshot {
+    /// stat the server, queues, vnodes, jobs and subjobs, and reservations and link them together
+    pub fn capture(srv: &Server) -> Result<ClusterSnapshot, String> {
+        debug!("Capturing cluster snapshot");
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(ClusterSnapshot::new(
+            timestamp,
+            srv.stat_server(&None, None)?.resources,
+            srv.stat_que(&None, None)?.resources,
+            srv.stat_vnode(&None, None)?.resources,
+            srv.stat_jobs_with_subjobs(Attribs::new(), None)?.resources,
+            srv.stat_reservation(&None, None)?.resources,
+        ))
+    }
+
+    /// build a snapshot from previously collected stats
+    pub fn new(
+        timestamp: u64,
+        server: Vec<Status>,
+        queues: Vec<Status>,
+        vnodes: Vec<Status>,
+        jobs: Vec<Status>,
+        reservations: Vec<Status>,
+    ) -> ClusterSnapshot {
+        let assigned = node::RESOURCES_ASSIGNED;
+        let available = node::RESOURCES_AVAILABLE;
+        let mut nodes: BTreeMap<String, NodeUsage> = vnodes
+            .iter()
+            .map(|v| {
+                let usage = NodeUsage {
+                    name: v.name(),
+                    state: v.vnode_state().unwrap_or_default(),
+                    available: Capacity::from_amounts(&v.resource_amounts(available)),
+                    assigned: Capacity::from_amounts(&v.resource_amounts(assigned)),
+                    jobs: Vec::new(),
+                    reservations: Vec::new(),
+                };
+                (v.name(), usage)
+            })
+            .collect();
+        let mut queue_usage: BTreeMap<String, QueueUsage> = queues
+            .iter()
+            .map(|q| {
+                let usage = QueueUsage {
+                    name: q.name(),
+                    ..Default::default()
+                };
+                (q.name(), usage)
+            })
+            .collect();
+        let mut job_vnodes = BTreeMap::new();
+        let resource_list = job::RESOURCE_LIST;
+        for job in jobs.iter().filter(|j| !is_array_parent(j)) {
+            let state = value(job, job::JOB_STATE).unwrap_or_default();
+            let exec = match job.exec_vnode() {
+                Some(Ok(e)) => Some(e),
+                Some(Err(e)) => {
+                    warn!("Can't parse exec_vnode of job {}: {e}", job.name());
+                    None
+                }
+                None => None,
+            };
+            if let Some(exec) = &exec {
+                let vnodes: Vec<String> = exec.vnodes().iter().map(|v| v.to_string()).collect();
+                for v in &vnodes {
+                    if let Some(n) = nodes.get_mut(v) {
+                        n.jobs.push(job.name());
+                    }
+                }
+                job_vnodes.insert(job.name(), vnodes);
+            }
+            if let Some(queue) = value(job, job::QUEUE) {
+                let q = queue_usage
+                    .entry(queue.clone())
+                    .or_insert_with(|| QueueUsage {
+                        name: queue,
+                        ..Default::default()
+                    });
+                q.jobs.push(job.name());
+                *q.jobs_by_state.entry(state.clone()).or_default() += 1;
+                match (state.as_str(), &exec) {
+                    ("R", Some(exec)) => {
+                        q.assigned += Capacity {
+                            ncpus: exec.total("ncpus"),
+                            mem: exec.total("mem"),
+                            ngpus: exec.total("ngpus"),
+                        }
+                    }
+                    ("Q", _) => {
+                        q.queued_demand +=
+                            Capacity::from_amounts(&job.resource_amounts(resource_list))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for resv in &reservations {
+            let active = resv
+                .resv_state()
+                .map(|(s, _)| s.is_usable())
+                .unwrap_or(false);
+            if !active {
+                continue;
+            }
+            if let Some(Ok(exec)) =
+                value(resv, reservation::RESV_NODES).map(|n| n.parse::<ExecVnode>())
+            {
+                for v in exec.vnodes() {
+                    if let Some(n) = nodes.get_mut(v) {
+                        n.reservations.push(resv.name());
+                    }
+                }
+            }
+        }
+        ClusterSnapshot {
+            timestamp,
+            server,
+            queues,
+            vnodes,
+            jobs,
+            reservations,
+            nodes,
+            queue_usage,
+            job_vnodes,
+        }
+    }
+
+    pub fn nodes(&self) -> &BTreeMap<String, NodeUsage> {
+        &self.nodes
+    }
+    pub fn queue_usage(&self) -> &BTreeMap<String, QueueUsage> {
+        &self.queue_usage
+    }
+    pub fn node(&self, name: &str) -> Option<&NodeUsage> {
+        self.nodes.get(name)
+    }
+    pub fn job(&self, id: &str) -> Option<&Status> {
+        self.jobs.iter().find(|j| j.name() == id)
+    }
+    /// vnodes a running job is placed on
+    pub fn job_vnodes(&self, id: &str) -> &[String] {
+        self.job_vnodes.get(id).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+    /// jobs and array subjobs in a queue, without array parents
+    pub fn queue_jobs(&self, queue: &str) -> Vec<&Status> {
+        self.jobs
+            .iter()
+            .filter(|j| !is_array_parent(j) && value(j, job::QUEUE).as_deref() == Some(queue))
+            .collect()
+    }
+    pub fn idle_nodes(&self) -> Vec<&NodeUsage> {
+        self.nodes.values().filter(|n| n.is_idle()).collect()
+    }
+    /// capacity of vnodes which can accept jobs
+    pub fn available(&self) -> Capacity {
+        self.usable_nodes()
+            .map(|n| n.available)
+            .fold(Capacity::default(), Add::add)
+    }
+    /// capacity assigned to jobs across all vnodes
+    pub fn assigned(&self) -> Capacity {
+        self.nodes
+            .values()
+            .map(|n| n.assigned)
+            .fold(Capacity::default(), Add::add)
+    }
+    /// free capacity on vnodes held by confirmed or running reservations
+    pub fn reservation_blocked(&self) -> Capacity {
+        self.usable_nodes()
+            .filter(|n| !n.reservations.is_empty())
+            .map(|n| n.free())
+            .fold(Capacity::default(), Add::add)
+    }
+    /// Fraction of free ncpus which sit on partially used vnodes
+    ///
+    /// 0 means all free cpus are on whole free vnodes, 1 means none are.
+    pub fn fragmentation(&self) -> f64 {
+        let free: u64 = self.usable_nodes().map(|n| n.free().ncpus).sum();
+        if free == 0 {
+            return 0.0;
+        }
+        let partial: u64 = self
+            .usable_nodes()
+            .filter(|n| n.is_partial())
+            .map(|n| n.free().ncpus)
+            .sum();
+        partial as f64 / free as f64
+    }
+
+    fn usable_nodes(&self) -> impl Iterator<Item = &NodeUsage> {
+        self.nodes
+            .values()
+            .filter(|n| !n.state.intersects(VnodeState::UNAVAILABLE))
+    }
+
+    /// Serialise the snapshot, including the raw attributes of every object
+    pub fn json(&self) -> Value {
+        fn objects(list: &[Status]) -> Value {
+            let mut map = Map::new();
+            for s in list {
+                map.insert(s.name(), s.attribs().json());
+            }
+            Value::Object(map)
+        }
+        let nodes: Map<String, Value> = self
+            .nodes
+            .iter()
+            .map(|(k, v)| (k.clone(), v.json()))
+            .collect();
+        let queues: Map<String, Value> = self
+            .queue_usage
+            .iter()
+            .map(|(k, v)| (k.clone(), v.json()))
+            .collect();
+        let idle: BTreeSet<&str> = self.idle_nodes().iter().map(|n| n.name.as_str()).collect();
+        json!({
+            "timestamp": self.timestamp,
+            "summary": {
+                "available": self.available().json(),
+                "assigned": self.assigned().json(),
+                "reservation_blocked": self.reservation_blocked().json(),
+                "fragmentation": self.fragmentation(),
+                "idle_nodes": idle,
+            },
+            "nodes": nodes,
+            "queues": queues,
+            "objects": {
+                "server": objects(&self.server),
+                "queues": objects(&self.queues),
+                "vnodes": objects(&self.vnodes),
+                "jobs": objects(&self.jobs),
+                "reservations": objects(&self.reservations),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_text;
+
+    const JOBS: &str = "\
+Job Id: 123[].svr
+    job_state = B
+    queue = workq
+    Resource_List.ncpus = 2
+
+Job Id: 123[1].svr
+    job_state = R
+    queue = workq
+    Resource_List.ncpus = 2
+    exec_vnode = (n1:ncpus=2)
+
+Job Id: 123[2].svr
+    job_state = Q
+    queue = workq
+    Resource_List.ncpus = 2
+
+Job Id: 124.svr
+    job_state = Q
+    queue = workq
+    Resource_List.ncpus = 4
+";
+
+    const NODES: &str = "\
+n1
+     state = free
+     resources_available.ncpus = 4
+     resources_assigned.ncpus = 2
+";
+
+    #[test]
+    fn array_parents_are_not_counted() {
+        let jobs = parse_text(JOBS).unwrap().1.resources;
+        let vnodes = parse_text(NODES).unwrap().1.resources;
+        let snap = ClusterSnapshot::new(0, Vec::new(), Vec::new(), vnodes, jobs, Vec::new());
+        let q = &snap.queue_usage()["workq"];
+        assert_eq!(
+            q.jobs_by_state,
+            BTreeMap::from([("Q".to_string(), 2), ("R".to_string(), 1)])
+        );
+        assert_eq!(q.jobs, ["123[1].svr", "123[2].svr", "124.svr"]);
+        assert_eq!(q.queued_demand.ncpus, 6);
+        assert_eq!(q.assigned.ncpus, 2);
+        assert_eq!(snap.queue_jobs("workq").len(), 3);
+        assert_eq!(snap.node("n1").unwrap().jobs, ["123[1].svr"]);
+        assert_eq!(snap.job_vnodes("123[1].svr"), ["n1"]);
+        assert_eq!(snap.jobs.len(), 4);
+    }
+}
@@ -0,0 +1,576 @@
+//! Columnar output over stat results, with presets matching the stock PBS tools
+use crate::attributes::{job, node, queue, reservation};
+use crate::helpers::{format_duration, format_size, parse_amount, parse_duration, parse_size};
+use crate::types::{Attribs, ReservationState, StatResp, Status};
+use chrono::{DateTime, Local};
+use std::collections::BTreeSet;
+
+/// Horizontal alignment of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// How a column's raw value is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellFormat {
+    /// as returned by the server
+    Raw,
+    /// sizes in the largest unit that keeps them >= 1, e.g. 1.5gb
+    Size,
+    /// durations as HH:MM:SS
+    Duration,
+    /// durations as HH:MM, like qstat -a
+    ShortDuration,
+    /// epoch seconds as local time
+    Timestamp,
+    /// user@host as user
+    User,
+}
+
+impl CellFormat {
+    fn apply(&self, val: String) -> String {
+        match self {
+            CellFormat::Raw => val,
+            CellFormat::Size => parse_size(&val).map(format_size).unwrap_or(val),
+            CellFormat::Duration => parse_duration(&val).map(format_duration).unwrap_or(val),
+            CellFormat::ShortDuration => parse_duration(&val)
+                .map(|s| format!("{:02}:{:02}", s / 3600, s / 60 % 60))
+                .unwrap_or(val),
+            CellFormat::Timestamp => val
+                .parse()
+                .ok()
+                .and_then(|t| DateTime::from_timestamp(t, 0))
+                .map(|t| t.with_timezone(&Local).format("%a %b %d %H:%M").to_string())
+                .unwrap_or(val),
+            CellFormat::User => val.split('@').next().unwrap_or("").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Name,
+    Attr(String),
+    Computed(fn(&Status) -> String),
+}
+
+/// A table column
+#[derive(Debug, Clone)]
+pub struct Column {
+    header: String,
+    source: Source,
+    width: Option<usize>,
+    align: Align,
+    format: CellFormat,
+}
+
+impl Column {
+    /// column showing an attribute, `key` is name or name.resource
+    pub fn new(header: &str, key: &str) -> Column {
+        Column::with_source(header, Source::Attr(key.to_string()))
+    }
+    /// column showing the object's name
+    pub fn name(header: &str) -> Column {
+        Column::with_source(header, Source::Name)
+    }
+    /// column computed from the whole object
+    pub fn computed(header: &str, f: fn(&Status) -> String) -> Column {
+        Column::with_source(header, Source::Computed(f))
+    }
+    fn with_source(header: &str, source: Source) -> Column {
+        Column {
+            header: header.to_string(),
+            source,
+            width: None,
+            align: Align::Left,
+            format: CellFormat::Raw,
+        }
+    }
+    /// fixed width, longer values are truncated with a trailing '*' unless the table is wide
+    pub fn width(mut self, width: usize) -> Column {
+        self.width = Some(width);
+        self
+    }
+    pub fn align(mut self, align: Align) -> Column {
+        self.align = align;
+        self
+    }
+    pub fn format(mut self, format: CellFormat) -> Column {
+        self.format = format;
+        self
+    }
+
+    fn cell(&self, status: &Status) -> String {
+        let raw = match &self.source {
+            Source::Name => status.name(),
+            Source::Attr(key) => match status.attribs().value(key) {
+                Some(v) => v,
+                None => return String::new(),
+            },
+            Source::Computed(f) => f(status),
+        };
+        self.format.apply(raw)
+    }
+}
+
+/// Column sets matching the stock tools' views
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// qstat
+    Qstat,
+    /// qstat -a
+    QstatAlt,
+    /// qstat -a -n -1, with the exec_host at the end of the line
+    QstatNodes,
+    /// qstat -a -s, with the job comment on the following line
+    QstatComment,
+    /// qstat -Q
+    QstatQueue,
+    /// pbsnodes -aSj
+    PbsnodesJobs,
+    /// pbs_rstat
+    PbsRstat,
+}
+
+fn value(status: &Status, key: &str) -> Option<String> {
+    status.attribs().value(key)
+}
+
+// flattened key of a resource, e.g. Resource_List.ncpus
+fn key(attr: &str, resource: &str) -> String {
+    format!("{attr}.{resource}")
+}
+
+// count for one state out of a queue's state_count
+fn state_count(status: &Status, state: &str) -> String {
+    value(status, queue::STATE_COUNT)
+        .and_then(|c| {
+            Attribs::split_state_count(&c)
+                .into_iter()
+                .find(|(s, _)| s == state)
+                .map(|(_, n)| n)
+        })
+        .unwrap_or_default()
+}
+
+fn yes_no(status: &Status, key: &str) -> String {
+    match value(status, key) {
+        Some(v) if v.eq_ignore_ascii_case("true") => "yes".to_string(),
+        Some(_) => "no".to_string(),
+        None => String::new(),
+    }
+}
+
+// unique job ids in a vnode's jobs attribute, "1.srv/0, 1.srv/1, 2.srv/0"
+fn vnode_jobs(status: &Status) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    value(status, node::JOBS)
+        .unwrap_or_default()
+        .split(',')
+        .map(|j| j.trim().split('/').next().unwrap_or("").to_string())
+        .filter(|j| !j.is_empty() && seen.insert(j.clone()))
+        .collect()
+}
+
+// free/total of a vnode resource, sizes shown human readable
+fn free_total(status: &Status, resource: &str) -> String {
+    let avail = value(status, &key(node::RESOURCES_AVAILABLE, resource));
+    let assigned = value(status, &key(node::RESOURCES_ASSIGNED, resource));
+    let total = match avail.as_deref().and_then(parse_amount) {
+        Some(t) => t,
+        None => return "0/0".to_string(),
+    };
+    let free = total.saturating_sub(assigned.as_deref().and_then(parse_amount).unwrap_or(0));
+    if avail.is_some_and(|a| a.parse::<u64>().is_err()) {
+        format!("{}/{}", format_size(free), format_size(total))
+    } else {
+        format!("{free}/{total}")
+    }
+}
+
+fn qstat_alt() -> Vec<Column> {
+    vec![
+        Column::name("Job ID").width(15),
+        Column::new("Username", job::JOB_OWNER)
+            .width(8)
+            .format(CellFormat::User),
+        Column::new("Queue", job::QUEUE).width(8),
+        Column::new("Jobname", job::JOB_NAME).width(10),
+        Column::new("SessID", job::SESSION_ID)
+            .width(6)
+            .align(Align::Right),
+        Column::new("NDS", &key(job::RESOURCE_LIST, "nodect"))
+            .width(3)
+            .align(Align::Right),
+        Column::new("TSK", &key(job::RESOURCE_LIST, "ncpus"))
+            .width(3)
+            .align(Align::Right),
+        Column::new("Req'd Memory", &key(job::RESOURCE_LIST, "mem"))
+            .width(6)
+            .align(Align::Right)
+            .format(CellFormat::Size),
+        Column::new("Req'd Time", &key(job::RESOURCE_LIST, "walltime"))
+            .width(5)
+            .align(Align::Right)
+            .format(CellFormat::ShortDuration),
+        Column::new("S", job::JOB_STATE).width(1),
+        Column::new("Elap Time", &key(job::RESOURCES_USED, "walltime"))
+            .width(5)
+            .align(Align::Right)
+            .format(CellFormat::ShortDuration),
+    ]
+}
+
+/// Renders a StatResp as a table
+#[derive(Debug, Clone)]
+pub struct Table {
+    columns: Vec<Column>,
+    wide: bool,
+    header: bool,
+    comment: bool,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Table::new()
+    }
+}
+
+impl Table {
+    /// table with no columns, add them with column()
+    pub fn new() -> Table {
+        Table {
+            columns: Vec::new(),
+            wide: false,
+            header: true,
+            comment: false,
+        }
+    }
+    pub fn preset(preset: Preset) -> Table {
+        let mut table = Table::new();
+        table.columns = match preset {
+            Preset::Qstat => vec![
+                Column::name("Job id").width(17),
+                Column::new("Name", job::JOB_NAME).width(16),
+                Column::new("User", job::JOB_OWNER)
+                    .width(16)
+                    .format(CellFormat::User),
+                Column::new("Time Use", &key(job::RESOURCES_USED, "cput"))
+                    .width(8)
+                    .align(Align::Right),
+                Column::new("S", job::JOB_STATE).width(1),
+                Column::new("Queue", job::QUEUE).width(5),
+            ],
+            Preset::QstatAlt => qstat_alt(),
+            Preset::QstatNodes => {
+                let mut c = qstat_alt();
+                c.push(Column::new("Exec Host", job::EXEC_HOST));
+                c
+            }
+            Preset::QstatComment => {
+                table.comment = true;
+                qstat_alt()
+            }
+            Preset::QstatQueue => vec![
+                Column::name("Queue").width(15),
+                Column::new("Max", queue::MAX_RUNNING)
+                    .width(5)
+                    .align(Align::Right),
+                Column::new("Tot", queue::TOTAL_JOBS)
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Ena", |s| yes_no(s, queue::ENABLED)).width(3),
+                Column::computed("Str", |s| yes_no(s, queue::STARTED)).width(3),
+                Column::computed("Que", |s| state_count(s, "Queued"))
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Run", |s| state_count(s, "Running"))
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Hld", |s| state_count(s, "Held"))
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Wat", |s| state_count(s, "Waiting"))
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Trn", |s| state_count(s, "Transit"))
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Ext", |s| state_count(s, "Exiting"))
+                    .width(5)
+                    .align(Align::Right),
+                Column::computed("Type", |s| {
+                    value(s, queue::QUEUE_TYPE)
+                        .map(|t| t.chars().take(4).collect())
+                        .unwrap_or_default()
+                })
+                .width(4),
+            ],
+            Preset::PbsnodesJobs => vec![
+                Column::name("vnode").width(15),
+                Column::new("state", node::STATE).width(15),
+                Column::computed("njobs", |s| vnode_jobs(s).len().to_string())
+                    .width(6)
+                    .align(Align::Right),
+                Column::computed("mem f/t", |s| free_total(s, "mem"))
+                    .width(12)
+                    .align(Align::Right),
+                Column::computed("ncpus f/t", |s| free_total(s, "ncpus"))
+                    .width(7)
+                    .align(Align::Right),
+                Column::computed("ngpus f/t", |s| free_total(s, "ngpus"))
+                    .width(7)
+                    .align(Align::Right),
+                Column::computed("jobs", |s| vnode_jobs(s).join(",")),
+            ],
+            Preset::PbsRstat => vec![
+                Column::name("Resv ID").width(10),
+                Column::new("Queue", reservation::QUEUE).width(8),
+                Column::new("User", reservation::RESERVE_OWNER)
+                    .width(8)
+                    .format(CellFormat::User),
+                Column::computed("State", |s| {
+                    s.resv_state()
+                        .map(|(st, _)| st.abbrev().to_string())
+                        .unwrap_or_else(|| {
+                            value(s, reservation::RESERVE_STATE)
+                                .and_then(|v| v.parse::<ReservationState>().ok())
+                                .map(|st| st.abbrev().to_string())
+                                .unwrap_or_default()
+                        })
+                })
+                .width(5),
+                Column::computed("Start / Duration / End", |s| {
+                    let time = |k| CellFormat::Timestamp.apply(value(s, k).unwrap_or_default());
+                    format!(
+                        "{} / {} / {}",
+                        time(reservation::RESERVE_START),
+                        value(s, reservation::RESERVE_DURATION).unwrap_or_default(),
+                        time(reservation::RESERVE_END)
+                    )
+                }),
+            ],
+        };
+        table
+    }
+    pub fn column(mut self, column: Column) -> Table {
+        self.columns.push(column);
+        self
+    }
+    /// don't truncate, widening columns to fit their values instead
+    pub fn wide(mut self, wide: bool) -> Table {
+        self.wide = wide;
+        self
+    }
+    /// print the header and separator lines, defaults to true
+    pub fn header(mut self, header: bool) -> Table {
+        self.header = header;
+        self
+    }
+    /// print each object's comment on the line below it, like qstat -s
+    pub fn comment(mut self, comment: bool) -> Table {
+        self.comment = comment;
+        self
+    }
+
+    pub fn render(&self, resp: &StatResp) -> String {
+        let rows: Vec<Vec<String>> = resp
+            .resources
+            .iter()
+            .map(|s| self.columns.iter().map(|c| c.cell(s)).collect())
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let longest = rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0);
+                // headers are never truncated, so fixed widths grow to fit them
+                let base = c.header.chars().count().max(c.width.unwrap_or(0));
+                match c.width {
+                    Some(_) if !self.wide => base,
+                    _ => base.max(longest),
+                }
+            })
+            .collect();
+        let line = |cells: &[String], truncate: bool| -> String {
+            let out: Vec<String> = cells
+                .iter()
+                .zip(&self.columns)
+                .zip(&widths)
+                .map(|((v, c), w)| {
+                    let mut v = v.clone();
+                    if truncate && v.chars().count() > *w && *w > 0 {
+                        v = v.chars().take(w - 1).collect();
+                        v.push('*');
+                    }
+                    match c.align {
+                        Align::Left => format!("{v:<w$}"),
+                        Align::Right => format!("{v:>w$}"),
+                    }
+                })
+                .collect();
+            out.join(" ").trim_end().to_string()
+        };
+        let mut out = String::new();
+        if self.header {
+            let headers: Vec<String> = self.columns.iter().map(|c| c.header.clone()).collect();
+            out.push_str(&line(&headers, false));
+            out.push('\n');
+            let dashes: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+            out.push_str(&dashes.join(" "));
+            out.push('\n');
+        }
+        for (row, status) in rows.iter().zip(&resp.resources) {
+            out.push_str(&line(row, !self.wide));
+            out.push('\n');
+            if self.comment {
+                if let Some(c) = value(status, job::COMMENT) {
+                    out.push_str(&format!("   {c}\n"));
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_text;
+
+    const JOBS: &str = "\
+Job Id: 1234.pbsserver
+    Job_Name = a_rather_long_job_name
+    Job_Owner = user1@login01
+    job_state = R
+    queue = workq
+    Resource_List.mem = 4096mb
+    Resource_List.walltime = 01:30:00
+    comment = Job run at Mon Jun 22 at 10:12 on (node01:ncpus=1)
+
+Job Id: 99.pbsserver
+    Job_Name = short
+    Job_Owner = user22@login01
+    job_state = Q
+    queue = long
+    Resource_List.mem = 512kb
+";
+
+    fn jobs() -> StatResp {
+        parse_text(JOBS).unwrap().1
+    }
+
+    fn table() -> Table {
+        Table::new()
+            .column(Column::name("Job ID").width(8))
+            .column(Column::new("Name", job::JOB_NAME).width(6))
+            .column(
+                Column::new("User", job::JOB_OWNER)
+                    .width(4)
+                    .format(CellFormat::User),
+            )
+            .column(
+                Column::new("Mem", &key(job::RESOURCE_LIST, "mem"))
+                    .width(5)
+                    .align(Align::Right)
+                    .format(CellFormat::Size),
+            )
+            .column(Column::new("S", job::JOB_STATE))
+    }
+
+    #[test]
+    fn widths_alignment_and_truncation() {
+        assert_eq!(
+            table().render(&jobs()),
+            "\
+Job ID   Name   User   Mem S
+-------- ------ ---- ----- -
+1234.pb* a_rat* use*   4gb R
+99.pbss* short  use* 512kb Q
+"
+        );
+    }
+
+    #[test]
+    fn wide_grows_columns() {
+        assert_eq!(
+            table().wide(true).header(false).render(&jobs()),
+            "\
+1234.pbsserver a_rather_long_job_name user1    4gb R
+99.pbsserver   short                  user22 512kb Q
+"
+        );
+    }
+
+    #[test]
+    fn comments_and_missing_values() {
+        let out = Table::new()
+            .column(Column::name("Job"))
+            .column(
+                Column::new("Walltime", &key(job::RESOURCE_LIST, "walltime"))
+                    .format(CellFormat::ShortDuration),
+            )
+            .column(Column::new("Host", job::EXEC_HOST))
+            .comment(true)
+            .header(false)
+            .render(&jobs());
+        assert_eq!(
+            out,
+            "\
+1234.pbsserver 01:30
+   Job run at Mon Jun 22 at 10:12 on (node01:ncpus=1)
+99.pbsserver
+"
+        );
+    }
+
+    #[test]
+    fn queue_preset() {
+        let queues = parse_text(
+            "\
+Queue: workq
+    queue_type = Execution
+    total_jobs = 3
+    state_count = Transit:0 Queued:2 Held:0 Waiting:0 Running:1 Exiting:0 Begun:0 
+    max_running = 100
+    enabled = True
+    started = False
+",
+        )
+        .unwrap()
+        .1;
+        assert_eq!(
+            Table::preset(Preset::QstatQueue).render(&queues),
+            "\
+Queue             Max   Tot Ena Str   Que   Run   Hld   Wat   Trn   Ext Type
+--------------- ----- ----- --- --- ----- ----- ----- ----- ----- ----- ----
+workq             100     3 yes no      2     1     0     0     0     0 Exec
+"
+        );
+    }
+
+    #[test]
+    fn vnode_jobs_and_free() {
+        let nodes = parse_text(
+            "\
+node01
+     state = job-busy
+     jobs = 1234.pbsserver/0, 1234.pbsserver/1, 1235.pbsserver/2
+     resources_available.mem = 4gb
+     resources_available.ncpus = 4
+     resources_assigned.mem = 1gb
+     resources_assigned.ncpus = 3
+",
+        )
+        .unwrap()
+        .1;
+        let n = &nodes.resources[0];
+        assert_eq!(vnode_jobs(n), ["1234.pbsserver", "1235.pbsserver"]);
+        assert_eq!(free_total(n, "ncpus"), "1/4");
+        assert_eq!(free_total(n, "mem"), "3gb/4gb");
+        assert_eq!(free_total(n, "ngpus"), "0/0");
+    }
+}
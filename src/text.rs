@@ -0,0 +1,322 @@
+//! Reading and writing the text formats of `qstat -f`, `pbs_rstat -f` and `pbsnodes -av`
+use crate::types::{Attribs, Attrl, Op, Resource, StatResp, Status};
+use log::trace;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// qstat wraps long values at this width
+const LINE_WIDTH: usize = 80;
+
+// object header prefixes and the attribute indent used for each kind of object
+fn layout(kind: Resource) -> (Option<&'static str>, &'static str) {
+    match kind {
+        Resource::Job => (Some("Job Id: "), "    "),
+        Resource::Que => (Some("Queue: "), "    "),
+        Resource::Server => (Some("Server: "), "    "),
+        Resource::Scheduler => (Some("Sched: "), "    "),
+        Resource::Reservation => (Some("Resv ID: "), ""),
+        Resource::Resource => (Some("Resource: "), "    "),
+        Resource::Vnode | Resource::Hostname => (None, "     "),
+    }
+}
+
+const HEADERS: [(&str, Resource); 6] = [
+    ("Job Id: ", Resource::Job),
+    ("Queue: ", Resource::Que),
+    ("Server: ", Resource::Server),
+    ("Sched: ", Resource::Scheduler),
+    ("Resv ID: ", Resource::Reservation),
+    ("Resource: ", Resource::Resource),
+];
+
+fn to_status(name: String, attribs: Vec<(String, String)>) -> Status {
+    let mut a = Attribs::new();
+    for (key, val) in attribs {
+        match key.split_once('.') {
+            Some((name, resource)) => {
+                let mut map = BTreeMap::new();
+                map.insert(resource.to_string(), Op::Default(val));
+                a.add(name.to_string(), Attrl::Resource(map));
+            }
+            None => a.add(key, Attrl::Value(Op::Default(val))),
+        }
+    }
+    Status::new(name, None, a)
+}
+
+/// Parse `qstat -f`, `qstat -Qf`, `qstat -Bf`, `pbs_rstat -f` or `pbsnodes -av` output
+///
+/// Returns the parsed objects along with the kind of object found in the text.
+pub fn parse_text(text: &str) -> Result<(Resource, StatResp), String> {
+    let mut kind = None;
+    let mut objects = Vec::new();
+    let mut current: Option<(String, Vec<(String, String)>)> = None;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        // wrapped continuation of the previous value, which can be just the
+        // trailing space of a state_count
+        if let Some(cont) = line.strip_prefix('\t') {
+            match current.as_mut().and_then(|(_, a)| a.last_mut()) {
+                Some((_, val)) => val.push_str(cont),
+                None => return Err(format!("line {}: continuation without attribute", n + 1)),
+            }
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let header = HEADERS
+            .iter()
+            .find_map(|(p, k)| line.strip_prefix(p).map(|name| (name, *k)));
+        let header = match header {
+            Some(h) => Some(h),
+            // pbsnodes puts the bare vnode name on an unindented line
+            None if !line.starts_with(' ') && !line.contains(" = ") => {
+                Some((line, Resource::Vnode))
+            }
+            None => None,
+        };
+        if let Some((name, k)) = header {
+            if kind.is_some_and(|x| x != k) {
+                return Err(format!("line {}: mixed object types", n + 1));
+            }
+            kind = Some(k);
+            if let Some((name, attribs)) = current.take() {
+                objects.push(to_status(name, attribs));
+            }
+            current = Some((name.trim().to_string(), Vec::new()));
+            continue;
+        }
+        let (key, val) = line
+            .trim_start()
+            .split_once(" = ")
+            .ok_or_else(|| format!("line {}: expected 'name = value': {line}", n + 1))?;
+        match current.as_mut() {
+            Some((_, attribs)) => attribs.push((key.to_string(), val.to_string())),
+            None => return Err(format!("line {}: attribute before object header", n + 1)),
+        }
+    }
+    if let Some((name, attribs)) = current.take() {
+        objects.push(to_status(name, attribs));
+    }
+    trace!("Parsed {} objects", objects.len());
+    Ok((
+        kind.unwrap_or(Resource::Job),
+        StatResp { resources: objects },
+    ))
+}
+
+// write value the way prt_attr in qstat does
+//
+// Values are split on commas, a piece that doesn't fit starts a new line and
+// one that doesn't fit there either is broken wherever the line fills up.
+// Like qstat, commas aren't counted and a broken line restarts at column 8.
+fn write_wrapped(f: &mut fmt::Formatter<'_>, start: usize, val: &str) -> fmt::Result {
+    let mut len = start;
+    for (i, piece) in val.split(',').enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        if len + piece.len() < LINE_WIDTH {
+            write!(f, "{piece}")?;
+            len += piece.len();
+            continue;
+        }
+        if i > 0 {
+            write!(f, "\n\t")?;
+            len = 9;
+        }
+        for c in piece.chars() {
+            write!(f, "{c}")?;
+            len += 1;
+            if len > LINE_WIDTH {
+                write!(f, "\n\t")?;
+                len = 8;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a stat in the format of the matching stock tool, see StatResp::text
+pub struct Text<'a> {
+    resp: &'a StatResp,
+    kind: Resource,
+}
+
+impl fmt::Display for Text<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (header, indent) = layout(self.kind);
+        for status in &self.resp.resources {
+            writeln!(f, "{}{}", header.unwrap_or(""), status.name())?;
+            for (key, val) in status.attribs().flat_ordered() {
+                let prefix = format!("{indent}{key} = ");
+                write!(f, "{prefix}")?;
+                // pbsnodes prints values on one line
+                if matches!(self.kind, Resource::Vnode | Resource::Hostname) {
+                    write!(f, "{val}")?;
+                } else {
+                    write_wrapped(f, prefix.len(), &val)?;
+                }
+                writeln!(f)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl StatResp {
+    /// display in the text format of the stock tool for `kind`, e.g. qstat -f for jobs
+    pub fn text(&self, kind: Resource) -> Text<'_> {
+        Text { resp: self, kind }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QSTAT_F: &str = "\
+Job Id: 1234.pbsserver
+    Job_Name = STDIN
+    Job_Owner = user1@login01
+    resources_used.cpupercent = 0
+    resources_used.cput = 00:00:00
+    resources_used.mem = 0kb
+    resources_used.ncpus = 96
+    resources_used.vmem = 0kb
+    resources_used.walltime = 00:00:05
+    job_state = R
+    queue = workq
+    server = pbsserver
+    Checkpoint = u
+    ctime = Mon Jun 22 10:12:14 2020
+    Error_Path = login01:/home/user1/STDIN.e1234
+    exec_host = node01/0*32+node02/0*32+node03/0*32
+    exec_vnode = (node01:ncpus=32:mem=125829120kb)+(node02:ncpus=32:mem=125829120
+	kb)+(node03:ncpus=32:mem=125829120kb)
+    Hold_Types = n
+    Join_Path = n
+    Keep_Files = n
+    Mail_Points = a
+    mtime = Mon Jun 22 10:12:15 2020
+    Output_Path = login01:/home/user1/STDIN.o1234
+    Priority = 0
+    qtime = Mon Jun 22 10:12:14 2020
+    Rerunable = False
+    Resource_List.mem = 360gb
+    Resource_List.ncpus = 96
+    Resource_List.nodect = 3
+    Resource_List.place = scatter
+    Resource_List.select = 3:ncpus=32:mem=120gb
+    stime = Mon Jun 22 10:12:15 2020
+    session_id = 12345
+    jobdir = /home/user1
+    substate = 42
+    Variable_List = PBS_O_HOME=/home/user1,PBS_O_LANG=en_US.UTF-8,
+	PBS_O_LOGNAME=user1,
+	PBS_O_PATH=/usr/local/bin:/usr/bin:/usr/local/sbin:/usr/sbin:/opt/pbs/bi
+	n,PBS_O_MAIL=/var/spool/mail/user1,PBS_O_SHELL=/bin/bash,
+	PBS_O_WORKDIR=/home/user1,PBS_O_SYSTEM=Linux,PBS_O_QUEUE=workq,
+	PBS_O_HOST=login01
+    comment = Job run at Mon Jun 22 at 10:12 on (node01:ncpus=32:mem=125829120kb)
+	+(node02:ncpus=32:mem=125829120kb)+(node03:ncpus=32:mem=125829120kb)
+    etime = Mon Jun 22 10:12:14 2020
+    run_count = 1
+    Submit_arguments = -- /bin/sleep 1000
+    project = _pbs_project_default
+
+Job Id: 1235.pbsserver
+    Job_Name = long
+    job_state = Q
+    queue = workq
+    comment = Not Running: Insufficient amount of resource: ncpus (R: 64 A: 48 T:
+	 128)
+
+";
+
+    const QSTAT_QF: &str = "\
+Queue: workq
+    queue_type = Execution
+    total_jobs = 2
+    state_count = Transit:0 Queued:1 Held:0 Waiting:0 Running:1 Exiting:0 Begun:0
+	 
+    acl_user_enable = True
+    acl_users = alice@login01.cluster,bob@login01.cluster,carol@login01.cluster,
+	dave@login01.cluster
+    resources_assigned.mem = 377487360kb
+    resources_assigned.ncpus = 96
+    resources_assigned.nodect = 3
+    enabled = True
+    started = True
+
+";
+
+    const PBSNODES_AV: &str = "\
+node01
+     Mom = node01.cluster
+     Port = 15002
+     pbs_version = 20.0.1
+     ntype = PBS
+     state = job-busy
+     pcpus = 32
+     jobs = 1234.pbsserver/0, 1234.pbsserver/1, 1234.pbsserver/2, 1234.pbsserver/3, 1234.pbsserver/4
+     resources_available.arch = linux
+     resources_available.host = node01
+     resources_available.mem = 131603604kb
+     resources_available.ncpus = 32
+     resources_available.vnode = node01
+     resources_assigned.mem = 125829120kb
+     resources_assigned.ncpus = 32
+     resv_enable = True
+     sharing = default_shared
+     last_state_change_time = Mon Jun 22 10:12:15 2020
+
+node02
+     Mom = node02.cluster
+     state = offline
+     comment = disk errors on /dev/sda, replace before returning to service
+     resources_available.ncpus = 32
+
+";
+
+    fn round_trip(text: &str, kind: Resource) {
+        let (k, resp) = parse_text(text).unwrap();
+        assert_eq!(k, kind);
+        assert_eq!(resp.text(kind).to_string(), text);
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(QSTAT_F, Resource::Job);
+        round_trip(QSTAT_QF, Resource::Que);
+        round_trip(PBSNODES_AV, Resource::Vnode);
+    }
+
+    #[test]
+    fn wrapped_values_are_joined() {
+        let resp = parse_text(QSTAT_F).unwrap().1;
+        let job = resp.resources[0].attribs().flat();
+        assert_eq!(
+            job["exec_vnode"],
+            "(node01:ncpus=32:mem=125829120kb)+(node02:ncpus=32:mem=125829120kb)+(node03:ncpus=32:mem=125829120kb)"
+        );
+        assert!(job["Variable_List"].contains(
+            ",PBS_O_PATH=/usr/local/bin:/usr/bin:/usr/local/sbin:/usr/sbin:/opt/pbs/bin,"
+        ));
+        let job = resp.resources[1].attribs().flat();
+        assert_eq!(
+            job["comment"],
+            "Not Running: Insufficient amount of resource: ncpus (R: 64 A: 48 T: 128)"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_text("\tcontinued\n").is_err());
+        assert!(parse_text("    queue = workq\n").is_err());
+        assert!(parse_text("Job Id: 1.svr\n    no value here\n").is_err());
+        assert!(parse_text("Job Id: 1.svr\n\nQueue: workq\n").is_err());
+    }
+}
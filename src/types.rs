@@ -1,15 +1,40 @@
 mod attribs;
 mod attrl;
+mod change;
+mod comment;
+mod decode;
 mod op;
+mod placement;
+mod reservation;
 mod resource;
+mod resourcedef;
+mod rrule;
 mod server;
 mod statresp;
 mod status;
+mod validate;
+mod varlist;
+mod vnode;
 
 pub use attribs::Attribs;
 pub use attrl::Attrl;
+pub use change::AttrChange;
+pub(crate) use change::{diff_resources, diff_values, same_list, same_value};
+pub use comment::{NotRunningReason, Scope};
+pub(crate) use decode::split_escaped;
+pub use decode::{decode, has_decoder, Decoded};
 pub use op::Op;
+pub use placement::{ExecHost, ExecVnode, HostSlot, VnodeAlloc};
+pub(crate) use reservation::validate_resv_mod;
+pub use reservation::{
+    ReservationSpec, ReservationState, ResvModResponse, ResvModStatus, ResvModification,
+};
 pub use resource::Resource;
+pub use resourcedef::{ResourceDef, ResourceFlags, ResourceType};
+pub use rrule::{Frequency, Rrule};
 pub use server::Server;
 pub use statresp::StatResp;
 pub use status::Status;
+pub use validate::{builtin_resources, Diagnostic, DiagnosticKind, Operation};
+pub use varlist::VariableList;
+pub use vnode::{VnodeState, VnodeStateChange};
@@ -1,5 +1,6 @@
+use crate::attributes::{job, ValueType};
 use crate::helpers;
-use crate::types::{Attrl, Op};
+use crate::types::{decode, Attrl, Decoded, Op};
 use linked_list_c::ConstList;
 use log::{debug, error, trace};
 use pbs_sys::attrl;
@@ -13,12 +14,15 @@ use std::fmt;
 #[derive(Debug)]
 pub struct Attribs {
     attribs: BTreeMap<String, Attrl>,
+    // flattened keys (name or name.resource) in the order they were added
+    order: Vec<String>,
 }
 
 impl Attribs {
     pub(crate) fn new() -> Attribs {
         Attribs {
             attribs: BTreeMap::new(),
+            order: Vec::new(),
         }
     }
     pub(crate) fn attribs(&self) -> &BTreeMap<String, Attrl> {
@@ -26,6 +30,15 @@ impl Attribs {
     }
 
     pub(crate) fn add(&mut self, name: String, value: Attrl) {
+        let keys = match &value {
+            Attrl::Value(_) => vec![name.clone()],
+            Attrl::Resource(map) => map.keys().map(|r| format!("{name}.{r}")).collect(),
+        };
+        for k in keys {
+            if !self.order.contains(&k) {
+                self.order.push(k);
+            }
+        }
         match self.attribs.get_mut(&name) {
             Some(Attrl::Value(old)) => {
                 if let Attrl::Value(_) = value {
@@ -52,6 +65,32 @@ impl Attribs {
         };
     }
 
+    /// Attributes to set from "name[.resource]=value" strings, e.g. for submit_job
+    ///
+    /// Values are Op::Set, unlike From<&Vec<String>> which builds filters, and
+    /// any other comparison is an error.
+    pub fn from_assignments(args: &[String]) -> Result<Attribs, String> {
+        let mut attribs = Attribs::new();
+        let re = Regex::new(r"^(\w+)(?:\.(\w+))?$").unwrap();
+        for a in args {
+            let (key, val) = a
+                .split_once('=')
+                .ok_or_else(|| format!("expected name[.resource]=value, got {a:?}"))?;
+            let caps = re
+                .captures(key)
+                .ok_or_else(|| format!("invalid attribute name {key:?}"))?;
+            let op = Op::Set(val.to_string());
+            match caps.get(2) {
+                Some(r) => {
+                    let map = BTreeMap::from([(r.as_str().to_string(), op)]);
+                    attribs.add(caps[1].to_string(), Attrl::Resource(map))
+                }
+                None => attribs.add(caps[1].to_string(), Attrl::Value(op)),
+            }
+        }
+        Ok(attribs)
+    }
+
     pub fn get(&self, key: &str) -> Option<&Attrl> {
         self.attribs.get(key)
     }
@@ -69,37 +108,90 @@ impl Attribs {
         }
         true
     }
+    // split "Transit:0 Queued:1 Held:0 ..." into (state, count) pairs
+    // runs of spaces (and the trailing space PBS adds) are skipped
+    pub(crate) fn split_state_count(val: &str) -> Vec<(String, String)> {
+        val.split_whitespace()
+            .filter_map(|s| s.split_once(':'))
+            .map(|(state, num)| (state.to_string(), num.to_string()))
+            .collect()
+    }
+
+    /// attribute values keyed by name, or name.resource for resource attributes
+    pub fn flat(&self) -> BTreeMap<String, String> {
+        let mut out = BTreeMap::new();
+        for (name, val) in &self.attribs {
+            match val {
+                Attrl::Value(x) => {
+                    out.insert(name.clone(), x.val());
+                }
+                Attrl::Resource(map) => {
+                    for (r, v) in map {
+                        out.insert(format!("{name}.{r}"), v.val());
+                    }
+                }
+            }
+        }
+        out
+    }
+    /// value of `key`, name or name.resource, as flat() would give it without flattening
+    pub fn value(&self, key: &str) -> Option<String> {
+        match (self.attribs.get(key), key.split_once('.')) {
+            (Some(Attrl::Value(x)), _) => Some(x.val()),
+            (_, Some((name, r))) => match self.attribs.get(name)? {
+                Attrl::Resource(map) => map.get(r).map(Op::val),
+                Attrl::Value(_) => None,
+            },
+            _ => None,
+        }
+    }
+    /// flattened attributes in the order they were received from the server
+    pub fn flat_ordered(&self) -> Vec<(String, String)> {
+        let flat = self.flat();
+        self.order
+            .iter()
+            .filter_map(|k| flat.get(k).map(|v| (k.clone(), v.clone())))
+            .collect()
+    }
     pub fn json(&self) -> Value {
+        self.json_with(|_, v| helpers::json_val(v))
+    }
+    /// like json(), but resource values are converted by the type of their definition
+    ///
+    /// Sizes become bytes and durations seconds, resources without a type are guessed as in json().
+    /// Types come from builtin_resources() or Server::resource_types().
+    pub fn json_typed(&self, types: &BTreeMap<String, ValueType>) -> Value {
+        self.json_with(|r, v| {
+            types
+                .get(r)
+                .and_then(|t| t.json(&v))
+                .unwrap_or_else(|| helpers::json_val(v))
+        })
+    }
+    fn json_with<F: Fn(&str, String) -> Value>(&self, resource: F) -> Value {
         let mut attribs = HashMap::new();
         for (name, val) in &self.attribs {
-            //TODO convert que "state_count":"Transit:0 Queued:1 Held:0 Waiting:0 Running:0 Exiting:0 Begun:0 "
-            // to state_count.Transit:0, state_count.Queued: 1, etc
             match val {
-                Attrl::Value(x) => {
-                    if name == "state_count" {
-                        let temp = x.val();
-                        let split = temp.split(' ');
-                        for s in split {
-                            if s.is_empty() {
-                                break;
-                            }
-                            let mut v = s.split(':');
-                            let state = v.next().unwrap();
-                            let num = v.next().unwrap();
-                            attribs.insert(
-                                format!("{}.{}", name, state),
-                                helpers::json_val(num.to_string()),
-                            );
+                Attrl::Value(x) => match decode(name, &x.val()) {
+                    // counters are flattened to name.state
+                    Some(Decoded::Counts(c)) => {
+                        for (state, num) in c {
+                            attribs.insert(format!("{}.{}", name, state), Value::from(num));
                         }
-                    } else if name == "comment" {
+                    }
+                    Some(d) => {
+                        attribs.insert(name.to_string(), d.json());
+                    }
+                    None if name == job::COMMENT => {
                         attribs.insert(name.to_string(), Value::String(x.val()));
-                    } else {
+                    }
+                    None => {
                         attribs.insert(name.to_string(), helpers::json_val(x.val()));
                     }
-                }
+                },
                 Attrl::Resource(map) => {
                     for (r, v) in map {
-                        attribs.insert(format!("{}.{}", name, r), helpers::json_val(v.val()));
+                        attribs.insert(format!("{}.{}", name, r), resource(r, v.val()));
                     }
                 }
             }
@@ -0,0 +1,194 @@
+use crate::helpers::{parse_bool, parse_duration, parse_size};
+use crate::types::Op;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Change to one attribute of a PBS object, named name or name.resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrChange {
+    Set(String, String),
+    Unset(String),
+}
+
+impl AttrChange {
+    pub fn name(&self) -> &str {
+        match self {
+            AttrChange::Set(n, _) | AttrChange::Unset(n) => n,
+        }
+    }
+    /// pbs_manager entry for this change
+    pub(crate) fn entry(&self) -> (String, Option<String>, Op) {
+        let (name, op) = match self {
+            AttrChange::Set(n, v) => (n, Op::Set(v.clone())),
+            AttrChange::Unset(n) => (n, Op::Unset(String::new())),
+        };
+        match name.split_once('.') {
+            Some((n, r)) => (n.to_string(), Some(r.to_string()), op),
+            None => (name.clone(), None, op),
+        }
+    }
+}
+
+impl fmt::Display for AttrChange {
+    /// in qmgr syntax, e.g. "resources_max.ncpus = 64"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrChange::Set(n, v) => write!(f, "{n} = {v}"),
+            AttrChange::Unset(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+// whether two values are the same once the server has normalised them, e.g. 1gb and 1024mb
+pub(crate) fn same_value(a: &str, b: &str) -> bool {
+    let (a, b) = (a.trim(), b.trim());
+    if a == b {
+        return true;
+    }
+    let same = |p: fn(&str) -> Option<u64>| p(a).is_some_and(|x| p(b) == Some(x));
+    same(parse_size)
+        || same(parse_duration)
+        || parse_bool(a).is_some_and(|x| parse_bool(b) == Some(x))
+}
+
+// whether two comma separated lists hold the same entries in any order
+pub(crate) fn same_list(a: &str, b: &str) -> bool {
+    let set = |v: &str| {
+        let mut l: Vec<String> = v
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(str::to_string)
+            .collect();
+        l.sort();
+        l
+    };
+    set(a) == set(b)
+}
+
+// Set changes for `desired` values that differ from `live`, keyed by name or name.resource
+pub(crate) fn diff_values(
+    desired: &BTreeMap<String, String>,
+    live: &BTreeMap<String, String>,
+    same: fn(&str, &str) -> bool,
+) -> Vec<AttrChange> {
+    desired
+        .iter()
+        .filter(|(k, v)| !live.get(*k).is_some_and(|l| same(v, l)))
+        .map(|(k, v)| AttrChange::Set(k.clone(), v.clone()))
+        .collect()
+}
+
+// changes making the resources of `name` in `live` exactly `desired`
+pub(crate) fn diff_resources(
+    name: &str,
+    desired: &BTreeMap<String, String>,
+    live: &BTreeMap<String, String>,
+) -> Vec<AttrChange> {
+    let prefix = format!("{name}.");
+    let live: BTreeMap<String, String> = live
+        .iter()
+        .filter_map(|(k, v)| Some((k.strip_prefix(&prefix)?.to_string(), v.clone())))
+        .collect();
+    let mut out: Vec<AttrChange> = diff_values(desired, &live, same_value)
+        .into_iter()
+        .map(|c| match c {
+            AttrChange::Set(r, v) => AttrChange::Set(format!("{prefix}{r}"), v),
+            c => c,
+        })
+        .collect();
+    out.extend(
+        live.keys()
+            .filter(|r| !desired.contains_key(*r))
+            .map(|r| AttrChange::Unset(format!("{prefix}{r}"))),
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(v: &[(&str, &str)]) -> BTreeMap<String, String> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn same_values() {
+        assert!(same_value("1gb", "1024mb"));
+        assert!(same_value("1048576kb", "1gb"));
+        assert!(same_value("01:00:00", "3600"));
+        assert!(same_value("True", "true"));
+        assert!(same_value(" 64", "64"));
+        assert!(!same_value("1gb", "1000mb"));
+        assert!(!same_value("True", "False"));
+        assert!(!same_value("workq", "longq"));
+    }
+
+    #[test]
+    fn same_lists() {
+        assert!(same_list("+alice,-bob", "-bob, +alice"));
+        assert!(same_list("alice,,bob", "bob,alice"));
+        assert!(!same_list("alice,bob", "alice"));
+        assert!(!same_list("+alice", "-alice"));
+    }
+
+    #[test]
+    fn diffs() {
+        let live = map(&[
+            ("enabled", "True"),
+            ("priority", "10"),
+            ("resources_max.mem", "1024mb"),
+            ("resources_max.ncpus", "64"),
+            ("resources_max.walltime", "24:00:00"),
+            ("resources_min.ncpus", "1"),
+        ]);
+        let desired = map(&[("enabled", "true"), ("priority", "20"), ("comment", "x")]);
+        assert_eq!(
+            diff_values(&desired, &live, same_value),
+            [
+                AttrChange::Set("comment".to_string(), "x".to_string()),
+                AttrChange::Set("priority".to_string(), "20".to_string()),
+            ]
+        );
+        // walltime isn't wanted any more, resources_min is another attribute
+        let desired = map(&[("mem", "1gb"), ("ncpus", "128")]);
+        assert_eq!(
+            diff_resources("resources_max", &desired, &live),
+            [
+                AttrChange::Set("resources_max.ncpus".to_string(), "128".to_string()),
+                AttrChange::Unset("resources_max.walltime".to_string()),
+            ]
+        );
+        assert_eq!(
+            diff_resources("resources_default", &desired, &live),
+            [
+                AttrChange::Set("resources_default.mem".to_string(), "1gb".to_string()),
+                AttrChange::Set("resources_default.ncpus".to_string(), "128".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn entries() {
+        let set = AttrChange::Set("resources_max.ncpus".to_string(), "64".to_string());
+        assert_eq!(set.name(), "resources_max.ncpus");
+        assert_eq!(set.to_string(), "resources_max.ncpus = 64");
+        assert_eq!(
+            set.entry(),
+            (
+                "resources_max".to_string(),
+                Some("ncpus".to_string()),
+                Op::Set("64".to_string())
+            )
+        );
+        let unset = AttrChange::Unset("max_running".to_string());
+        assert_eq!(unset.to_string(), "max_running");
+        assert_eq!(
+            unset.entry(),
+            ("max_running".to_string(), None, Op::Unset(String::new()))
+        );
+    }
+}
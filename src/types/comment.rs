@@ -0,0 +1,512 @@
+use crate::attributes::job;
+use crate::types::{Attrl, Status};
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Where a resource shortage or limit applies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    Node,
+    Queue(Option<String>),
+    Server,
+}
+
+/// Why the scheduler didn't run a job, or why a vnode is unavailable, parsed from a comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotRunningReason {
+    /// "Insufficient amount of [queue |server ]resource: ngpus (R: 8 A: 4 T: 4)"
+    InsufficientResource {
+        resource: String,
+        scope: Scope,
+        requested: Option<String>,
+        available: Option<String>,
+        total: Option<String>,
+    },
+    /// a run or resource limit, e.g. "User has reached queue workq running job limit."
+    Limit {
+        /// user, group or project the limit applies to, None for overall limits
+        entity: Option<String>,
+        /// name of the user, group or project if given
+        name: Option<String>,
+        scope: Scope,
+        /// None for job count limits
+        resource: Option<String>,
+    },
+    /// "Not enough free nodes available", total is set for "Not enough total nodes available"
+    NotEnoughNodes { total: bool },
+    /// "Node is in an ineligible state: offline"
+    NodeIneligible { state: String },
+    /// "Job is requesting an exclusive node and node is in use"
+    ExclusiveNodeInUse,
+    /// "Job would conflict with reservation or top job"
+    ReservationConflict,
+    /// "Draining system to allow top job to run"
+    Draining,
+    /// job would run into or cross dedicated time
+    DedicatedTime,
+    /// job would cross into prime or non-prime time
+    PrimeTime,
+    /// "Queue not started." or the queue isn't an execution queue
+    QueueNotStarted { queue: Option<String> },
+    /// "Job held by <who> on <date>"
+    Held { by: String },
+    /// the server or MoM rejected the run, "PBS Error: ..."
+    PbsError(String),
+    /// vnode comment "offlined by hook 'h' due to hook error"
+    OfflinedByHook { hook: String },
+    /// vnode comment "node down: communication closed"
+    NodeDown(String),
+    /// anything else after the "Not Running: " prefix
+    Other(String),
+}
+
+static INSUFFICIENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^Insufficient amount of (?:(queue|server) )?resource: (\S+)(?: \(R: (\S+) A: (\S+) T: (\S+)\))?",
+    )
+    .unwrap()
+});
+static REACHED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(User|Group|Project) (?:(\S+) )?has reached (?:the )?(queue|server)(?: (\S+))? (?:running job |job |)limit",
+    )
+    .unwrap()
+});
+static OVERALL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(Queue|Server)(?: (\S+))? job limit (?:has been )?reached").unwrap()
+});
+// "would exceed user alice's limit on resource ncpus in queue workq"
+// "would exceed queue workq's per-user limit on resource ncpus"
+static EXCEED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^would exceed (?:(user|group|project) (\S+)'s limit|(queue|complex|server)(?: (\S+))?'s per-(user|group|project) limit|(queue|complex|server)(?: (\S+))?'s limit)(?: on resource (\S+))?(?: in (queue|complex|server)(?: (\S+))?)?",
+    )
+    .unwrap()
+});
+
+fn scope(queue_word: Option<&str>, queue: Option<&str>) -> Scope {
+    match queue_word {
+        Some("queue") => Scope::Queue(queue.map(str::to_string)),
+        Some("server") | Some("complex") => Scope::Server,
+        _ => Scope::Node,
+    }
+}
+
+impl NotRunningReason {
+    /// Classify a job or vnode comment, None if it doesn't explain why something isn't running
+    pub fn parse(comment: &str) -> Option<NotRunningReason> {
+        let comment = comment.trim();
+        if let Some(by) = comment.strip_prefix("Job held by ") {
+            let by = by.split(" on ").next().unwrap_or(by);
+            return Some(NotRunningReason::Held { by: by.to_string() });
+        }
+        if let Some(hook) = comment
+            .strip_prefix("offlined by hook '")
+            .and_then(|h| h.split_once('\''))
+        {
+            return Some(NotRunningReason::OfflinedByHook {
+                hook: hook.0.to_string(),
+            });
+        }
+        if let Some(reason) = comment.strip_prefix("node down: ") {
+            return Some(NotRunningReason::NodeDown(reason.to_string()));
+        }
+        let msg = comment
+            .strip_prefix("Not Running: ")
+            .or_else(|| comment.strip_prefix("Can Never Run: "))?
+            .trim();
+        let text = msg.trim_end_matches('.');
+
+        if let Some(c) = INSUFFICIENT.captures(text) {
+            let get = |i| c.get(i).map(|m: regex::Match| m.as_str().to_string());
+            return Some(NotRunningReason::InsufficientResource {
+                resource: c[2].to_string(),
+                scope: scope(c.get(1).map(|m| m.as_str()), None),
+                requested: get(3),
+                available: get(4),
+                total: get(5),
+            });
+        }
+        if let Some(c) = REACHED.captures(text) {
+            return Some(NotRunningReason::Limit {
+                entity: Some(c[1].to_lowercase()),
+                name: c.get(2).map(|m| m.as_str().to_string()),
+                // "queue running job limit" has no queue name
+                scope: scope(
+                    Some(&c[3]),
+                    c.get(4)
+                        .map(|m| m.as_str())
+                        .filter(|q| *q != "running" && *q != "job"),
+                ),
+                resource: None,
+            });
+        }
+        if let Some(c) = OVERALL.captures(text) {
+            return Some(NotRunningReason::Limit {
+                entity: None,
+                name: None,
+                scope: scope(Some(&c[1].to_lowercase()), c.get(2).map(|m| m.as_str())),
+                resource: None,
+            });
+        }
+        if let Some(c) = EXCEED.captures(text) {
+            let s = |i| c.get(i).map(|m: regex::Match| m.as_str());
+            let (where_word, queue) = match (s(3), s(6), s(9)) {
+                (Some(w), _, _) => (Some(w), s(4)),
+                (_, Some(w), _) => (Some(w), s(7)),
+                (_, _, Some(w)) => (Some(w), s(10)),
+                _ => (Some("server"), None),
+            };
+            return Some(NotRunningReason::Limit {
+                entity: s(1).or(s(5)).map(str::to_string),
+                name: s(2).map(str::to_string),
+                scope: scope(where_word, queue),
+                resource: s(8).map(str::to_string),
+            });
+        }
+        let reason = if text.starts_with("Not enough free nodes available") {
+            NotRunningReason::NotEnoughNodes { total: false }
+        } else if text.starts_with("Not enough total nodes available") {
+            NotRunningReason::NotEnoughNodes { total: true }
+        } else if let Some(state) = text.strip_prefix("Node is in an ineligible state: ") {
+            NotRunningReason::NodeIneligible {
+                state: state.to_string(),
+            }
+        } else if text.starts_with("Job is requesting an exclusive node and node is in use") {
+            NotRunningReason::ExclusiveNodeInUse
+        } else if text.starts_with("Job would conflict with reservation or top job") {
+            NotRunningReason::ReservationConflict
+        } else if text.starts_with("Draining system to allow") {
+            NotRunningReason::Draining
+        } else if text.contains("dedicated time") {
+            NotRunningReason::DedicatedTime
+        } else if text.contains("prime time") || text.contains("primetime") {
+            NotRunningReason::PrimeTime
+        } else if text.starts_with("Queue not started")
+            || text.starts_with("Queue not an execution queue")
+        {
+            NotRunningReason::QueueNotStarted { queue: None }
+        } else if let Some(q) = text
+            .strip_prefix("Queue ")
+            .and_then(|q| q.strip_suffix(" is not started"))
+        {
+            NotRunningReason::QueueNotStarted {
+                queue: Some(q.to_string()),
+            }
+        } else if let Some(e) = text.strip_prefix("PBS Error: ") {
+            NotRunningReason::PbsError(e.to_string())
+        } else {
+            NotRunningReason::Other(msg.to_string())
+        };
+        Some(reason)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Node => write!(f, "node"),
+            Scope::Queue(Some(q)) => write!(f, "queue {q}"),
+            Scope::Queue(None) => write!(f, "queue"),
+            Scope::Server => write!(f, "server"),
+        }
+    }
+}
+
+impl fmt::Display for NotRunningReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotRunningReason::InsufficientResource {
+                resource,
+                scope,
+                requested,
+                available,
+                ..
+            } => {
+                write!(f, "not enough {resource} free at {scope} level")?;
+                if let (Some(r), Some(a)) = (requested, available) {
+                    write!(f, " (requested {r}, available {a})")?;
+                }
+                Ok(())
+            }
+            NotRunningReason::Limit {
+                entity,
+                name,
+                scope,
+                resource,
+            } => {
+                match (entity, name) {
+                    (Some(e), Some(n)) => write!(f, "{e} {n} is at the ")?,
+                    (Some(e), None) => write!(f, "{e} is at the ")?,
+                    _ => write!(f, "at the ")?,
+                }
+                match resource {
+                    Some(r) => write!(f, "{scope} limit on {r}"),
+                    None => write!(f, "{scope} job limit"),
+                }
+            }
+            NotRunningReason::NotEnoughNodes { total: false } => {
+                write!(f, "not enough free nodes")
+            }
+            NotRunningReason::NotEnoughNodes { total: true } => {
+                write!(f, "not enough nodes in the cluster")
+            }
+            NotRunningReason::NodeIneligible { state } => write!(f, "node is {state}"),
+            NotRunningReason::ExclusiveNodeInUse => {
+                write!(f, "exclusive node requested but nodes are in use")
+            }
+            NotRunningReason::ReservationConflict => {
+                write!(f, "would conflict with a reservation or top job")
+            }
+            NotRunningReason::Draining => write!(f, "nodes are draining for a top job"),
+            NotRunningReason::DedicatedTime => write!(f, "would run into dedicated time"),
+            NotRunningReason::PrimeTime => write!(f, "would cross a prime time boundary"),
+            NotRunningReason::QueueNotStarted { queue: Some(q) } => {
+                write!(f, "queue {q} is not started")
+            }
+            NotRunningReason::QueueNotStarted { queue: None } => {
+                write!(f, "queue is not started")
+            }
+            NotRunningReason::Held { by } => write!(f, "held by {by}"),
+            NotRunningReason::PbsError(e) => write!(f, "PBS error: {e}"),
+            NotRunningReason::OfflinedByHook { hook } => write!(f, "offlined by hook {hook}"),
+            NotRunningReason::NodeDown(r) => write!(f, "node down: {r}"),
+            NotRunningReason::Other(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl Status {
+    fn comment(&self) -> Option<String> {
+        match self.attribs().get(job::COMMENT) {
+            Some(Attrl::Value(v)) => Some(v.val()),
+            _ => None,
+        }
+    }
+    /// reason this job isn't running, or this vnode is unavailable, from its comment
+    pub fn not_running_reason(&self) -> Option<NotRunningReason> {
+        NotRunningReason::parse(&self.comment()?)
+    }
+    /// whether the scheduler decided this job can never run as submitted
+    pub fn can_never_run(&self) -> bool {
+        self.comment()
+            .is_some_and(|c| c.trim_start().starts_with("Can Never Run: "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Attribs, Op};
+    use NotRunningReason::*;
+
+    fn s(v: &str) -> Option<String> {
+        Some(v.to_string())
+    }
+
+    fn limit(
+        entity: Option<&str>,
+        name: Option<&str>,
+        scope: Scope,
+        resource: Option<&str>,
+    ) -> NotRunningReason {
+        Limit {
+            entity: entity.map(str::to_string),
+            name: name.map(str::to_string),
+            scope,
+            resource: resource.map(str::to_string),
+        }
+    }
+
+    fn insufficient(
+        resource: &str,
+        scope: Scope,
+        rat: Option<(&str, &str, &str)>,
+    ) -> NotRunningReason {
+        InsufficientResource {
+            resource: resource.to_string(),
+            scope,
+            requested: rat.and_then(|r| s(r.0)),
+            available: rat.and_then(|r| s(r.1)),
+            total: rat.and_then(|r| s(r.2)),
+        }
+    }
+
+    #[test]
+    fn scheduler_comments() {
+        let workq = || Scope::Queue(s("workq"));
+        let cases = [
+            (
+                "Not Running: Insufficient amount of resource: ngpus",
+                insufficient("ngpus", Scope::Node, None),
+            ),
+            (
+                "Not Running: Insufficient amount of resource: ncpus (R: 64 A: 48 T: 128)",
+                insufficient("ncpus", Scope::Node, Some(("64", "48", "128"))),
+            ),
+            (
+                "Not Running: Insufficient amount of queue resource: mem (R: 100gb A: 50gb T: 200gb)",
+                insufficient("mem", Scope::Queue(None), Some(("100gb", "50gb", "200gb"))),
+            ),
+            (
+                "Can Never Run: Insufficient amount of server resource: matlab (R: 4 A: 0 T: 2)",
+                insufficient("matlab", Scope::Server, Some(("4", "0", "2"))),
+            ),
+            (
+                "Not Running: User has reached queue limit",
+                limit(Some("user"), None, Scope::Queue(None), None),
+            ),
+            (
+                "Not Running: User has reached queue workq running job limit.",
+                limit(Some("user"), None, workq(), None),
+            ),
+            (
+                "Not Running: Group has reached server running job limit.",
+                limit(Some("group"), None, Scope::Server, None),
+            ),
+            (
+                "Not Running: Project climate has reached the server job limit",
+                limit(Some("project"), Some("climate"), Scope::Server, None),
+            ),
+            (
+                "Not Running: Server job limit reached",
+                limit(None, None, Scope::Server, None),
+            ),
+            (
+                "Not Running: Queue workq job limit has been reached.",
+                limit(None, None, workq(), None),
+            ),
+            (
+                "Not Running: would exceed user alice's limit on resource ncpus in queue workq",
+                limit(Some("user"), Some("alice"), workq(), Some("ncpus")),
+            ),
+            (
+                "Not Running: would exceed queue workq's per-user limit on resource ncpus",
+                limit(Some("user"), None, workq(), Some("ncpus")),
+            ),
+            (
+                "Not Running: would exceed complex's limit on resource mem",
+                limit(None, None, Scope::Server, Some("mem")),
+            ),
+            (
+                "Not Running: Not enough free nodes available",
+                NotEnoughNodes { total: false },
+            ),
+            (
+                "Can Never Run: Not enough total nodes available",
+                NotEnoughNodes { total: true },
+            ),
+            (
+                "Not Running: Node is in an ineligible state: offline",
+                NodeIneligible {
+                    state: "offline".to_string(),
+                },
+            ),
+            (
+                "Not Running: Job is requesting an exclusive node and node is in use",
+                ExclusiveNodeInUse,
+            ),
+            (
+                "Not Running: Job would conflict with reservation or top job",
+                ReservationConflict,
+            ),
+            (
+                "Not Running: Draining system to allow top job to run",
+                Draining,
+            ),
+            (
+                "Not Running: Job would cross dedicated time boundary",
+                DedicatedTime,
+            ),
+            (
+                "Not Running: Job will cross into primetime",
+                PrimeTime,
+            ),
+            (
+                "Not Running: Queue not started.",
+                QueueNotStarted { queue: None },
+            ),
+            (
+                "Not Running: Queue routeq is not started",
+                QueueNotStarted { queue: s("routeq") },
+            ),
+            (
+                "Job held by user1 on Mon Jun 22 10:12:14 2020",
+                Held {
+                    by: "user1".to_string(),
+                },
+            ),
+            (
+                "Not Running: PBS Error: Execution server rejected request",
+                PbsError("Execution server rejected request".to_string()),
+            ),
+            (
+                "offlined by hook 'pbs_cgroups' due to hook error",
+                OfflinedByHook {
+                    hook: "pbs_cgroups".to_string(),
+                },
+            ),
+            (
+                "node down: communication closed",
+                NodeDown("communication closed".to_string()),
+            ),
+            (
+                "Not Running: Job would exceed the walltime boundary.",
+                Other("Job would exceed the walltime boundary.".to_string()),
+            ),
+        ];
+        for (comment, want) in cases {
+            assert_eq!(NotRunningReason::parse(comment), Some(want), "{comment}");
+        }
+    }
+
+    #[test]
+    fn running_comments() {
+        for comment in [
+            "Job run at Mon Jun 22 at 10:12 on (node01:ncpus=1)",
+            "Job Array Began at Mon Jun 22 10:12:14",
+            "",
+        ] {
+            assert_eq!(NotRunningReason::parse(comment), None, "{comment}");
+        }
+    }
+
+    #[test]
+    fn display() {
+        let r = NotRunningReason::parse(
+            "Not Running: Insufficient amount of resource: ncpus (R: 64 A: 48 T: 128)",
+        );
+        assert_eq!(
+            r.unwrap().to_string(),
+            "not enough ncpus free at node level (requested 64, available 48)"
+        );
+        let r = NotRunningReason::parse(
+            "Not Running: would exceed user alice's limit on resource ncpus in queue workq",
+        );
+        assert_eq!(
+            r.unwrap().to_string(),
+            "user alice is at the queue workq limit on ncpus"
+        );
+    }
+
+    #[test]
+    fn from_status() {
+        let mut a = Attribs::new();
+        a.add(
+            job::COMMENT.to_string(),
+            Attrl::Value(Op::Default(
+                "Can Never Run: Not enough total nodes available".to_string(),
+            )),
+        );
+        let job = Status::new("1.svr".to_string(), None, a);
+        assert!(job.can_never_run());
+        assert_eq!(
+            job.not_running_reason(),
+            Some(NotEnoughNodes { total: true })
+        );
+        let job = Status::new("2.svr".to_string(), None, Attribs::new());
+        assert!(!job.can_never_run());
+        assert_eq!(job.not_running_reason(), None);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::attributes::{job, node, queue, reservation, server};
+use crate::types::{Attribs, Attrl, ReservationState, Status, VariableList};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Structured value of a compound attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decoded {
+    /// "Queued:1 Running:2" style counters
+    Counts(BTreeMap<String, u64>),
+    /// key=value lists such as Variable_List
+    Map(BTreeMap<String, String>),
+    /// comma separated lists such as Mail_Users or acl_users
+    List(Vec<String>),
+    Int(i64),
+    Str(String),
+}
+
+impl Decoded {
+    pub fn json(&self) -> Value {
+        match self {
+            Decoded::Counts(c) => Value::Object(
+                c.iter()
+                    .map(|(k, v)| (k.clone(), Value::from(*v)))
+                    .collect::<Map<_, _>>(),
+            ),
+            Decoded::Map(m) => Value::Object(
+                m.iter()
+                    .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                    .collect::<Map<_, _>>(),
+            ),
+            Decoded::List(l) => Value::Array(l.iter().cloned().map(Value::String).collect()),
+            Decoded::Int(i) => Value::from(*i),
+            Decoded::Str(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+type Decoder = fn(&str) -> Option<Decoded>;
+
+// attribute name -> decoder
+const DECODERS: [(&str, Decoder); 22] = [
+    (queue::STATE_COUNT, counts),
+    (job::ARRAY_STATE_COUNT, counts),
+    (server::LICENSE_COUNT, counts),
+    (reservation::RESERVE_STATE, resv_state),
+    (reservation::RESERVE_SUBSTATE, resv_state),
+    (job::VARIABLE_LIST, variable_list),
+    (job::MAIL_USERS, list),
+    (queue::ACL_USERS, list),
+    (queue::ACL_GROUPS, list),
+    (queue::ACL_HOSTS, list),
+    (server::ACL_RESV_USERS, list),
+    (server::ACL_RESV_GROUPS, list),
+    (server::ACL_RESV_HOSTS, list),
+    (server::ACL_ROOTS, list),
+    (server::MANAGERS, list),
+    (server::OPERATORS, list),
+    (reservation::AUTHORIZED_USERS, list),
+    (reservation::AUTHORIZED_GROUPS, list),
+    (reservation::AUTHORIZED_HOSTS, list),
+    (queue::ROUTE_DESTINATIONS, list),
+    (node::PCPUS, int),
+    (node::SHARING, string),
+];
+
+// "Transit:0 Queued:1 Held:0 " -> {Transit: 0, Queued: 1, Held: 0}
+fn counts(val: &str) -> Option<Decoded> {
+    let mut out = BTreeMap::new();
+    for (state, n) in Attribs::split_state_count(val) {
+        out.insert(state, n.parse().ok()?);
+    }
+    Some(Decoded::Counts(out))
+}
+
+// numeric reservation state -> RESV_* name
+fn resv_state(val: &str) -> Option<Decoded> {
+    val.parse::<ReservationState>()
+        .ok()
+        .map(|s| Decoded::Str(s.to_string()))
+}
+
+// split on commas not escaped with a backslash, unescaping the rest
+pub(crate) fn split_escaped(val: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut chars = val.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(n) => cur.push(n),
+                None => cur.push('\\'),
+            },
+            ',' => out.push(std::mem::take(&mut cur)),
+            c => cur.push(c),
+        }
+    }
+    out.push(cur);
+    out
+}
+
+// "PBS_O_HOME=/home/a,FOO=a\,b" -> {PBS_O_HOME: /home/a, FOO: "a,b"}
+fn variable_list(val: &str) -> Option<Decoded> {
+    let vars: VariableList = val.parse().ok()?;
+    Some(Decoded::Map(
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    ))
+}
+
+fn list(val: &str) -> Option<Decoded> {
+    Some(Decoded::List(
+        val.split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .collect(),
+    ))
+}
+
+fn int(val: &str) -> Option<Decoded> {
+    val.trim().parse().ok().map(Decoded::Int)
+}
+
+fn string(val: &str) -> Option<Decoded> {
+    Some(Decoded::Str(val.trim().to_string()))
+}
+
+/// Decode a compound attribute value, None if `name` has no decoder or `val` is malformed
+pub fn decode(name: &str, val: &str) -> Option<Decoded> {
+    DECODERS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, d)| d(val))
+}
+
+/// whether decode() knows how to decode attribute `name`
+pub fn has_decoder(name: &str) -> bool {
+    DECODERS.iter().any(|(n, _)| *n == name)
+}
+
+impl Status {
+    /// decoded value of a compound attribute
+    pub fn decoded(&self, name: &str) -> Option<Decoded> {
+        match self.attribs().get(name) {
+            Some(Attrl::Value(v)) => decode(name, &v.val()),
+            _ => None,
+        }
+    }
+    fn counts(&self, name: &str) -> Option<BTreeMap<String, u64>> {
+        match self.decoded(name)? {
+            Decoded::Counts(c) => Some(c),
+            _ => None,
+        }
+    }
+    fn list(&self, name: &str) -> Vec<String> {
+        match self.decoded(name) {
+            Some(Decoded::List(l)) => l,
+            _ => Vec::new(),
+        }
+    }
+    /// jobs per state of a queue or server
+    pub fn state_count(&self) -> Option<BTreeMap<String, u64>> {
+        self.counts(queue::STATE_COUNT)
+    }
+    /// subjobs per state of a job array
+    pub fn array_state_count(&self) -> Option<BTreeMap<String, u64>> {
+        self.counts(job::ARRAY_STATE_COUNT)
+    }
+    /// server license counters
+    pub fn license_count(&self) -> Option<BTreeMap<String, u64>> {
+        self.counts(server::LICENSE_COUNT)
+    }
+    /// a job's Variable_List with escaped commas resolved
+    pub fn variable_list(&self) -> Option<BTreeMap<String, String>> {
+        match self.decoded(job::VARIABLE_LIST)? {
+            Decoded::Map(m) => Some(m),
+            _ => None,
+        }
+    }
+    pub fn mail_users(&self) -> Vec<String> {
+        self.list(job::MAIL_USERS)
+    }
+    /// entries of an ACL attribute such as acl_users, with their +/- prefixes
+    pub fn acl(&self, name: &str) -> Vec<String> {
+        self.list(name)
+    }
+    /// numeric resources assigned on a vnode, sizes in bytes
+    pub fn resources_assigned(&self) -> BTreeMap<String, u64> {
+        self.resource_amounts(node::RESOURCES_ASSIGNED)
+    }
+    pub fn pcpus(&self) -> Option<i64> {
+        match self.decoded(node::PCPUS)? {
+            Decoded::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+    /// vnode sharing setting, e.g. default_shared or force_excl
+    pub fn sharing(&self) -> Option<String> {
+        match self.decoded(node::SHARING)? {
+            Decoded::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::ValueType;
+    use crate::parse_text;
+    use serde_json::json;
+
+    fn map(v: &[(&str, &str)]) -> BTreeMap<String, String> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn list(v: &[&str]) -> Decoded {
+        Decoded::List(v.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn decoders() {
+        assert_eq!(
+            decode(
+                "state_count",
+                "Transit:0 Queued:12 Held:1 Waiting:0 Running:3 Exiting:0 Begun:0 "
+            ),
+            Some(Decoded::Counts(BTreeMap::from([
+                ("Begun".to_string(), 0),
+                ("Exiting".to_string(), 0),
+                ("Held".to_string(), 1),
+                ("Queued".to_string(), 12),
+                ("Running".to_string(), 3),
+                ("Transit".to_string(), 0),
+                ("Waiting".to_string(), 0),
+            ])))
+        );
+        assert_eq!(
+            decode(
+                "license_count",
+                "Avail_Global:1000 Avail_Local:24 Used:8 High_Use:32"
+            ),
+            Some(Decoded::Counts(BTreeMap::from([
+                ("Avail_Global".to_string(), 1000),
+                ("Avail_Local".to_string(), 24),
+                ("High_Use".to_string(), 32),
+                ("Used".to_string(), 8),
+            ])))
+        );
+        assert_eq!(decode("array_state_count", "Queued:x"), None);
+        assert_eq!(
+            decode(
+                "Variable_List",
+                r"PBS_O_HOME=/home/user1,FOO=a\,b,PBS_O_PATH=/usr/bin:/bin,EMPTY"
+            ),
+            Some(Decoded::Map(map(&[
+                ("EMPTY", ""),
+                ("FOO", "a,b"),
+                ("PBS_O_HOME", "/home/user1"),
+                ("PBS_O_PATH", "/usr/bin:/bin"),
+            ])))
+        );
+        assert_eq!(
+            decode("Mail_Users", "alice@example.com, bob@example.com"),
+            Some(list(&["alice@example.com", "bob@example.com"]))
+        );
+        assert_eq!(
+            decode("acl_users", "+alice@*,-bob@login01,"),
+            Some(list(&["+alice@*", "-bob@login01"]))
+        );
+        assert_eq!(
+            decode("Authorized_Users", "user1@login01"),
+            Some(list(&["user1@login01"]))
+        );
+        assert_eq!(
+            decode("reserve_state", "5"),
+            Some(Decoded::Str("RESV_RUNNING".to_string()))
+        );
+        assert_eq!(decode("pcpus", " 32"), Some(Decoded::Int(32)));
+        assert_eq!(decode("pcpus", "many"), None);
+        assert_eq!(
+            decode("sharing", "force_excl"),
+            Some(Decoded::Str("force_excl".to_string()))
+        );
+        assert_eq!(decode("Job_Name", "STDIN"), None);
+        assert!(has_decoder("route_destinations") && has_decoder("managers"));
+        assert!(!has_decoder("Job_Name"));
+    }
+
+    #[test]
+    fn status_accessors() {
+        let resp = parse_text(
+            "\
+Job Id: 1.svr
+    Mail_Users = alice@example.com
+    Variable_List = PBS_O_HOME=/home/alice
+    array_state_count = Queued:2 Running:1 Exiting:0 Expired:0 
+
+",
+        )
+        .unwrap()
+        .1;
+        let job = &resp.resources[0];
+        assert_eq!(job.mail_users(), ["alice@example.com"]);
+        assert_eq!(
+            job.variable_list(),
+            Some(map(&[("PBS_O_HOME", "/home/alice")]))
+        );
+        assert_eq!(job.array_state_count().unwrap()["Queued"], 2);
+        assert_eq!(job.state_count(), None);
+        assert!(job.acl("acl_users").is_empty());
+    }
+
+    // pins the shape of Attribs::json, which exports and the CLI print as is
+    #[test]
+    fn json_shape() {
+        let resp = parse_text(
+            "\
+Queue: workq
+    queue_type = Execution
+    total_jobs = 3
+    state_count = Transit:0 Queued:2 Held:0 Waiting:0 Running:1 Exiting:0 Begun:0 
+    acl_users = +alice,-bob
+    resources_max.mem = 4gb
+    resources_max.walltime = 24:00:00
+    comment = 42
+    enabled = True
+
+",
+        )
+        .unwrap()
+        .1;
+        let a = resp.resources[0].attribs();
+        assert_eq!(
+            a.json(),
+            json!({
+                "queue_type": "Execution",
+                "total_jobs": 3,
+                "state_count.Transit": 0,
+                "state_count.Queued": 2,
+                "state_count.Held": 0,
+                "state_count.Waiting": 0,
+                "state_count.Running": 1,
+                "state_count.Exiting": 0,
+                "state_count.Begun": 0,
+                "acl_users": ["+alice", "-bob"],
+                "resources_max.mem": 4000,
+                "resources_max.walltime": "24:00:00",
+                "comment": "42",
+                "enabled": true,
+            })
+        );
+        let types = BTreeMap::from([
+            ("mem".to_string(), ValueType::Size),
+            ("walltime".to_string(), ValueType::Duration),
+        ]);
+        let typed = a.json_typed(&types);
+        assert_eq!(typed["resources_max.mem"], json!(4u64 << 30));
+        assert_eq!(typed["resources_max.walltime"], json!(86400));
+        assert_eq!(typed["total_jobs"], json!(3));
+    }
+}
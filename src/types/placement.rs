@@ -0,0 +1,316 @@
+use crate::attributes::job;
+use crate::helpers::parse_amount;
+use crate::types::{Attrl, Status};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Resources allocated to a job on a single vnode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VnodeAlloc {
+    pub vnode: String,
+    pub resources: BTreeMap<String, String>,
+}
+
+impl VnodeAlloc {
+    pub fn get(&self, resource: &str) -> Option<&str> {
+        self.resources.get(resource).map(|r| r.as_str())
+    }
+    /// numeric amount of a resource, sizes are in bytes
+    pub fn amount(&self, resource: &str) -> Option<u64> {
+        self.get(resource).and_then(parse_amount)
+    }
+    pub fn ncpus(&self) -> u64 {
+        self.amount("ncpus").unwrap_or(0)
+    }
+    pub fn ngpus(&self) -> u64 {
+        self.amount("ngpus").unwrap_or(0)
+    }
+    /// memory in bytes
+    pub fn mem(&self) -> u64 {
+        self.amount("mem").unwrap_or(0)
+    }
+}
+
+impl fmt::Display for VnodeAlloc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.vnode)?;
+        for (r, v) in &self.resources {
+            write!(f, ":{r}={v}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parsed exec_vnode, e.g. `(node1:ncpus=36:mem=100gb)+(node2a:ncpus=2+node2b:ncpus=2)`
+///
+/// Each chunk of the job's select maps to one or more vnodes.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExecVnode {
+    pub chunks: Vec<Vec<VnodeAlloc>>,
+}
+
+impl ExecVnode {
+    /// every vnode allocation, in order
+    pub fn allocs(&self) -> impl Iterator<Item = &VnodeAlloc> {
+        self.chunks.iter().flatten()
+    }
+    /// distinct vnode names, in order of first appearance
+    pub fn vnodes(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        for a in self.allocs() {
+            if !out.contains(&a.vnode.as_str()) {
+                out.push(&a.vnode);
+            }
+        }
+        out
+    }
+    /// numeric resources summed per vnode, sizes are in bytes
+    pub fn by_vnode(&self) -> BTreeMap<String, BTreeMap<String, u64>> {
+        let mut out: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for a in self.allocs() {
+            let entry = out.entry(a.vnode.clone()).or_default();
+            for r in a.resources.keys() {
+                if let Some(amount) = a.amount(r) {
+                    *entry.entry(r.clone()).or_default() += amount;
+                }
+            }
+        }
+        out
+    }
+    /// numeric amount of a resource summed over all vnodes
+    pub fn total(&self, resource: &str) -> u64 {
+        self.allocs().filter_map(|a| a.amount(resource)).sum()
+    }
+}
+
+impl FromStr for ExecVnode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chunks = Vec::new();
+        let mut rest = s.trim();
+        while !rest.is_empty() {
+            let chunk = rest
+                .strip_prefix('(')
+                .ok_or_else(|| format!("invalid exec_vnode, expected '(' at: {rest}"))?;
+            let end = chunk
+                .find(')')
+                .ok_or_else(|| format!("invalid exec_vnode, unclosed chunk: {rest}"))?;
+            let mut allocs = Vec::new();
+            for part in chunk[..end].split('+') {
+                let mut fields = part.split(':');
+                let vnode = fields.next().unwrap_or("").trim();
+                if vnode.is_empty() {
+                    return Err(format!("invalid exec_vnode, missing vnode name in: {part}"));
+                }
+                let mut resources = BTreeMap::new();
+                for f in fields {
+                    let (r, v) = f
+                        .split_once('=')
+                        .ok_or_else(|| format!("invalid exec_vnode resource: {f}"))?;
+                    resources.insert(r.to_string(), v.to_string());
+                }
+                allocs.push(VnodeAlloc {
+                    vnode: vnode.to_string(),
+                    resources,
+                });
+            }
+            chunks.push(allocs);
+            rest = chunk[end + 1..].trim_start();
+            if let Some(r) = rest.strip_prefix('+') {
+                rest = r.trim_start();
+            } else if !rest.is_empty() {
+                return Err(format!("invalid exec_vnode, expected '+' at: {rest}"));
+            }
+        }
+        Ok(ExecVnode { chunks })
+    }
+}
+
+impl fmt::Display for ExecVnode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chunks: Vec<String> = self
+            .chunks
+            .iter()
+            .map(|c| {
+                let allocs: Vec<String> = c.iter().map(|a| a.to_string()).collect();
+                format!("({})", allocs.join("+"))
+            })
+            .collect();
+        write!(f, "{}", chunks.join("+"))
+    }
+}
+
+/// A job's slot on a host from exec_host, e.g. `node1/0*36`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostSlot {
+    pub host: String,
+    pub index: u32,
+    pub ncpus: u32,
+}
+
+/// Parsed exec_host, e.g. `node1/0*36+node2/0*36`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExecHost {
+    pub slots: Vec<HostSlot>,
+}
+
+impl ExecHost {
+    /// distinct host names, in order of first appearance
+    pub fn hosts(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        for s in &self.slots {
+            if !out.contains(&s.host.as_str()) {
+                out.push(&s.host);
+            }
+        }
+        out
+    }
+    /// ncpus summed per host
+    pub fn by_host(&self) -> BTreeMap<String, u32> {
+        let mut out = BTreeMap::new();
+        for s in &self.slots {
+            *out.entry(s.host.clone()).or_default() += s.ncpus;
+        }
+        out
+    }
+}
+
+impl FromStr for ExecHost {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut slots = Vec::new();
+        for part in s.trim().split('+').filter(|p| !p.is_empty()) {
+            let (host, slot) = part
+                .split_once('/')
+                .ok_or_else(|| format!("invalid exec_host entry: {part}"))?;
+            let (index, ncpus) = match slot.split_once('*') {
+                Some((i, n)) => (i, n),
+                None => (slot, "1"),
+            };
+            slots.push(HostSlot {
+                host: host.trim().to_string(),
+                index: index
+                    .parse()
+                    .map_err(|_| format!("invalid exec_host index: {part}"))?,
+                ncpus: ncpus
+                    .parse()
+                    .map_err(|_| format!("invalid exec_host ncpus: {part}"))?,
+            });
+        }
+        Ok(ExecHost { slots })
+    }
+}
+
+impl fmt::Display for ExecHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let slots: Vec<String> = self
+            .slots
+            .iter()
+            .map(|s| {
+                if s.ncpus == 1 {
+                    format!("{}/{}", s.host, s.index)
+                } else {
+                    format!("{}/{}*{}", s.host, s.index, s.ncpus)
+                }
+            })
+            .collect();
+        write!(f, "{}", slots.join("+"))
+    }
+}
+
+impl Status {
+    /// parsed exec_vnode of a running job
+    pub fn exec_vnode(&self) -> Option<Result<ExecVnode, String>> {
+        match self.attribs().get(job::EXEC_VNODE) {
+            Some(Attrl::Value(v)) => Some(v.val().parse()),
+            _ => None,
+        }
+    }
+    /// parsed exec_host of a running job
+    pub fn exec_host(&self) -> Option<Result<ExecHost, String>> {
+        match self.attribs().get(job::EXEC_HOST) {
+            Some(Attrl::Value(v)) => Some(v.val().parse()),
+            _ => None,
+        }
+    }
+    /// numeric amounts of a resource attribute such as resources_assigned or
+    /// resources_available, sizes are in bytes and non numeric values are left out
+    pub fn resource_amounts(&self, attrib: &str) -> BTreeMap<String, u64> {
+        match self.attribs().get(attrib) {
+            Some(Attrl::Resource(map)) => map
+                .iter()
+                .filter_map(|(r, v)| parse_amount(&v.val()).map(|a| (r.clone(), a)))
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_vnode_chunks() {
+        let ev: ExecVnode = "(a:ncpus=2+b:ncpus=2)+(c:mem=1gb)".parse().unwrap();
+        assert_eq!(ev.chunks.len(), 2);
+        assert_eq!(ev.chunks[0].len(), 2);
+        assert_eq!(ev.vnodes(), ["a", "b", "c"]);
+        assert_eq!(ev.total("ncpus"), 4);
+        assert_eq!(ev.chunks[1][0].mem(), 1 << 30);
+        assert_eq!(ev.to_string(), "(a:ncpus=2+b:ncpus=2)+(c:mem=1gb)");
+    }
+
+    #[test]
+    fn exec_vnode_by_vnode() {
+        let ev: ExecVnode = "(n1:ncpus=4:mem=2gb)+(n1:ncpus=4:ngpus=1)+(n2[0]:ncpus=8)"
+            .parse()
+            .unwrap();
+        let by = ev.by_vnode();
+        assert_eq!(by["n1"]["ncpus"], 8);
+        assert_eq!(by["n1"]["ngpus"], 1);
+        assert_eq!(by["n1"]["mem"], 2 << 30);
+        assert_eq!(by["n2[0]"]["ncpus"], 8);
+        assert_eq!(ev.vnodes(), ["n1", "n2[0]"]);
+    }
+
+    #[test]
+    fn exec_vnode_malformed() {
+        for s in [
+            "a:ncpus=2",
+            "(a:ncpus=2",
+            "(a:ncpus=2)(b:ncpus=2)",
+            "(:ncpus=2)",
+            "(a:ncpus)",
+        ] {
+            assert!(s.parse::<ExecVnode>().is_err(), "{s}");
+        }
+        assert_eq!("".parse::<ExecVnode>(), Ok(ExecVnode::default()));
+    }
+
+    #[test]
+    fn exec_host() {
+        let eh: ExecHost = "node1/0*36+node2/1*4+node3/2".parse().unwrap();
+        assert_eq!(
+            eh.slots[1],
+            HostSlot {
+                host: "node2".to_string(),
+                index: 1,
+                ncpus: 4
+            }
+        );
+        assert_eq!(eh.slots[2].ncpus, 1);
+        assert_eq!(eh.hosts(), ["node1", "node2", "node3"]);
+        assert_eq!(eh.to_string(), "node1/0*36+node2/1*4+node3/2");
+        let eh: ExecHost = "n1/0*2+n1/1*2".parse().unwrap();
+        assert_eq!(eh.by_host()["n1"], 4);
+    }
+
+    #[test]
+    fn exec_host_malformed() {
+        for s in ["node1", "node1/x", "node1/0*", "node1/0*many"] {
+            assert!(s.parse::<ExecHost>().is_err(), "{s}");
+        }
+    }
+}
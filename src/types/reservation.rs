@@ -0,0 +1,691 @@
+use crate::attributes::reservation;
+use crate::types::{Attribs, Attrl, Op, Rrule, Status};
+use chrono::{DateTime, Duration, LocalResult, Offset, TimeZone};
+use chrono_tz::Tz;
+use log::trace;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Builder for advance, standing and maintenance reservations
+///
+/// Times are seconds since the epoch, as PBS stores them.
+#[derive(Debug, Clone, Default)]
+pub struct ReservationSpec {
+    name: Option<String>,
+    start: Option<i64>,
+    end: Option<i64>,
+    duration: Option<i64>,
+    select: Option<String>,
+    users: Vec<String>,
+    groups: Vec<String>,
+    queue: Option<String>,
+    rrule: Option<Rrule>,
+    timezone: Option<String>,
+    hosts: Vec<String>,
+}
+
+impl ReservationSpec {
+    pub fn new() -> ReservationSpec {
+        ReservationSpec::default()
+    }
+    pub fn name(mut self, name: &str) -> ReservationSpec {
+        self.name = Some(name.to_string());
+        self
+    }
+    pub fn start(mut self, start: i64) -> ReservationSpec {
+        self.start = Some(start);
+        self
+    }
+    pub fn end(mut self, end: i64) -> ReservationSpec {
+        self.end = Some(end);
+        self
+    }
+    /// duration in seconds
+    pub fn duration(mut self, duration: i64) -> ReservationSpec {
+        self.duration = Some(duration);
+        self
+    }
+    pub fn select(mut self, select: &str) -> ReservationSpec {
+        self.select = Some(select.to_string());
+        self
+    }
+    /// add a user to the reservation's Authorized_Users ACL
+    pub fn user(mut self, user: &str) -> ReservationSpec {
+        self.users.push(user.to_string());
+        self
+    }
+    /// add a group to the reservation's Authorized_Groups ACL
+    pub fn group(mut self, group: &str) -> ReservationSpec {
+        self.groups.push(group.to_string());
+        self
+    }
+    pub fn queue(mut self, queue: &str) -> ReservationSpec {
+        self.queue = Some(queue.to_string());
+        self
+    }
+    /// make this a standing reservation recurring according to `rrule`
+    pub fn rrule(mut self, rrule: Rrule) -> ReservationSpec {
+        self.rrule = Some(rrule);
+        self
+    }
+    /// timezone the rrule is evaluated in, defaults to $PBS_TZID like pbs_rsub
+    pub fn timezone(mut self, tz: &str) -> ReservationSpec {
+        self.timezone = Some(tz.to_string());
+        self
+    }
+    /// make this a maintenance reservation over the given hosts
+    pub fn maintenance_hosts<S: AsRef<str>>(mut self, hosts: &[S]) -> ReservationSpec {
+        self.hosts = hosts.iter().map(|h| h.as_ref().to_string()).collect();
+        self
+    }
+
+    pub fn is_standing(&self) -> bool {
+        self.rrule.is_some()
+    }
+    pub fn is_maintenance(&self) -> bool {
+        !self.hosts.is_empty()
+    }
+
+    // start and end of the first occurrence
+    fn window(&self) -> Result<(i64, i64), String> {
+        let start = self
+            .start
+            .ok_or_else(|| "reservation start time is required".to_string())?;
+        let end = match (self.end, self.duration) {
+            (Some(e), Some(d)) if e - start != d => {
+                return Err(format!(
+                    "reservation end {e} doesn't match start {start} + duration {d}"
+                ))
+            }
+            (Some(e), _) => e,
+            (None, Some(d)) => start + d,
+            (None, None) => return Err("reservation end or duration is required".to_string()),
+        };
+        if end <= start {
+            return Err("reservation must end after it starts".to_string());
+        }
+        Ok((start, end))
+    }
+
+    fn tz(&self) -> Option<String> {
+        self.timezone
+            .clone()
+            .or_else(|| std::env::var("PBS_TZID").ok())
+    }
+
+    /// check the spec is complete and consistent, without contacting the server
+    pub fn validate(&self) -> Result<(), String> {
+        let (start, end) = self.window()?;
+        if self.is_maintenance() && self.select.is_some() {
+            return Err("maintenance reservations take a host list, not a select".to_string());
+        }
+        if !self.is_maintenance() && self.select.is_none() {
+            return Err("reservation select is required".to_string());
+        }
+        let name_re = Regex::new(r"^[^,\s]+$").unwrap();
+        for u in self
+            .users
+            .iter()
+            .chain(self.groups.iter())
+            .chain(self.hosts.iter())
+        {
+            if !name_re.is_match(u) {
+                return Err(format!("invalid user, group or host name: {u:?}"));
+            }
+        }
+        if let Some(rrule) = &self.rrule {
+            rrule.validate()?;
+            if rrule.get_count().is_none() && rrule.get_until().is_none() {
+                return Err("standing reservation rrule needs COUNT or UNTIL".to_string());
+            }
+            if self.is_maintenance() {
+                return Err("maintenance reservations can't be standing reservations".to_string());
+            }
+            let zone = match self.tz() {
+                None => {
+                    return Err(
+                        "standing reservation needs a timezone (or PBS_TZID set)".to_string()
+                    )
+                }
+                Some(tz) => zone(&tz)?,
+            };
+            // occurrences must not overlap
+            let occ = expand(rrule, zone, start, 2)?;
+            if occ.len() > 1 && occ[1] - occ[0] < end - start {
+                return Err(format!(
+                    "reservation duration {} is longer than the recurrence interval",
+                    end - start
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand the reservation into (start, end) pairs, at most `limit` of them
+    ///
+    /// Standing reservations recur at the same local time in their timezone, so
+    /// occurrences move in UTC across daylight saving changes.
+    pub fn occurrences(&self, limit: usize) -> Result<Vec<(i64, i64)>, String> {
+        let (start, end) = self.window()?;
+        let rrule = match &self.rrule {
+            Some(r) => r,
+            None => return Ok(vec![(start, end)].into_iter().take(limit).collect()),
+        };
+        let tz = self
+            .tz()
+            .ok_or("standing reservation needs a timezone (or PBS_TZID set)")?;
+        Ok(expand(rrule, zone(&tz)?, start, limit)?
+            .into_iter()
+            .map(|s| (s, s + end - start))
+            .collect())
+    }
+
+    pub(crate) fn attribs(&self) -> Result<Attribs, String> {
+        self.validate()?;
+        let (start, end) = self.window()?;
+        let mut attribs = Attribs::new();
+        let mut set =
+            |name: &str, val: String| attribs.add(name.to_string(), Attrl::Value(Op::Set(val)));
+        set(reservation::RESERVE_START, start.to_string());
+        set(reservation::RESERVE_END, end.to_string());
+        if let Some(n) = &self.name {
+            set(reservation::RESERVE_NAME, n.clone());
+        }
+        if !self.users.is_empty() {
+            set(reservation::AUTHORIZED_USERS, self.users.join(","));
+        }
+        if !self.groups.is_empty() {
+            set(reservation::AUTHORIZED_GROUPS, self.groups.join(","));
+        }
+        if let Some(q) = &self.queue {
+            set(reservation::QUEUE, q.clone());
+        }
+        if let Some(r) = &self.rrule {
+            set(reservation::RESERVE_RRULE, r.to_string());
+            set(reservation::RESERVE_TIMEZONE, self.tz().unwrap());
+        }
+        let mut resources = BTreeMap::new();
+        if self.is_maintenance() {
+            let select: Vec<String> = self.hosts.iter().map(|h| format!("host={h}")).collect();
+            resources.insert("select".to_string(), Op::Set(select.join("+")));
+            resources.insert("place".to_string(), Op::Set("exclhost".to_string()));
+        } else if let Some(s) = &self.select {
+            resources.insert("select".to_string(), Op::Set(s.clone()));
+        }
+        attribs.add(
+            reservation::RESOURCE_LIST.to_string(),
+            Attrl::Resource(resources),
+        );
+        trace!("reservation attribs: {attribs:?}");
+        Ok(attribs)
+    }
+}
+
+fn zone(tz: &str) -> Result<Tz, String> {
+    tz.parse().map_err(|_| format!("invalid timezone: {tz}"))
+}
+
+// start times of the first `limit` occurrences, the rrule is evaluated in local time
+fn expand(rrule: &Rrule, zone: Tz, start: i64, limit: usize) -> Result<Vec<i64>, String> {
+    let local = DateTime::from_timestamp(start, 0)
+        .ok_or_else(|| format!("invalid start time: {start}"))?
+        .with_timezone(&zone)
+        .naive_local();
+    Ok(rrule
+        .occurrences(local, limit)
+        .into_iter()
+        .map(|o| match zone.from_local_datetime(&o) {
+            LocalResult::Single(t) => t.timestamp(),
+            // the first of a repeated hour
+            LocalResult::Ambiguous(t, _) => t.timestamp(),
+            // a skipped hour keeps the offset from before the change, as in RFC 5545
+            LocalResult::None => {
+                let before = zone
+                    .offset_from_utc_datetime(&(o - Duration::days(1)))
+                    .fix()
+                    .local_minus_utc();
+                o.and_utc().timestamp() - i64::from(before)
+            }
+        })
+        .collect())
+}
+
+/// Reservation states, as found in reserve_state and reserve_substate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationState {
+    None,
+    Unconfirmed,
+    Confirmed,
+    Wait,
+    TimeToRun,
+    Running,
+    Finished,
+    BeingDeleted,
+    Deleted,
+    DeletingJobs,
+    Degraded,
+    BeingAltered,
+    InConflict,
+}
+
+impl ReservationState {
+    /// whether jobs can be submitted into and run in the reservation
+    pub fn is_usable(&self) -> bool {
+        matches!(
+            self,
+            ReservationState::Confirmed | ReservationState::Running | ReservationState::Degraded
+        )
+    }
+    /// whether the reservation will never become usable again
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            ReservationState::Finished
+                | ReservationState::BeingDeleted
+                | ReservationState::Deleted
+                | ReservationState::DeletingJobs
+        )
+    }
+    /// two letter abbreviation used by pbs_rstat
+    pub fn abbrev(&self) -> &'static str {
+        match self {
+            ReservationState::None => "NO",
+            ReservationState::Unconfirmed => "UN",
+            ReservationState::Confirmed => "CO",
+            ReservationState::Wait => "WT",
+            ReservationState::TimeToRun => "TR",
+            ReservationState::Running => "RN",
+            ReservationState::Finished => "FN",
+            ReservationState::BeingDeleted => "BD",
+            ReservationState::Deleted => "DE",
+            ReservationState::DeletingJobs => "DJ",
+            ReservationState::Degraded => "DG",
+            ReservationState::BeingAltered => "AL",
+            ReservationState::InConflict => "IC",
+        }
+    }
+}
+
+impl TryFrom<u32> for ReservationState {
+    type Error = String;
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        Ok(match v {
+            pbs_sys::resv_states_RESV_NONE => ReservationState::None,
+            pbs_sys::resv_states_RESV_UNCONFIRMED => ReservationState::Unconfirmed,
+            pbs_sys::resv_states_RESV_CONFIRMED => ReservationState::Confirmed,
+            pbs_sys::resv_states_RESV_WAIT => ReservationState::Wait,
+            pbs_sys::resv_states_RESV_TIME_TO_RUN => ReservationState::TimeToRun,
+            pbs_sys::resv_states_RESV_RUNNING => ReservationState::Running,
+            pbs_sys::resv_states_RESV_FINISHED => ReservationState::Finished,
+            pbs_sys::resv_states_RESV_BEING_DELETED => ReservationState::BeingDeleted,
+            pbs_sys::resv_states_RESV_DELETED => ReservationState::Deleted,
+            pbs_sys::resv_states_RESV_DELETING_JOBS => ReservationState::DeletingJobs,
+            pbs_sys::resv_states_RESV_DEGRADED => ReservationState::Degraded,
+            pbs_sys::resv_states_RESV_BEING_ALTERED => ReservationState::BeingAltered,
+            pbs_sys::resv_states_RESV_IN_CONFLICT => ReservationState::InConflict,
+            _ => return Err(format!("unknown reservation state: {v}")),
+        })
+    }
+}
+
+impl FromStr for ReservationState {
+    type Err = String;
+    // accepts the numeric value from the api, RESV_* names and pbs_rstat abbreviations
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(v) = s.parse::<u32>() {
+            return v.try_into();
+        }
+        Ok(match s.trim_start_matches("RESV_") {
+            "NONE" | "NO" => ReservationState::None,
+            "UNCONFIRMED" | "UN" => ReservationState::Unconfirmed,
+            "CONFIRMED" | "CO" => ReservationState::Confirmed,
+            "WAIT" | "WT" => ReservationState::Wait,
+            "TIME_TO_RUN" | "TR" => ReservationState::TimeToRun,
+            "RUNNING" | "RN" => ReservationState::Running,
+            "FINISHED" | "FN" => ReservationState::Finished,
+            "BEING_DELETED" | "BD" => ReservationState::BeingDeleted,
+            "DELETED" | "DE" => ReservationState::Deleted,
+            "DELETING_JOBS" | "DJ" => ReservationState::DeletingJobs,
+            "DEGRADED" | "DG" => ReservationState::Degraded,
+            "BEING_ALTERED" | "AL" => ReservationState::BeingAltered,
+            "IN_CONFLICT" | "IC" => ReservationState::InConflict,
+            _ => return Err(format!("unknown reservation state: {s}")),
+        })
+    }
+}
+
+impl fmt::Display for ReservationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReservationState::None => "RESV_NONE",
+            ReservationState::Unconfirmed => "RESV_UNCONFIRMED",
+            ReservationState::Confirmed => "RESV_CONFIRMED",
+            ReservationState::Wait => "RESV_WAIT",
+            ReservationState::TimeToRun => "RESV_TIME_TO_RUN",
+            ReservationState::Running => "RESV_RUNNING",
+            ReservationState::Finished => "RESV_FINISHED",
+            ReservationState::BeingDeleted => "RESV_BEING_DELETED",
+            ReservationState::Deleted => "RESV_DELETED",
+            ReservationState::DeletingJobs => "RESV_DELETING_JOBS",
+            ReservationState::Degraded => "RESV_DEGRADED",
+            ReservationState::BeingAltered => "RESV_BEING_ALTERED",
+            ReservationState::InConflict => "RESV_IN_CONFLICT",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Status {
+    /// reserve_state and reserve_substate of a reservation stat
+    pub fn resv_state(&self) -> Option<(ReservationState, Option<ReservationState>)> {
+        let get = |name: &str| match self.attribs().get(name) {
+            Some(Attrl::Value(v)) => v.val().parse().ok(),
+            _ => None,
+        };
+        get(reservation::RESERVE_STATE).map(|s| (s, get(reservation::RESERVE_SUBSTATE)))
+    }
+}
+
+/// Builder for changes to an existing reservation, the pbs_ralter equivalent
+///
+/// Times are seconds since the epoch. For standing reservations the changes
+/// apply to the next (or currently running) occurrence.
+#[derive(Debug, Clone, Default)]
+pub struct ResvModification {
+    name: Option<String>,
+    start: Option<i64>,
+    end: Option<i64>,
+    duration: Option<i64>,
+    select: Option<String>,
+    users: Option<Vec<String>>,
+    groups: Option<Vec<String>>,
+}
+
+impl ResvModification {
+    pub fn new() -> ResvModification {
+        ResvModification::default()
+    }
+    pub fn name(mut self, name: &str) -> ResvModification {
+        self.name = Some(name.to_string());
+        self
+    }
+    pub fn start(mut self, start: i64) -> ResvModification {
+        self.start = Some(start);
+        self
+    }
+    pub fn end(mut self, end: i64) -> ResvModification {
+        self.end = Some(end);
+        self
+    }
+    /// duration in seconds
+    pub fn duration(mut self, duration: i64) -> ResvModification {
+        self.duration = Some(duration);
+        self
+    }
+    /// new select, the server decides whether a running reservation can take it
+    pub fn select(mut self, select: &str) -> ResvModification {
+        self.select = Some(select.to_string());
+        self
+    }
+    /// replace the Authorized_Users ACL
+    pub fn users<S: AsRef<str>>(mut self, users: &[S]) -> ResvModification {
+        self.users = Some(users.iter().map(|u| u.as_ref().to_string()).collect());
+        self
+    }
+    /// replace the Authorized_Groups ACL
+    pub fn groups<S: AsRef<str>>(mut self, groups: &[S]) -> ResvModification {
+        self.groups = Some(groups.iter().map(|g| g.as_ref().to_string()).collect());
+        self
+    }
+
+    /// build the attribute list to pass to Server::mod_resv
+    pub fn attribs(&self) -> Result<Attribs, String> {
+        if let (Some(s), Some(e)) = (self.start, self.end) {
+            if e <= s {
+                return Err("reservation must end after it starts".to_string());
+            }
+            if matches!(self.duration, Some(d) if e - s != d) {
+                return Err("reservation end doesn't match start + duration".to_string());
+            }
+        }
+        if matches!(self.duration, Some(d) if d <= 0) {
+            return Err("reservation duration must be positive".to_string());
+        }
+        let mut attribs = Attribs::new();
+        let mut set =
+            |name: &str, val: String| attribs.add(name.to_string(), Attrl::Value(Op::Set(val)));
+        if let Some(s) = self.start {
+            set(reservation::RESERVE_START, s.to_string());
+        }
+        if let Some(e) = self.end {
+            set(reservation::RESERVE_END, e.to_string());
+        }
+        if let Some(d) = self.duration {
+            set(reservation::RESERVE_DURATION, d.to_string());
+        }
+        if let Some(n) = &self.name {
+            set(reservation::RESERVE_NAME, n.clone());
+        }
+        if let Some(u) = &self.users {
+            set(reservation::AUTHORIZED_USERS, u.join(","));
+        }
+        if let Some(g) = &self.groups {
+            set(reservation::AUTHORIZED_GROUPS, g.join(","));
+        }
+        if let Some(s) = &self.select {
+            let mut map = BTreeMap::new();
+            map.insert("select".to_string(), Op::Set(s.clone()));
+            attribs.add(reservation::RESOURCE_LIST.to_string(), Attrl::Resource(map));
+        }
+        if attribs.attribs().is_empty() {
+            return Err("no reservation modifications requested".to_string());
+        }
+        Ok(attribs)
+    }
+}
+
+/// Check a reservation modification only touches attributes pbs_ralter can change
+///
+/// If the reservation's current state is known, changes PBS refuses for that
+/// state are rejected too (e.g. moving the start of a running reservation).
+pub(crate) fn validate_resv_mod(
+    attribs: &Attribs,
+    state: Option<ReservationState>,
+) -> Result<(), String> {
+    let allowed = [
+        reservation::RESERVE_START,
+        reservation::RESERVE_END,
+        reservation::RESERVE_DURATION,
+        reservation::RESERVE_NAME,
+        reservation::AUTHORIZED_USERS,
+        reservation::AUTHORIZED_GROUPS,
+        reservation::MAIL_POINTS,
+        reservation::MAIL_USERS,
+        reservation::INTERACTIVE,
+        reservation::RESOURCE_LIST,
+    ];
+    for (name, val) in attribs.attribs() {
+        if !allowed.contains(&name.as_str()) {
+            return Err(format!("reservation attribute {name} can't be modified"));
+        }
+        let ops: Vec<&Op> = match val {
+            Attrl::Value(op) => vec![op],
+            Attrl::Resource(map) => {
+                if let Some(r) = map.keys().find(|r| *r != "select") {
+                    return Err(format!("reservation resource {name}.{r} can't be modified"));
+                }
+                map.values().collect()
+            }
+        };
+        if let Some(op) = ops
+            .iter()
+            .find(|op| !matches!(op, Op::Set(_) | Op::Default(_)))
+        {
+            return Err(format!(
+                "reservation attribute {name} can only be set, not {op:?}"
+            ));
+        }
+    }
+    if let Some(state) = state {
+        if state.is_terminal() {
+            return Err(format!("can't modify a reservation in state {state}"));
+        }
+        if state == ReservationState::BeingAltered {
+            return Err("reservation is already being altered".to_string());
+        }
+        if state == ReservationState::Running && attribs.get(reservation::RESERVE_START).is_some() {
+            return Err("can't change the start time of a running reservation".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of a reservation modification, as reported by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResvModStatus {
+    /// change was confirmed and applied
+    Modified,
+    /// change was accepted and is waiting for the scheduler to confirm it
+    Requested,
+    /// scheduler couldn't fit the change, reservation is unchanged
+    Denied,
+}
+
+/// Parsed response to a reservation modification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResvModResponse {
+    pub id: String,
+    pub status: ResvModStatus,
+    /// the server's raw message
+    pub message: String,
+}
+
+impl ResvModResponse {
+    /// parse the reply to pbs_modify_resv, "<id> CONFIRMED", "<id> ALTER REQUESTED",
+    /// "<id> UNCONFIRMED" or "<id> DENIED"
+    pub(crate) fn parse(resv: &str, msg: &str) -> ResvModResponse {
+        let (id, status) = match msg.trim().split_once(char::is_whitespace) {
+            Some((id, status)) => (id, status.trim()),
+            None => (resv, msg.trim()),
+        };
+        let status = match status.to_uppercase().as_str() {
+            "DENIED" => ResvModStatus::Denied,
+            "ALTER REQUESTED" | "UNCONFIRMED" => ResvModStatus::Requested,
+            _ => ResvModStatus::Modified,
+        };
+        ResvModResponse {
+            id: id.to_string(),
+            status,
+            message: msg.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Frequency;
+
+    fn standing(tz: &str) -> ReservationSpec {
+        ReservationSpec::new()
+            .start(1767603600)
+            .duration(3600)
+            .select("1:ncpus=1")
+            .rrule(Rrule::new(Frequency::Daily).count(3))
+            .timezone(tz)
+    }
+
+    #[test]
+    fn occurrences_in_utc() {
+        let day = 24 * 3600;
+        let start = 1767603600;
+        assert_eq!(
+            standing("Etc/UTC").occurrences(10),
+            Ok(vec![
+                (start, start + 3600),
+                (start + day, start + day + 3600),
+                (start + 2 * day, start + 2 * day + 3600)
+            ])
+        );
+    }
+
+    fn utc(m: u32, d: u32, h: u32, min: u32) -> i64 {
+        chrono::Utc
+            .with_ymd_and_hms(2026, m, d, h, min, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    fn starts(spec: ReservationSpec) -> Vec<i64> {
+        spec.occurrences(10)
+            .unwrap()
+            .into_iter()
+            .map(|(s, _)| s)
+            .collect()
+    }
+
+    #[test]
+    fn occurrences_across_dst() {
+        // 09:00 in Denver is 16:00 UTC in MST and 15:00 UTC in MDT, from March 8th
+        let spec = standing("America/Denver").start(utc(3, 7, 16, 0));
+        assert!(spec.validate().is_ok());
+        assert_eq!(
+            starts(spec),
+            [utc(3, 7, 16, 0), utc(3, 8, 15, 0), utc(3, 9, 15, 0)]
+        );
+        let spec = standing("America/Denver").start(utc(10, 31, 15, 0));
+        assert_eq!(
+            starts(spec),
+            [utc(10, 31, 15, 0), utc(11, 1, 16, 0), utc(11, 2, 16, 0)]
+        );
+    }
+
+    #[test]
+    fn occurrences_in_dst_gap_and_fold() {
+        // 02:30 doesn't exist on March 8th, it runs an hour later at 03:30 MDT
+        let spec = standing("America/Denver").start(utc(3, 7, 9, 30));
+        assert_eq!(
+            starts(spec),
+            [utc(3, 7, 9, 30), utc(3, 8, 9, 30), utc(3, 9, 8, 30)]
+        );
+        // 01:30 happens twice on November 1st, the first one is used
+        let spec = standing("America/Denver").start(utc(10, 31, 7, 30));
+        assert_eq!(
+            starts(spec),
+            [utc(10, 31, 7, 30), utc(11, 1, 7, 30), utc(11, 2, 8, 30)]
+        );
+    }
+
+    #[test]
+    fn resv_mod_responses() {
+        for (msg, id, status) in [
+            ("R123.svr CONFIRMED", "R123.svr", ResvModStatus::Modified),
+            (
+                "R123.svr ALTER REQUESTED",
+                "R123.svr",
+                ResvModStatus::Requested,
+            ),
+            ("R123.svr UNCONFIRMED", "R123.svr", ResvModStatus::Requested),
+            ("R123.svr DENIED", "R123.svr", ResvModStatus::Denied),
+            (
+                "S45.svr ALTER REQUESTED",
+                "S45.svr",
+                ResvModStatus::Requested,
+            ),
+            ("", "R123", ResvModStatus::Modified),
+        ] {
+            let resp = ResvModResponse::parse("R123", msg);
+            assert_eq!((resp.id.as_str(), resp.status), (id, status), "{msg:?}");
+            assert_eq!(resp.message, msg);
+        }
+    }
+
+    #[test]
+    fn unknown_timezone() {
+        assert!(standing("Mars/Olympus_Mons").validate().is_err());
+        assert!(standing("Mars/Olympus_Mons").occurrences(1).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 /// Different types of resources in PBS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Resource {
     Hostname,
     Que,
@@ -0,0 +1,211 @@
+use crate::attributes::{resource, ValueType};
+use crate::types::{Attrl, Status};
+use std::fmt;
+use std::str::FromStr;
+
+/// Type of a resource, as given to `qmgr create resource ... type=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceType {
+    Long,
+    Float,
+    Size,
+    String,
+    Boolean,
+    StringArray,
+}
+
+impl ResourceType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResourceType::Long => "long",
+            ResourceType::Float => "float",
+            ResourceType::Size => "size",
+            ResourceType::String => "string",
+            ResourceType::Boolean => "boolean",
+            ResourceType::StringArray => "string_array",
+        }
+    }
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            ResourceType::Long => ValueType::Long,
+            ResourceType::Float => ValueType::Float,
+            ResourceType::Size => ValueType::Size,
+            ResourceType::String => ValueType::String,
+            ResourceType::Boolean => ValueType::Boolean,
+            ResourceType::StringArray => ValueType::StringArray,
+        }
+    }
+}
+
+impl FromStr for ResourceType {
+    type Err = String;
+    /// parse a type name, or the numeric type stat_resource returns
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // numeric codes are the server's ATR_TYPE_* values
+        match s.trim() {
+            "1" | "long" => Ok(ResourceType::Long),
+            "3" | "string" => Ok(ResourceType::String),
+            "4" | "string_array" => Ok(ResourceType::StringArray),
+            "5" | "size" => Ok(ResourceType::Size),
+            "11" | "boolean" => Ok(ResourceType::Boolean),
+            "14" | "float" => Ok(ResourceType::Float),
+            t => Err(format!("unknown resource type {t}")),
+        }
+    }
+}
+
+impl fmt::Display for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// ATR_DFLAG_* bits stat_resource reports flags as
+const DFLAG_USRD: u32 = 0x01;
+const DFLAG_USWR: u32 = 0x02;
+const DFLAG_MOM: u32 = 0x400;
+const DFLAG_RASSN: u32 = 0x4000;
+const DFLAG_ANASSN: u32 = 0x8000;
+const DFLAG_FNASSN: u32 = 0x10000;
+const DFLAG_CVTSLT: u32 = 0x20000;
+
+/// Flags of a resource, as given to `qmgr create resource ... flag=`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ResourceFlags {
+    /// h, host level, requested inside select chunks
+    pub host: bool,
+    /// n, consumable on every vnode of a job
+    pub consumable: bool,
+    /// f, consumable on the first vnode of a job only
+    pub consumable_first: bool,
+    /// q, consumable at the server and queue level
+    pub consumable_server: bool,
+    /// m, sent to the MoMs
+    pub mom: bool,
+    /// i, invisible to users and operators
+    pub invisible: bool,
+    /// r, read only for users and operators
+    pub read_only: bool,
+}
+
+impl ResourceFlags {
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceFlags::default()
+    }
+    /// decode the numeric flags stat_resource returns
+    fn from_bits(bits: u32) -> ResourceFlags {
+        ResourceFlags {
+            host: bits & DFLAG_CVTSLT != 0,
+            consumable: bits & DFLAG_ANASSN != 0,
+            consumable_first: bits & DFLAG_FNASSN != 0,
+            consumable_server: bits & DFLAG_RASSN != 0,
+            mom: bits & DFLAG_MOM != 0,
+            invisible: bits & DFLAG_USRD == 0,
+            read_only: bits & DFLAG_USRD != 0 && bits & DFLAG_USWR == 0,
+        }
+    }
+}
+
+impl FromStr for ResourceFlags {
+    type Err = String;
+    /// parse flag letters, e.g. "nh", or the numeric flags stat_resource returns
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(bits) = s.parse() {
+            return Ok(ResourceFlags::from_bits(bits));
+        }
+        let mut flags = ResourceFlags::default();
+        for c in s.chars() {
+            match c {
+                'h' => flags.host = true,
+                'n' => flags.consumable = true,
+                'f' => flags.consumable_first = true,
+                'q' => flags.consumable_server = true,
+                'm' => flags.mom = true,
+                'i' => flags.invisible = true,
+                'r' => flags.read_only = true,
+                c => return Err(format!("unknown resource flag {c}")),
+            }
+        }
+        if flags.consumable && flags.consumable_first {
+            return Err("resource flags n and f can't be combined".to_string());
+        }
+        if flags.invisible && flags.read_only {
+            return Err("resource flags i and r can't be combined".to_string());
+        }
+        Ok(flags)
+    }
+}
+
+impl fmt::Display for ResourceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (set, c) in [
+            (self.host, 'h'),
+            (self.consumable, 'n'),
+            (self.consumable_first, 'f'),
+            (self.consumable_server, 'q'),
+            (self.mom, 'm'),
+            (self.invisible, 'i'),
+            (self.read_only, 'r'),
+        ] {
+            if set {
+                write!(f, "{c}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Definition of a custom or built in resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceDef {
+    pub name: String,
+    pub resource_type: ResourceType,
+    pub flags: ResourceFlags,
+}
+
+impl ResourceDef {
+    pub fn new(name: &str, resource_type: ResourceType) -> ResourceDef {
+        ResourceDef {
+            name: name.to_string(),
+            resource_type,
+            flags: ResourceFlags::default(),
+        }
+    }
+    /// set the flags from letters, e.g. "nh"
+    pub fn flags(mut self, flags: &str) -> Result<ResourceDef, String> {
+        self.flags = flags.parse()?;
+        Ok(self)
+    }
+    /// type and flag entries for pbs_manager
+    pub(crate) fn entries(&self) -> Vec<(String, String)> {
+        let mut out = vec![(resource::TYPE.to_string(), self.resource_type.to_string())];
+        if !self.flags.is_empty() {
+            out.push((resource::FLAG.to_string(), self.flags.to_string()));
+        }
+        out
+    }
+}
+
+impl TryFrom<&Status> for ResourceDef {
+    type Error = String;
+    /// a resource from stat_resource
+    fn try_from(s: &Status) -> Result<Self, Self::Error> {
+        let get = |attr: &str| match s.attribs().get(attr) {
+            Some(Attrl::Value(v)) => Some(v.val()),
+            _ => None,
+        };
+        let resource_type = get(resource::TYPE)
+            .ok_or_else(|| format!("resource {} has no type", s.name()))?
+            .parse()?;
+        let flags = match get(resource::FLAG) {
+            Some(f) => f.parse()?,
+            None => ResourceFlags::default(),
+        };
+        Ok(ResourceDef {
+            name: s.name(),
+            resource_type,
+            flags,
+        })
+    }
+}
@@ -0,0 +1,598 @@
+/// iCalendar (RFC 5545) recurrence rules, as used by PBS standing reservations
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use std::fmt;
+use std::str::FromStr;
+
+// give up expanding a rule after this many consecutive periods without a match
+// e.g. FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30 never matches
+const MAX_EMPTY_PERIODS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+}
+
+impl Frequency {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Frequency::Yearly => "YEARLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Daily => "DAILY",
+            Frequency::Hourly => "HOURLY",
+            Frequency::Minutely => "MINUTELY",
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "YEARLY" => Ok(Frequency::Yearly),
+            "MONTHLY" => Ok(Frequency::Monthly),
+            "WEEKLY" => Ok(Frequency::Weekly),
+            "DAILY" => Ok(Frequency::Daily),
+            "HOURLY" => Ok(Frequency::Hourly),
+            "MINUTELY" => Ok(Frequency::Minutely),
+            _ => Err(format!("unsupported FREQ: {s}")),
+        }
+    }
+}
+
+/// A recurrence rule, e.g. `FREQ=MONTHLY;BYDAY=1TU;COUNT=12`
+///
+/// Supports the FREQ, INTERVAL, COUNT, UNTIL, BYMONTH, BYMONTHDAY, BYDAY and
+/// BYHOUR parts. BYDAY ordinals (e.g. `-1FR`) are interpreted within the month.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rrule {
+    freq: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDateTime>,
+    by_month: Vec<u32>,
+    by_month_day: Vec<i32>,
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_hour: Vec<u32>,
+}
+
+impl Rrule {
+    pub fn new(freq: Frequency) -> Rrule {
+        Rrule {
+            freq,
+            interval: 1,
+            count: None,
+            until: None,
+            by_month: Vec::new(),
+            by_month_day: Vec::new(),
+            by_day: Vec::new(),
+            by_hour: Vec::new(),
+        }
+    }
+    pub fn interval(mut self, interval: u32) -> Rrule {
+        self.interval = interval;
+        self
+    }
+    pub fn count(mut self, count: u32) -> Rrule {
+        self.count = Some(count);
+        self
+    }
+    pub fn until(mut self, until: NaiveDateTime) -> Rrule {
+        self.until = Some(until);
+        self
+    }
+    pub fn by_month(mut self, months: &[u32]) -> Rrule {
+        self.by_month = months.to_vec();
+        self
+    }
+    pub fn by_month_day(mut self, days: &[i32]) -> Rrule {
+        self.by_month_day = days.to_vec();
+        self
+    }
+    /// add a weekday, optionally restricted to the nth (or -nth) one in the month
+    pub fn by_day(mut self, ordinal: Option<i32>, day: Weekday) -> Rrule {
+        self.by_day.push((ordinal, day));
+        self
+    }
+    pub fn by_hour(mut self, hours: &[u32]) -> Rrule {
+        self.by_hour = hours.to_vec();
+        self
+    }
+    pub fn freq(&self) -> Frequency {
+        self.freq
+    }
+    pub fn get_count(&self) -> Option<u32> {
+        self.count
+    }
+    pub fn get_until(&self) -> Option<NaiveDateTime> {
+        self.until
+    }
+
+    /// check the rule is well formed
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err("INTERVAL must be greater than 0".to_string());
+        }
+        if self.count.is_some() && self.until.is_some() {
+            return Err("COUNT and UNTIL are mutually exclusive".to_string());
+        }
+        if self.count == Some(0) {
+            return Err("COUNT must be greater than 0".to_string());
+        }
+        if let Some(m) = self.by_month.iter().find(|m| !(1..=12).contains(*m)) {
+            return Err(format!("invalid BYMONTH value: {m}"));
+        }
+        if let Some(d) = self
+            .by_month_day
+            .iter()
+            .find(|d| **d == 0 || !(-31..=31).contains(*d))
+        {
+            return Err(format!("invalid BYMONTHDAY value: {d}"));
+        }
+        if let Some((o, _)) = self
+            .by_day
+            .iter()
+            .find(|(o, _)| matches!(o, Some(n) if *n == 0 || !(-5..=5).contains(n)))
+        {
+            return Err(format!("invalid BYDAY ordinal: {}", o.unwrap()));
+        }
+        if let Some(h) = self.by_hour.iter().find(|h| **h > 23) {
+            return Err(format!("invalid BYHOUR value: {h}"));
+        }
+        Ok(())
+    }
+
+    /// Expand the rule starting at `start`, returning at most `limit` occurrences
+    ///
+    /// `start` is always the first occurrence if it matches the rule, as in PBS.
+    pub fn occurrences(&self, start: NaiveDateTime, limit: usize) -> Vec<NaiveDateTime> {
+        let mut out = Vec::new();
+        let max = self
+            .count
+            .map(|c| c as usize)
+            .unwrap_or(usize::MAX)
+            .min(limit);
+        let mut empty = 0;
+        let mut period = 0;
+        while out.len() < max && empty < MAX_EMPTY_PERIODS {
+            let (period_start, mut set) = match self.period(start, period) {
+                Some(p) => p,
+                None => break,
+            };
+            if let Some(until) = self.until {
+                if period_start > until {
+                    break;
+                }
+            }
+            set.sort();
+            set.dedup();
+            if set.is_empty() {
+                empty += 1;
+            } else {
+                empty = 0;
+            }
+            for dt in set {
+                if dt < start {
+                    continue;
+                }
+                if matches!(self.until, Some(until) if dt > until) {
+                    return out;
+                }
+                out.push(dt);
+                if out.len() >= max {
+                    return out;
+                }
+            }
+            period += 1;
+        }
+        out
+    }
+
+    // candidate occurrences for the nth period after start, and the beginning of that period
+    fn period(&self, start: NaiveDateTime, n: u32) -> Option<(NaiveDateTime, Vec<NaiveDateTime>)> {
+        let step = n.checked_mul(self.interval)?;
+        let midnight = NaiveTime::MIN;
+        match self.freq {
+            Frequency::Yearly => {
+                let year = start.year().checked_add(step as i32)?;
+                let months = if !self.by_month.is_empty() {
+                    self.by_month.clone()
+                } else if self.by_month_day.is_empty() && self.by_day.is_empty() {
+                    vec![start.month()]
+                } else {
+                    (1..=12).collect()
+                };
+                let mut days = Vec::new();
+                for m in months {
+                    days.append(&mut self.month_days(start, year, m));
+                }
+                let period_start = NaiveDate::from_ymd_opt(year, 1, 1)?.and_time(midnight);
+                Some((period_start, self.with_times(start, days)))
+            }
+            Frequency::Monthly => {
+                let months = start.year() as i64 * 12 + start.month0() as i64 + step as i64;
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = months.rem_euclid(12) as u32 + 1;
+                let period_start = NaiveDate::from_ymd_opt(year, month, 1)?.and_time(midnight);
+                if !self.by_month.is_empty() && !self.by_month.contains(&month) {
+                    return Some((period_start, Vec::new()));
+                }
+                let days = self.month_days(start, year, month);
+                Some((period_start, self.with_times(start, days)))
+            }
+            Frequency::Weekly => {
+                let monday = start.date()
+                    - Duration::days(start.weekday().num_days_from_monday() as i64)
+                    + Duration::weeks(step as i64);
+                let days: Vec<NaiveDate> = if self.by_day.is_empty() {
+                    vec![monday + Duration::days(start.weekday().num_days_from_monday() as i64)]
+                } else {
+                    self.by_day
+                        .iter()
+                        .map(|(_, d)| monday + Duration::days(d.num_days_from_monday() as i64))
+                        .collect()
+                };
+                let days = days
+                    .into_iter()
+                    .filter(|d| self.by_month.is_empty() || self.by_month.contains(&d.month()))
+                    .collect();
+                Some((monday.and_time(midnight), self.with_times(start, days)))
+            }
+            Frequency::Daily => {
+                let day = start.date() + Duration::days(step as i64);
+                let days = if self.matches_date(day) {
+                    vec![day]
+                } else {
+                    Vec::new()
+                };
+                Some((day.and_time(midnight), self.with_times(start, days)))
+            }
+            Frequency::Hourly | Frequency::Minutely => {
+                let dt = if self.freq == Frequency::Hourly {
+                    start + Duration::hours(step as i64)
+                } else {
+                    start + Duration::minutes(step as i64)
+                };
+                let set = if self.matches_date(dt.date())
+                    && (self.by_hour.is_empty() || self.by_hour.contains(&dt.hour()))
+                {
+                    vec![dt]
+                } else {
+                    Vec::new()
+                };
+                Some((dt, set))
+            }
+        }
+    }
+
+    // days in the given month selected by BYMONTHDAY/BYDAY, or start's day of month if neither is set
+    fn month_days(&self, start: NaiveDateTime, year: i32, month: u32) -> Vec<NaiveDate> {
+        let len = days_in_month(year, month);
+        if self.by_month_day.is_empty() && self.by_day.is_empty() {
+            return NaiveDate::from_ymd_opt(year, month, start.day())
+                .into_iter()
+                .collect();
+        }
+        let by_month_day: Vec<u32> = self
+            .by_month_day
+            .iter()
+            .filter_map(|d| resolve_month_day(*d, len))
+            .collect();
+        let mut by_day = Vec::new();
+        for (ordinal, weekday) in &self.by_day {
+            let matching: Vec<u32> = (1..=len)
+                .filter(|d| {
+                    NaiveDate::from_ymd_opt(year, month, *d)
+                        .map(|x| x.weekday() == *weekday)
+                        .unwrap_or(false)
+                })
+                .collect();
+            match ordinal {
+                None => by_day.extend(matching),
+                Some(o) if *o > 0 => by_day.extend(matching.get(*o as usize - 1)),
+                Some(o) => {
+                    let back = o.unsigned_abs() as usize;
+                    if back <= matching.len() {
+                        by_day.push(matching[matching.len() - back]);
+                    }
+                }
+            }
+        }
+        let days: Vec<u32> = if self.by_day.is_empty() {
+            by_month_day
+        } else if self.by_month_day.is_empty() {
+            by_day
+        } else {
+            by_day
+                .into_iter()
+                .filter(|d| by_month_day.contains(d))
+                .collect()
+        };
+        days.into_iter()
+            .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+            .collect()
+    }
+
+    // check a date against the BYMONTH, BYMONTHDAY and BYDAY filters, ignoring ordinals
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        let len = days_in_month(date.year(), date.month());
+        (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            && (self.by_month_day.is_empty()
+                || self
+                    .by_month_day
+                    .iter()
+                    .any(|d| resolve_month_day(*d, len) == Some(date.day())))
+            && (self.by_day.is_empty() || self.by_day.iter().any(|(_, d)| *d == date.weekday()))
+    }
+
+    // combine candidate days with start's time of day, or each BYHOUR if set
+    fn with_times(&self, start: NaiveDateTime, days: Vec<NaiveDate>) -> Vec<NaiveDateTime> {
+        let mut out = Vec::new();
+        for day in days {
+            if self.by_hour.is_empty() {
+                out.push(day.and_time(start.time()));
+            } else {
+                for h in &self.by_hour {
+                    if let Some(t) = NaiveTime::from_hms_opt(*h, start.minute(), start.second()) {
+                        out.push(day.and_time(t));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (y, m) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(y, m, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(31)
+}
+
+// turn a possibly negative BYMONTHDAY into a day of the month
+fn resolve_month_day(day: i32, len: u32) -> Option<u32> {
+    let d = if day < 0 { len as i32 + day + 1 } else { day };
+    if d >= 1 && d as u32 <= len {
+        Some(d as u32)
+    } else {
+        None
+    }
+}
+
+fn weekday_str(d: Weekday) -> &'static str {
+    match d {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    match s.to_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("invalid weekday: {s}")),
+    }
+}
+
+fn parse_list<T: FromStr>(key: &str, val: &str) -> Result<Vec<T>, String> {
+    val.split(',')
+        .map(|v| {
+            v.trim()
+                .parse()
+                .map_err(|_| format!("invalid {key} value: {v}"))
+        })
+        .collect()
+}
+
+fn parse_until(val: &str) -> Result<NaiveDateTime, String> {
+    let v = val.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(v, "%Y%m%dT%H%M%S")
+        .or_else(|_| NaiveDate::parse_from_str(v, "%Y%m%d").map(|d| d.and_time(NaiveTime::MIN)))
+        .map_err(|_| format!("invalid UNTIL value: {val}"))
+}
+
+impl FromStr for Rrule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches("RRULE:");
+        let mut freq = None;
+        let mut rule = Rrule::new(Frequency::Daily);
+        for part in s.split(';').filter(|p| !p.is_empty()) {
+            let (key, val) = part
+                .split_once('=')
+                .ok_or_else(|| format!("invalid rrule part: {part}"))?;
+            match key.to_uppercase().as_str() {
+                "FREQ" => freq = Some(val.parse()?),
+                "INTERVAL" => {
+                    rule.interval = val
+                        .parse()
+                        .map_err(|_| format!("invalid INTERVAL value: {val}"))?
+                }
+                "COUNT" => {
+                    rule.count = Some(
+                        val.parse()
+                            .map_err(|_| format!("invalid COUNT value: {val}"))?,
+                    )
+                }
+                "UNTIL" => rule.until = Some(parse_until(val)?),
+                "BYMONTH" => rule.by_month = parse_list(key, val)?,
+                "BYMONTHDAY" => rule.by_month_day = parse_list(key, val)?,
+                "BYHOUR" => rule.by_hour = parse_list(key, val)?,
+                "BYDAY" => {
+                    for d in val.split(',') {
+                        let d = d.trim();
+                        if d.len() < 2 {
+                            return Err(format!("invalid BYDAY value: {d}"));
+                        }
+                        let (ord, day) = d.split_at(d.len() - 2);
+                        let ord = if ord.is_empty() {
+                            None
+                        } else {
+                            Some(
+                                ord.trim_start_matches('+')
+                                    .parse()
+                                    .map_err(|_| format!("invalid BYDAY value: {d}"))?,
+                            )
+                        };
+                        rule.by_day.push((ord, parse_weekday(day)?));
+                    }
+                }
+                _ => return Err(format!("unsupported rrule part: {key}")),
+            }
+        }
+        rule.freq = freq.ok_or_else(|| "rrule is missing FREQ".to_string())?;
+        rule.validate()?;
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for Rrule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: ToString>(v: &[T]) -> String {
+            v.iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+        write!(f, "FREQ={}", self.freq.as_str())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_month.is_empty() {
+            write!(f, ";BYMONTH={}", join(&self.by_month))?;
+        }
+        if !self.by_month_day.is_empty() {
+            write!(f, ";BYMONTHDAY={}", join(&self.by_month_day))?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|(o, d)| match o {
+                    Some(o) => format!("{o}{}", weekday_str(*d)),
+                    None => weekday_str(*d).to_string(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if !self.by_hour.is_empty() {
+            write!(f, ";BYHOUR={}", join(&self.by_hour))?;
+        }
+        if let Some(c) = self.count {
+            write!(f, ";COUNT={c}")?;
+        }
+        if let Some(u) = self.until {
+            write!(f, ";UNTIL={}", u.format("%Y%m%dT%H%M%S"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap()
+    }
+
+    fn expand(rule: &str, start: NaiveDateTime) -> Vec<NaiveDateTime> {
+        rule.parse::<Rrule>().unwrap().occurrences(start, 100)
+    }
+
+    #[test]
+    fn count() {
+        assert_eq!(
+            expand("FREQ=DAILY;COUNT=3", at(2026, 1, 5)),
+            vec![at(2026, 1, 5), at(2026, 1, 6), at(2026, 1, 7)]
+        );
+    }
+
+    #[test]
+    fn until_is_inclusive() {
+        assert_eq!(
+            expand("FREQ=WEEKLY;UNTIL=20260126T090000", at(2026, 1, 5)),
+            vec![
+                at(2026, 1, 5),
+                at(2026, 1, 12),
+                at(2026, 1, 19),
+                at(2026, 1, 26)
+            ]
+        );
+    }
+
+    #[test]
+    fn weekly_by_day() {
+        assert_eq!(
+            expand("FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=5", at(2026, 1, 5)),
+            vec![
+                at(2026, 1, 5),
+                at(2026, 1, 7),
+                at(2026, 1, 9),
+                at(2026, 1, 12),
+                at(2026, 1, 14)
+            ]
+        );
+    }
+
+    #[test]
+    fn start_not_matching_is_skipped() {
+        assert_eq!(
+            expand("FREQ=WEEKLY;BYDAY=TU;COUNT=2", at(2026, 1, 5)),
+            vec![at(2026, 1, 6), at(2026, 1, 13)]
+        );
+    }
+
+    #[test]
+    fn monthly_by_day_ordinals() {
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3", at(2026, 1, 5)),
+            vec![at(2026, 1, 30), at(2026, 2, 27), at(2026, 3, 27)]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=1TU;COUNT=2", at(2026, 1, 5)),
+            vec![at(2026, 1, 6), at(2026, 2, 3)]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let rule = "FREQ=MONTHLY;INTERVAL=2;BYDAY=1TU,-1FR;COUNT=12";
+        assert_eq!(rule.parse::<Rrule>().unwrap().to_string(), rule);
+        let rule = "FREQ=WEEKLY;BYDAY=MO;UNTIL=20260126T090000";
+        assert_eq!(rule.parse::<Rrule>().unwrap().to_string(), rule);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("COUNT=3".parse::<Rrule>().is_err());
+        assert!("FREQ=DAILY;COUNT=3;UNTIL=20260126"
+            .parse::<Rrule>()
+            .is_err());
+        assert!("FREQ=MONTHLY;BYDAY=6MO".parse::<Rrule>().is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use crate::attributes::{self, ValueType};
+use crate::helpers::{parse_bool, parse_duration, parse_size};
+use crate::types::{Attribs, Attrl, Op, Resource, Server};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What a set of attributes is going to be sent for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// creating an object, e.g. submit_job or submit_resv
+    Submit,
+    /// changing an existing object, e.g. alter_job or a pbs_manager set
+    Alter,
+    /// selection criteria, e.g. stat_job filters
+    Select,
+}
+
+/// Problem found by Attribs::validate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    UnknownAttribute,
+    UnknownResource,
+    ReadOnly,
+    /// a resource was given for an attribute that doesn't take resources
+    UnexpectedResource,
+    /// no resource was given for an attribute that takes resources
+    MissingResource,
+    InvalidValue {
+        expected: ValueType,
+        value: String,
+    },
+    InvalidSelect {
+        value: String,
+        reason: String,
+    },
+    IllegalOp(Op),
+}
+
+/// Problem with one attribute, or one resource of an attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// name or name.resource
+    pub attribute: String,
+    pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.attribute)?;
+        match &self.kind {
+            DiagnosticKind::UnknownAttribute => write!(f, "unknown attribute"),
+            DiagnosticKind::UnknownResource => write!(f, "resource isn't defined"),
+            DiagnosticKind::ReadOnly => write!(f, "attribute is read only"),
+            DiagnosticKind::UnexpectedResource => write!(f, "attribute doesn't take resources"),
+            DiagnosticKind::MissingResource => write!(f, "attribute needs a resource"),
+            DiagnosticKind::InvalidValue { expected, value } => {
+                write!(f, "expected a {} value, got {value:?}", expected.name())
+            }
+            DiagnosticKind::InvalidSelect { value, reason } => {
+                write!(f, "invalid select {value:?}, {reason}")
+            }
+            DiagnosticKind::IllegalOp(op) => write!(f, "{op:?} isn't allowed here"),
+        }
+    }
+}
+
+// resources every PBS server defines
+const BUILTIN_RESOURCES: [(&str, ValueType); 38] = [
+    ("accelerator", ValueType::Boolean),
+    ("accelerator_group", ValueType::String),
+    ("accelerator_memory", ValueType::Size),
+    ("accelerator_model", ValueType::String),
+    ("aoe", ValueType::StringArray),
+    ("arch", ValueType::String),
+    ("cpupercent", ValueType::Long),
+    ("cput", ValueType::Duration),
+    ("eoe", ValueType::String),
+    ("exec_vnode", ValueType::String),
+    ("file", ValueType::Size),
+    ("hbmem", ValueType::Size),
+    ("host", ValueType::String),
+    ("hpm", ValueType::Long),
+    ("max_walltime", ValueType::Duration),
+    ("mem", ValueType::Size),
+    ("min_walltime", ValueType::Duration),
+    ("mpiprocs", ValueType::Long),
+    ("naccelerators", ValueType::Long),
+    ("ncpus", ValueType::Long),
+    ("nice", ValueType::Long),
+    ("nodect", ValueType::Long),
+    ("nodes", ValueType::String),
+    ("ompthreads", ValueType::Long),
+    ("pcput", ValueType::Duration),
+    ("place", ValueType::String),
+    ("pmem", ValueType::Size),
+    ("preempt_targets", ValueType::StringArray),
+    ("pvmem", ValueType::Size),
+    ("select", ValueType::String),
+    ("site", ValueType::String),
+    ("soft_walltime", ValueType::Duration),
+    ("software", ValueType::String),
+    ("start_time", ValueType::Long),
+    ("vmem", ValueType::Size),
+    ("vnode", ValueType::String),
+    ("vntype", ValueType::StringArray),
+    ("walltime", ValueType::Duration),
+];
+
+/// The built in resources and their types
+pub fn builtin_resources() -> BTreeMap<String, ValueType> {
+    BUILTIN_RESOURCES
+        .iter()
+        .map(|(n, t)| (n.to_string(), *t))
+        .collect()
+}
+
+fn valid_value(value_type: ValueType, val: &str) -> bool {
+    match value_type {
+        ValueType::Long | ValueType::Time => val.trim().parse::<i64>().is_ok(),
+        ValueType::Float => val.trim().parse::<f64>().is_ok(),
+        ValueType::Boolean => parse_bool(val).is_some(),
+        ValueType::Size => parse_size(val).is_some(),
+        ValueType::Duration => parse_duration(val).is_some(),
+        ValueType::String | ValueType::StringArray | ValueType::Acl | ValueType::Resources => true,
+    }
+}
+
+// "2:ncpus=4:mem=10gb+ngpus=1", returns the reason it's invalid
+fn check_select(val: &str, resources: &BTreeMap<String, ValueType>) -> Result<(), String> {
+    for chunk in val.split('+') {
+        let mut parts = chunk.split(':').peekable();
+        if parts.peek().is_some_and(|p| !p.contains('=')) {
+            let count = parts.next().unwrap_or_default();
+            if count.parse::<u32>().map_or(true, |c| c == 0) {
+                return Err(format!("chunk count {count:?} isn't a positive number"));
+            }
+        }
+        for part in parts {
+            let (name, v) = part
+                .split_once('=')
+                .ok_or_else(|| format!("{part:?} isn't resource=value"))?;
+            match resources.get(name) {
+                None => return Err(format!("resource {name} isn't defined")),
+                Some(t) if !valid_value(*t, v) => {
+                    return Err(format!("{name}={v} isn't a {} value", t.name()))
+                }
+                _ => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn legal_op(op: &Op, operation: Operation) -> bool {
+    match operation {
+        Operation::Submit => matches!(op, Op::Set(_) | Op::Default(_)),
+        Operation::Alter => matches!(
+            op,
+            Op::Set(_) | Op::Default(_) | Op::Unset(_) | Op::Incr(_) | Op::Decr(_)
+        ),
+        Operation::Select => !matches!(op, Op::Set(_) | Op::Unset(_) | Op::Incr(_) | Op::Decr(_)),
+    }
+}
+
+// value syntax is only checked when there's a value to check
+fn has_value(op: &Op) -> bool {
+    !matches!(op, Op::Unset(_) | Op::Default(_)) || !op.val().is_empty()
+}
+
+impl Attribs {
+    /// Check attributes against the catalog before sending them to the server
+    ///
+    /// Resources are checked against the built in resources, use validate_with or
+    /// Server::validate to include site defined ones.
+    pub fn validate(&self, object: Resource, operation: Operation) -> Vec<Diagnostic> {
+        self.validate_with(object, operation, &builtin_resources())
+    }
+
+    /// Check attributes against the catalog and the given resource definitions
+    pub fn validate_with(
+        &self,
+        object: Resource,
+        operation: Operation,
+        resources: &BTreeMap<String, ValueType>,
+    ) -> Vec<Diagnostic> {
+        let mut out = Vec::new();
+        let mut report =
+            |attribute: String, kind: DiagnosticKind| out.push(Diagnostic { attribute, kind });
+        for (name, val) in self.attribs() {
+            let Some(def) = attributes::lookup_attribute(object, name) else {
+                report(name.clone(), DiagnosticKind::UnknownAttribute);
+                continue;
+            };
+            if def.is_read_only() && operation != Operation::Select {
+                report(name.clone(), DiagnosticKind::ReadOnly);
+            }
+            match val {
+                Attrl::Value(op) => {
+                    if !legal_op(op, operation) {
+                        report(name.clone(), DiagnosticKind::IllegalOp(op.clone()));
+                    }
+                    if def.takes_resources() {
+                        report(name.clone(), DiagnosticKind::MissingResource);
+                    } else if has_value(op) && !valid_value(def.value_type, &op.val()) {
+                        report(
+                            name.clone(),
+                            DiagnosticKind::InvalidValue {
+                                expected: def.value_type,
+                                value: op.val(),
+                            },
+                        );
+                    }
+                }
+                Attrl::Resource(map) => {
+                    for (res, op) in map {
+                        let attribute = format!("{name}.{res}");
+                        if !legal_op(op, operation) {
+                            report(attribute.clone(), DiagnosticKind::IllegalOp(op.clone()));
+                        }
+                        if !def.takes_resources() {
+                            report(attribute, DiagnosticKind::UnexpectedResource);
+                            continue;
+                        }
+                        let Some(t) = resources.get(res) else {
+                            report(attribute, DiagnosticKind::UnknownResource);
+                            continue;
+                        };
+                        if !has_value(op) {
+                            continue;
+                        }
+                        let v = op.val();
+                        if res == "select" {
+                            if let Err(reason) = check_select(&v, resources) {
+                                report(
+                                    attribute,
+                                    DiagnosticKind::InvalidSelect { value: v, reason },
+                                );
+                            }
+                        } else if !valid_value(*t, &v) {
+                            report(
+                                attribute,
+                                DiagnosticKind::InvalidValue {
+                                    expected: *t,
+                                    value: v,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Server {
+    /// Defined resources and their types, built in ones included
+    pub fn resource_types(&self) -> Result<BTreeMap<String, ValueType>, String> {
+        let mut out = builtin_resources();
+        for def in self.resource_defs()? {
+            // time resources such as walltime are reported as long, keep them as durations
+            out.entry(def.name)
+                .or_insert(def.resource_type.value_type());
+        }
+        Ok(out)
+    }
+
+    /// Attribs::validate including the server's site defined resources
+    pub fn validate(
+        &self,
+        attribs: &Attribs,
+        object: Resource,
+        operation: Operation,
+    ) -> Result<Vec<Diagnostic>, String> {
+        Ok(attribs.validate_with(object, operation, &self.resource_types()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::job;
+
+    fn attribs(values: &[(&str, Op)]) -> Attribs {
+        let mut a = Attribs::new();
+        for (key, op) in values {
+            match key.split_once('.') {
+                Some((name, res)) => {
+                    let map = BTreeMap::from([(res.to_string(), op.clone())]);
+                    a.add(name.to_string(), Attrl::Resource(map));
+                }
+                None => a.add(key.to_string(), Attrl::Value(op.clone())),
+            }
+        }
+        a
+    }
+
+    fn kinds(a: &Attribs, operation: Operation) -> Vec<(String, DiagnosticKind)> {
+        a.validate(Resource::Job, operation)
+            .into_iter()
+            .map(|d| (d.attribute, d.kind))
+            .collect()
+    }
+
+    fn set(v: &str) -> Op {
+        Op::Set(v.to_string())
+    }
+
+    #[test]
+    fn selects() {
+        let mut res = builtin_resources();
+        assert_eq!(
+            check_select("2:ncpus=4:mem=10gb+ncpus=1:host=n1", &res),
+            Ok(())
+        );
+        assert_eq!(
+            check_select("1:ncpus=4:ngpus=1", &res),
+            Err("resource ngpus isn't defined".to_string())
+        );
+        res.insert("ngpus".to_string(), ValueType::Long);
+        assert_eq!(check_select("1:ncpus=4:ngpus=1", &res), Ok(()));
+        assert_eq!(
+            check_select("0:ncpus=1", &res),
+            Err("chunk count \"0\" isn't a positive number".to_string())
+        );
+        assert!(check_select("two:ncpus=1", &res).is_err());
+        assert_eq!(
+            check_select("1:ncpus=1:mem", &res),
+            Err("\"mem\" isn't resource=value".to_string())
+        );
+        assert_eq!(
+            check_select("1:mem=lots", &res),
+            Err("mem=lots isn't a size value".to_string())
+        );
+        assert!(check_select("1:ncpus=4+", &res).is_err());
+    }
+
+    #[test]
+    fn legal_ops() {
+        let v = String::new;
+        let ops = [
+            (Op::Set(v()), [true, true, false]),
+            (Op::Default(v()), [true, true, true]),
+            (Op::Unset(v()), [false, true, false]),
+            (Op::Incr(v()), [false, true, false]),
+            (Op::Decr(v()), [false, true, false]),
+            (Op::Equal(v()), [false, false, true]),
+            (Op::NotEqual(v()), [false, false, true]),
+            (Op::GreaterThan(v()), [false, false, true]),
+            (Op::LessThan(v()), [false, false, true]),
+            (Op::EqualOrGreaterThan(v()), [false, false, true]),
+            (Op::EqualOrLessThan(v()), [false, false, true]),
+        ];
+        for (op, legal) in ops {
+            for (operation, ok) in [Operation::Submit, Operation::Alter, Operation::Select]
+                .into_iter()
+                .zip(legal)
+            {
+                assert_eq!(legal_op(&op, operation), ok, "{op:?} {operation:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn valid_submit() {
+        let a = attribs(&[
+            (job::JOB_NAME, set("run")),
+            ("Resource_List.select", set("2:ncpus=4:mem=8gb")),
+            ("Resource_List.walltime", set("01:00:00")),
+            ("Resource_List.software", set("matlab")),
+            ("Resource_List.nice", set("5")),
+            (job::PRIORITY, set("10")),
+        ]);
+        assert_eq!(kinds(&a, Operation::Submit), []);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let a = attribs(&[
+            ("Jbo_Name", set("run")),
+            (job::JOB_STATE, set("R")),
+            ("Job_Name.ncpus", set("4")),
+            ("Resource_List.ngpus", set("1")),
+            ("Resource_List.mem", set("lots")),
+            ("Resource_List.select", set("1:ncpus=x")),
+            (job::PRIORITY, Op::Incr("1".to_string())),
+        ]);
+        let mut got = kinds(&a, Operation::Submit);
+        got.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            got,
+            [
+                ("Jbo_Name".to_string(), DiagnosticKind::UnknownAttribute),
+                (
+                    "Job_Name.ncpus".to_string(),
+                    DiagnosticKind::UnexpectedResource
+                ),
+                (
+                    job::PRIORITY.to_string(),
+                    DiagnosticKind::IllegalOp(Op::Incr("1".to_string()))
+                ),
+                (
+                    "Resource_List.mem".to_string(),
+                    DiagnosticKind::InvalidValue {
+                        expected: ValueType::Size,
+                        value: "lots".to_string()
+                    }
+                ),
+                (
+                    "Resource_List.ngpus".to_string(),
+                    DiagnosticKind::UnknownResource
+                ),
+                (
+                    "Resource_List.select".to_string(),
+                    DiagnosticKind::InvalidSelect {
+                        value: "1:ncpus=x".to_string(),
+                        reason: "ncpus=x isn't a long value".to_string()
+                    }
+                ),
+                (job::JOB_STATE.to_string(), DiagnosticKind::ReadOnly),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_resource() {
+        let a = attribs(&[(job::RESOURCE_LIST, set("ncpus=4"))]);
+        assert_eq!(
+            kinds(&a, Operation::Submit),
+            [(
+                job::RESOURCE_LIST.to_string(),
+                DiagnosticKind::MissingResource
+            )]
+        );
+    }
+
+    #[test]
+    fn read_only_and_unset() {
+        let state = attribs(&[(job::JOB_STATE, Op::Equal("R".to_string()))]);
+        assert_eq!(kinds(&state, Operation::Select), []);
+        let state = attribs(&[(job::JOB_STATE, set("R"))]);
+        assert_eq!(
+            kinds(&state, Operation::Alter),
+            [(job::JOB_STATE.to_string(), DiagnosticKind::ReadOnly)]
+        );
+        // no value to check on an unset
+        let unset = attribs(&[
+            (job::PRIORITY, Op::Unset(String::new())),
+            ("Resource_List.walltime", Op::Unset(String::new())),
+        ]);
+        assert_eq!(kinds(&unset, Operation::Alter), []);
+    }
+
+    #[test]
+    fn display() {
+        let d = Diagnostic {
+            attribute: "Resource_List.mem".to_string(),
+            kind: DiagnosticKind::InvalidValue {
+                expected: ValueType::Size,
+                value: "lots".to_string(),
+            },
+        };
+        assert_eq!(
+            d.to_string(),
+            "Resource_List.mem: expected a size value, got \"lots\""
+        );
+    }
+}
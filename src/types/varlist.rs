@@ -0,0 +1,186 @@
+use crate::attributes::job;
+use crate::types::decode::split_escaped;
+use crate::types::{Attribs, Attrl, Op, Status};
+use std::fmt;
+use std::str::FromStr;
+
+/// A job's exported environment, the Variable_List attribute
+///
+/// Variables keep the order they were added in, setting an existing variable
+/// replaces its value in place.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VariableList {
+    vars: Vec<(String, String)>,
+}
+
+impl VariableList {
+    pub fn new() -> VariableList {
+        VariableList::default()
+    }
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn set(&mut self, name: &str, val: &str) {
+        match self.vars.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = val.to_string(),
+            None => self.vars.push((name.to_string(), val.to_string())),
+        }
+    }
+    /// remove a variable, returning its value if it was set
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let i = self.vars.iter().position(|(n, _)| n == name)?;
+        Some(self.vars.remove(i).1)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+    /// the PBS_O_* variables qsub sets from the submission environment
+    pub fn submit_env(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.iter().filter(|(n, _)| n.starts_with("PBS_O_"))
+    }
+}
+
+impl FromStr for VariableList {
+    type Err = String;
+    /// parse "NAME=value,NAME2=a\,b", a variable without '=' is set to ""
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut list = VariableList::new();
+        for var in split_escaped(s) {
+            if var.is_empty() {
+                continue;
+            }
+            let (name, val) = var.split_once('=').unwrap_or((var.as_str(), ""));
+            if name.is_empty() {
+                return Err(format!("variable without a name: {var:?}"));
+            }
+            list.set(name, val);
+        }
+        Ok(list)
+    }
+}
+
+impl fmt::Display for VariableList {
+    /// serialise with commas and backslashes in values escaped
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, val)) in self.vars.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{name}=")?;
+            for c in val.chars() {
+                if c == ',' || c == '\\' {
+                    write!(f, "\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Attribs {
+    /// set Variable_List, for submit_job or alter_job
+    pub fn set_variables(&mut self, vars: &VariableList) {
+        self.add(
+            job::VARIABLE_LIST.to_string(),
+            Attrl::Value(Op::Set(vars.to_string())),
+        );
+    }
+}
+
+impl Status {
+    /// a job's Variable_List, None if it's missing or can't be parsed
+    pub fn variables(&self) -> Option<VariableList> {
+        match self.attribs().get(job::VARIABLE_LIST) {
+            Some(Attrl::Value(v)) => v.val().parse().ok(),
+            _ => None,
+        }
+    }
+    fn variable(&self, name: &str) -> Option<String> {
+        self.variables()?.get(name).map(str::to_string)
+    }
+    /// directory qsub was run from
+    pub fn pbs_o_workdir(&self) -> Option<String> {
+        self.variable("PBS_O_WORKDIR")
+    }
+    /// host qsub was run on
+    pub fn pbs_o_host(&self) -> Option<String> {
+        self.variable("PBS_O_HOST")
+    }
+    pub fn pbs_o_home(&self) -> Option<String> {
+        self.variable("PBS_O_HOME")
+    }
+    pub fn pbs_o_logname(&self) -> Option<String> {
+        self.variable("PBS_O_LOGNAME")
+    }
+    pub fn pbs_o_path(&self) -> Option<String> {
+        self.variable("PBS_O_PATH")
+    }
+    pub fn pbs_o_shell(&self) -> Option<String> {
+        self.variable("PBS_O_SHELL")
+    }
+    pub fn pbs_o_system(&self) -> Option<String> {
+        self.variable("PBS_O_SYSTEM")
+    }
+    pub fn pbs_o_mail(&self) -> Option<String> {
+        self.variable("PBS_O_MAIL")
+    }
+    pub fn pbs_o_lang(&self) -> Option<String> {
+        self.variable("PBS_O_LANG")
+    }
+    /// queue the job was originally submitted to
+    pub fn pbs_o_queue(&self) -> Option<String> {
+        self.variable("PBS_O_QUEUE")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_escaped() {
+        let vars: VariableList = r"PBS_O_HOME=/home/a,FOO=a\,b,EMPTY,EQ=x=y".parse().unwrap();
+        assert_eq!(vars.get("PBS_O_HOME"), Some("/home/a"));
+        assert_eq!(vars.get("FOO"), Some("a,b"));
+        assert_eq!(vars.get("EMPTY"), Some(""));
+        assert_eq!(vars.get("EQ"), Some("x=y"));
+        let names: Vec<&str> = vars.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["PBS_O_HOME", "FOO", "EMPTY", "EQ"]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut vars = VariableList::new();
+        vars.set("A", "a,b");
+        vars.set("B", r"c\d");
+        vars.set("C", "trailing\\");
+        vars.set("D", "");
+        let s = vars.to_string();
+        assert_eq!(s, r"A=a\,b,B=c\\d,C=trailing\\,D=");
+        assert_eq!(s.parse::<VariableList>(), Ok(vars));
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let mut vars: VariableList = "A=1,B=2".parse().unwrap();
+        vars.set("A", "3");
+        assert_eq!(vars.remove("B"), Some("2".to_string()));
+        assert_eq!(vars.remove("B"), None);
+        assert_eq!(vars.to_string(), "A=3");
+    }
+
+    #[test]
+    fn missing_name() {
+        assert!("A=1,=2".parse::<VariableList>().is_err());
+    }
+}
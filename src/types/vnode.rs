@@ -0,0 +1,192 @@
+use crate::attributes::node;
+use crate::types::{Attrl, Status};
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not, Sub};
+use std::str::FromStr;
+
+/// Set of vnode states, a vnode with no states set is free
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct VnodeState(u32);
+
+// names as used in the vnode state attribute, in the order pbsnodes prints them
+const NAMES: [(VnodeState, &str); 14] = [
+    (VnodeState::OFFLINE, "offline"),
+    (VnodeState::DOWN, "down"),
+    (VnodeState::JOB_BUSY, "job-busy"),
+    (VnodeState::JOB_EXCLUSIVE, "job-exclusive"),
+    (VnodeState::RESV_EXCLUSIVE, "resv-exclusive"),
+    (VnodeState::STALE, "stale"),
+    (VnodeState::PROVISIONING, "provisioning"),
+    (VnodeState::WAIT_PROVISIONING, "wait-provisioning"),
+    (VnodeState::UNRESOLVABLE, "unresolvable"),
+    (VnodeState::SLEEP, "sleep"),
+    (VnodeState::MAINTENANCE, "maintenance"),
+    (VnodeState::BUSY, "busy"),
+    (VnodeState::UNKNOWN, "state-unknown"),
+    (VnodeState::INITIALIZING, "initializing"),
+];
+
+impl VnodeState {
+    pub const FREE: VnodeState = VnodeState(0);
+    pub const OFFLINE: VnodeState = VnodeState(1 << 0);
+    pub const DOWN: VnodeState = VnodeState(1 << 1);
+    pub const JOB_BUSY: VnodeState = VnodeState(1 << 2);
+    pub const JOB_EXCLUSIVE: VnodeState = VnodeState(1 << 3);
+    pub const RESV_EXCLUSIVE: VnodeState = VnodeState(1 << 4);
+    pub const STALE: VnodeState = VnodeState(1 << 5);
+    pub const PROVISIONING: VnodeState = VnodeState(1 << 6);
+    pub const WAIT_PROVISIONING: VnodeState = VnodeState(1 << 7);
+    pub const UNRESOLVABLE: VnodeState = VnodeState(1 << 8);
+    pub const SLEEP: VnodeState = VnodeState(1 << 9);
+    pub const MAINTENANCE: VnodeState = VnodeState(1 << 10);
+    pub const BUSY: VnodeState = VnodeState(1 << 11);
+    pub const UNKNOWN: VnodeState = VnodeState(1 << 12);
+    pub const INITIALIZING: VnodeState = VnodeState(1 << 13);
+
+    /// states an operator or manager can set and clear with pbsnodes/qmgr
+    pub const SETTABLE: VnodeState = VnodeState(
+        VnodeState::OFFLINE.0
+            | VnodeState::DOWN.0
+            | VnodeState::SLEEP.0
+            | VnodeState::MAINTENANCE.0,
+    );
+    /// states in which a vnode won't accept new jobs
+    pub const UNAVAILABLE: VnodeState = VnodeState(
+        VnodeState::OFFLINE.0
+            | VnodeState::DOWN.0
+            | VnodeState::STALE.0
+            | VnodeState::PROVISIONING.0
+            | VnodeState::WAIT_PROVISIONING.0
+            | VnodeState::UNRESOLVABLE.0
+            | VnodeState::SLEEP.0
+            | VnodeState::MAINTENANCE.0
+            | VnodeState::UNKNOWN.0
+            | VnodeState::INITIALIZING.0,
+    );
+
+    pub fn is_free(&self) -> bool {
+        self.0 == 0
+    }
+    pub fn contains(&self, other: VnodeState) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn intersects(&self, other: VnodeState) -> bool {
+        self.0 & other.0 != 0
+    }
+    pub fn insert(&mut self, other: VnodeState) {
+        self.0 |= other.0;
+    }
+    pub fn remove(&mut self, other: VnodeState) {
+        self.0 &= !other.0;
+    }
+    /// names of the individual states set
+    pub fn names(&self) -> Vec<&'static str> {
+        NAMES
+            .iter()
+            .filter(|(s, _)| self.contains(*s))
+            .map(|(_, n)| *n)
+            .collect()
+    }
+}
+
+impl BitOr for VnodeState {
+    type Output = VnodeState;
+    fn bitor(self, rhs: VnodeState) -> VnodeState {
+        VnodeState(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for VnodeState {
+    type Output = VnodeState;
+    fn bitand(self, rhs: VnodeState) -> VnodeState {
+        VnodeState(self.0 & rhs.0)
+    }
+}
+
+impl Sub for VnodeState {
+    type Output = VnodeState;
+    fn sub(self, rhs: VnodeState) -> VnodeState {
+        VnodeState(self.0 & !rhs.0)
+    }
+}
+
+impl Not for VnodeState {
+    type Output = VnodeState;
+    fn not(self) -> VnodeState {
+        let all = NAMES.iter().fold(0, |acc, (s, _)| acc | s.0);
+        VnodeState(!self.0 & all)
+    }
+}
+
+impl FromStr for VnodeState {
+    type Err = String;
+    /// parse a comma separated state list, e.g. "offline,job-busy"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut state = VnodeState::FREE;
+        for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if name == "free" {
+                continue;
+            }
+            match NAMES.iter().find(|(_, n)| *n == name) {
+                Some((st, _)) => state.insert(*st),
+                None => return Err(format!("unknown vnode state: {name}")),
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl fmt::Display for VnodeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_free() {
+            write!(f, "free")
+        } else {
+            write!(f, "{}", self.names().join(","))
+        }
+    }
+}
+
+impl Status {
+    /// state of a vnode stat, an error names a missing or unknown state
+    pub fn vnode_state(&self) -> Result<VnodeState, String> {
+        match self.attribs().get(node::STATE) {
+            Some(Attrl::Value(v)) => v
+                .val()
+                .parse()
+                .map_err(|e| format!("vnode {}: {e}", self.name())),
+            _ => Err(format!("no state found for vnode {}", self.name())),
+        }
+    }
+}
+
+/// Result of a vnode state change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VnodeStateChange {
+    pub name: String,
+    /// state before the change
+    pub before: VnodeState,
+    /// expected state once the change is applied
+    pub after: VnodeState,
+    /// false for dry runs
+    pub applied: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_print() {
+        let s: VnodeState = "job-busy, offline".parse().unwrap();
+        assert_eq!(s, VnodeState::OFFLINE | VnodeState::JOB_BUSY);
+        assert_eq!(s.to_string(), "offline,job-busy");
+        assert_eq!("free".parse(), Ok(VnodeState::FREE));
+        assert_eq!(VnodeState::FREE.to_string(), "free");
+    }
+
+    #[test]
+    fn unknown_state_is_named() {
+        let e = "offline,hibernating".parse::<VnodeState>().unwrap_err();
+        assert!(e.contains("hibernating"), "{e}");
+    }
+}
@@ -0,0 +1,447 @@
+use crate::attributes::{job, node};
+use crate::types::{Attribs, Resource, Server, StatResp, VnodeState};
+use log::{debug, trace, warn};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// object name -> flattened attributes
+type Objects = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Change between two successive stats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Appeared {
+        name: String,
+    },
+    Disappeared {
+        name: String,
+    },
+    /// any attribute change not covered by a more specific event
+    AttributeChanged {
+        name: String,
+        attribute: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// job_state changed, e.g. Q -> R
+    JobStateChanged {
+        name: String,
+        old: String,
+        new: String,
+    },
+    /// job reached F or X, or vanished before reaching them
+    JobFinished {
+        name: String,
+    },
+    VnodeStateChanged {
+        name: String,
+        old: VnodeState,
+        new: VnodeState,
+    },
+    /// vnode became down or offline
+    VnodeDown {
+        name: String,
+        state: VnodeState,
+    },
+    /// vnode is no longer down or offline
+    VnodeUp {
+        name: String,
+    },
+}
+
+fn objects(resp: &StatResp) -> Objects {
+    resp.resources
+        .iter()
+        .map(|s| (s.name(), s.attribs().flat()))
+        .collect()
+}
+
+/// Diff two stats of the same kind of object into events
+pub fn diff(kind: Resource, old: &StatResp, new: &StatResp) -> Vec<Event> {
+    diff_objects(kind, &objects(old), &objects(new), &BTreeSet::new())
+}
+
+fn diff_objects(
+    kind: Resource,
+    old: &Objects,
+    new: &Objects,
+    ignore: &BTreeSet<String>,
+) -> Vec<Event> {
+    let job_state = job::JOB_STATE;
+    let node_state = node::STATE;
+    let down = VnodeState::DOWN | VnodeState::OFFLINE;
+    let finished = |s: &str| s == "F" || s == "X";
+    let mut events = Vec::new();
+    for (name, attribs) in old {
+        if !new.contains_key(name) {
+            events.push(Event::Disappeared { name: name.clone() });
+            if kind == Resource::Job
+                && !attribs.get(job_state).map(|s| finished(s)).unwrap_or(false)
+            {
+                events.push(Event::JobFinished { name: name.clone() });
+            }
+        }
+    }
+    for (name, attribs) in new {
+        let prev = match old.get(name) {
+            Some(p) => p,
+            None => {
+                events.push(Event::Appeared { name: name.clone() });
+                continue;
+            }
+        };
+        let keys: BTreeSet<&String> = prev.keys().chain(attribs.keys()).collect();
+        for key in keys {
+            if ignore.contains(key) {
+                continue;
+            }
+            let (o, n) = (prev.get(key), attribs.get(key));
+            if o == n {
+                continue;
+            }
+            match (kind, key.as_str(), o, n) {
+                (Resource::Job, k, Some(o), Some(n)) if k == job_state => {
+                    events.push(Event::JobStateChanged {
+                        name: name.clone(),
+                        old: o.clone(),
+                        new: n.clone(),
+                    });
+                    if finished(n) && !finished(o) {
+                        events.push(Event::JobFinished { name: name.clone() });
+                    }
+                }
+                (Resource::Vnode | Resource::Hostname, k, Some(o), Some(n)) if k == node_state => {
+                    let (o, n) = match (o.parse::<VnodeState>(), n.parse::<VnodeState>()) {
+                        (Ok(o), Ok(n)) => (o, n),
+                        _ => {
+                            warn!("Can't parse state of vnode {name}: {o} -> {n}");
+                            continue;
+                        }
+                    };
+                    events.push(Event::VnodeStateChanged {
+                        name: name.clone(),
+                        old: o,
+                        new: n,
+                    });
+                    if n.intersects(down) && !o.intersects(down) {
+                        events.push(Event::VnodeDown {
+                            name: name.clone(),
+                            state: n,
+                        });
+                    } else if o.intersects(down) && !n.intersects(down) {
+                        events.push(Event::VnodeUp { name: name.clone() });
+                    }
+                }
+                _ => events.push(Event::AttributeChanged {
+                    name: name.clone(),
+                    attribute: key.clone(),
+                    old: o.cloned(),
+                    new: n.cloned(),
+                }),
+            }
+        }
+    }
+    events
+}
+
+fn stat(srv: &Server, kind: Resource) -> Result<StatResp, String> {
+    match kind {
+        Resource::Hostname => srv.stat_host(&None, None),
+        Resource::Que => srv.stat_que(&None, None),
+        // subjobs of arrays get their own events
+        Resource::Job => srv.stat_jobs_with_subjobs(Attribs::new(), None),
+        Resource::Reservation => srv.stat_reservation(&None, None),
+        Resource::Resource => srv.stat_resource(&None, None),
+        Resource::Scheduler => srv.stat_scheduler(&None, None),
+        Resource::Server => srv.stat_server(&None, None),
+        Resource::Vnode => srv.stat_vnode(&None, None),
+    }
+}
+
+/// Polls a stat and reports changes between successive results
+///
+/// The first poll only records a baseline, events start with the second. Job
+/// watchers include array subjobs.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    kind: Resource,
+    interval: Duration,
+    ignore: BTreeSet<String>,
+}
+
+impl Watcher {
+    pub fn new(kind: Resource) -> Watcher {
+        Watcher {
+            kind,
+            interval: Duration::from_secs(30),
+            ignore: BTreeSet::new(),
+        }
+    }
+    /// time between polls, defaults to 30s
+    pub fn interval(mut self, interval: Duration) -> Watcher {
+        self.interval = interval;
+        self
+    }
+    /// don't report changes to an attribute (or name.resource), e.g. resources_used.walltime
+    pub fn ignore(mut self, attribute: &str) -> Watcher {
+        self.ignore.insert(attribute.to_string());
+        self
+    }
+
+    /// blocking iterator over events, polling on `srv`
+    pub fn iter(self, srv: &Server) -> WatchIter<'_> {
+        WatchIter {
+            srv,
+            watcher: self,
+            last: None,
+            pending: VecDeque::new(),
+            next_poll: Instant::now(),
+        }
+    }
+
+    /// Poll from a background thread, sending events over a channel
+    ///
+    /// The thread stops once the returned handle is dropped, without waiting for
+    /// the next event. Stat failures are sent as errors and polling carries on.
+    pub fn spawn(self, srv: Server) -> WatchHandle {
+        let (tx, rx) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let interval = self.interval;
+        thread::spawn(move || {
+            let mut iter = self.iter(&srv);
+            loop {
+                let polled = iter
+                    .poll()
+                    .map(|_| iter.pending.drain(..).map(Ok).collect());
+                for event in polled.unwrap_or_else(|e| vec![Err(e)]) {
+                    if tx.send(event).is_err() {
+                        debug!("Watcher receiver dropped, stopping");
+                        return;
+                    }
+                }
+                if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                    debug!("Watcher handle dropped, stopping");
+                    return;
+                }
+            }
+        });
+        WatchHandle { rx, _stop: stop }
+    }
+}
+
+/// Events from Watcher::spawn, dropping it stops the polling thread
+pub struct WatchHandle {
+    rx: Receiver<Result<Event, String>>,
+    // never sent on, the thread notices it's dropped
+    _stop: Sender<()>,
+}
+
+impl WatchHandle {
+    /// wait for the next event, None if the polling thread has stopped
+    pub fn recv(&self) -> Option<Result<Event, String>> {
+        self.rx.recv().ok()
+    }
+    /// wait at most `timeout` for the next event
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<Event, String>> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+    /// next event if there is one already, Err if the polling thread has stopped
+    pub fn try_recv(&self) -> Result<Option<Result<Event, String>>, String> {
+        match self.rx.try_recv() {
+            Ok(e) => Ok(Some(e)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err("watcher thread stopped".to_string()),
+        }
+    }
+}
+
+impl Iterator for WatchHandle {
+    type Item = Result<Event, String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+/// Iterator returned by Watcher::iter, never ends on its own
+pub struct WatchIter<'a> {
+    srv: &'a Server,
+    watcher: Watcher,
+    last: Option<Objects>,
+    pending: VecDeque<Event>,
+    next_poll: Instant,
+}
+
+impl WatchIter<'_> {
+    // stat once and queue the changes since the last poll
+    fn poll(&mut self) -> Result<(), String> {
+        trace!("Watcher polling {:?}", self.watcher.kind);
+        let current = objects(&stat(self.srv, self.watcher.kind)?);
+        if let Some(last) = &self.last {
+            self.pending.extend(diff_objects(
+                self.watcher.kind,
+                last,
+                &current,
+                &self.watcher.ignore,
+            ));
+        }
+        self.last = Some(current);
+        Ok(())
+    }
+}
+
+impl Iterator for WatchIter<'_> {
+    type Item = Result<Event, String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            let now = Instant::now();
+            if now < self.next_poll {
+                thread::sleep(self.next_poll - now);
+            }
+            self.next_poll = Instant::now() + self.watcher.interval;
+            if let Err(e) = self.poll() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_text;
+
+    fn stat(text: &str) -> StatResp {
+        parse_text(text).unwrap().1
+    }
+
+    #[test]
+    fn job_changes() {
+        let old = stat(
+            "\
+Job Id: 1.svr
+    job_state = Q
+    comment = Not Running: Insufficient amount of resource: ncpus
+
+Job Id: 2.svr
+    job_state = R
+
+Job Id: 3.svr
+    job_state = R
+",
+        );
+        let new = stat(
+            "\
+Job Id: 1.svr
+    job_state = R
+    exec_vnode = (n1:ncpus=1)
+
+Job Id: 3.svr
+    job_state = F
+
+Job Id: 4.svr
+    job_state = Q
+",
+        );
+        let name = |n: &str| n.to_string();
+        assert_eq!(
+            diff(Resource::Job, &old, &new),
+            [
+                Event::Disappeared {
+                    name: name("2.svr")
+                },
+                Event::JobFinished {
+                    name: name("2.svr")
+                },
+                Event::AttributeChanged {
+                    name: name("1.svr"),
+                    attribute: name("comment"),
+                    old: Some(name("Not Running: Insufficient amount of resource: ncpus")),
+                    new: None,
+                },
+                Event::AttributeChanged {
+                    name: name("1.svr"),
+                    attribute: name("exec_vnode"),
+                    old: None,
+                    new: Some(name("(n1:ncpus=1)")),
+                },
+                Event::JobStateChanged {
+                    name: name("1.svr"),
+                    old: name("Q"),
+                    new: name("R"),
+                },
+                Event::JobStateChanged {
+                    name: name("3.svr"),
+                    old: name("R"),
+                    new: name("F"),
+                },
+                Event::JobFinished {
+                    name: name("3.svr")
+                },
+                Event::Appeared {
+                    name: name("4.svr")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finished_jobs_are_not_finished_again() {
+        let old = stat("Job Id: 1.svr\n    job_state = F\n");
+        let new = stat("Job Id: 2.svr\n    job_state = Q\n");
+        assert_eq!(
+            diff(Resource::Job, &old, &new),
+            [
+                Event::Disappeared {
+                    name: "1.svr".to_string()
+                },
+                Event::Appeared {
+                    name: "2.svr".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn vnode_down_and_up() {
+        let old = stat("n1\n     state = free\n\nn2\n     state = offline\n");
+        let new = stat("n1\n     state = offline\n\nn2\n     state = free\n");
+        let free: VnodeState = "free".parse().unwrap();
+        assert_eq!(
+            diff(Resource::Vnode, &old, &new),
+            [
+                Event::VnodeStateChanged {
+                    name: "n1".to_string(),
+                    old: free,
+                    new: VnodeState::OFFLINE,
+                },
+                Event::VnodeDown {
+                    name: "n1".to_string(),
+                    state: VnodeState::OFFLINE,
+                },
+                Event::VnodeStateChanged {
+                    name: "n2".to_string(),
+                    old: VnodeState::OFFLINE,
+                    new: free,
+                },
+                Event::VnodeUp {
+                    name: "n2".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_and_unchanged_attributes() {
+        let old = stat("n1\n     state = free\n     resources_assigned.ncpus = 0\n");
+        let new = stat("n1\n     state = free\n     resources_assigned.ncpus = 2\n");
+        assert!(diff(Resource::Vnode, &old, &old).is_empty());
+        let ignore = BTreeSet::from(["resources_assigned.ncpus".to_string()]);
+        assert!(diff_objects(Resource::Vnode, &objects(&old), &objects(&new), &ignore).is_empty());
+        assert_eq!(diff(Resource::Vnode, &old, &new).len(), 1);
+    }
+}